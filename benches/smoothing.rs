@@ -0,0 +1,86 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use main_pc_2_openrgb_client::metrics::smoothing::{
+    ExponentialMovingAverage, MovingMedian, WeightedMovingAverage, WindowAverage,
+};
+use std::hint::black_box;
+
+const WINDOWS: [usize; 3] = [10, 100, 1000];
+/// Samples per iteration: one second's worth at 1 kHz and at 10 kHz.
+const RATES: [(&str, usize); 2] = [("1kHz", 1_000), ("10kHz", 10_000)];
+
+fn samples(count: usize) -> Vec<f32> {
+    // Cheap, deterministic noise between 0.0 and 1.0.
+    (0..count)
+        .map(|index| (index as f32 * 0.618_034).fract())
+        .collect()
+}
+
+fn smoothing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("smoothing");
+
+    for (rate_name, rate) in RATES {
+        let samples = samples(rate);
+        group.throughput(Throughput::Elements(rate as u64));
+
+        for window in WINDOWS {
+            let parameter = format!("{window}/{rate_name}");
+
+            group.bench_with_input(
+                BenchmarkId::new("window_average", &parameter),
+                &samples,
+                |b, samples| {
+                    let mut smoother = WindowAverage::new(window);
+                    b.iter(|| {
+                        for &sample in samples {
+                            black_box(smoother.push(sample));
+                        }
+                    });
+                },
+            );
+
+            group.bench_with_input(
+                BenchmarkId::new("exponential_moving_average", &parameter),
+                &samples,
+                |b, samples| {
+                    let mut smoother = ExponentialMovingAverage::new(2.0 / (window as f32 + 1.0));
+                    b.iter(|| {
+                        for &sample in samples {
+                            black_box(smoother.push(sample));
+                        }
+                    });
+                },
+            );
+
+            group.bench_with_input(
+                BenchmarkId::new("weighted_moving_average", &parameter),
+                &samples,
+                |b, samples| {
+                    let mut smoother = WeightedMovingAverage::new(window);
+                    b.iter(|| {
+                        for &sample in samples {
+                            black_box(smoother.push(sample));
+                        }
+                    });
+                },
+            );
+
+            group.bench_with_input(
+                BenchmarkId::new("moving_median", &parameter),
+                &samples,
+                |b, samples| {
+                    let mut smoother = MovingMedian::new(window);
+                    b.iter(|| {
+                        for &sample in samples {
+                            black_box(smoother.push(sample));
+                        }
+                    });
+                },
+            );
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, smoothing);
+criterion_main!(benches);
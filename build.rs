@@ -0,0 +1,20 @@
+use std::process::Command;
+
+fn main() {
+    // Not fatal, e.g. when building from a source tarball.
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|commit| commit.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=GIT_COMMIT={commit}");
+
+    let built = chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ");
+    println!("cargo:rustc-env=BUILD_TIMESTAMP={built}");
+
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+}
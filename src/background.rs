@@ -0,0 +1,64 @@
+use daemonize::Daemonize;
+use nix::{sys::signal, unistd::Pid};
+use std::{
+    env,
+    error::Error,
+    fs::{self, OpenOptions},
+    path::{Path, PathBuf},
+};
+use tracing::warn;
+
+const FILE_STEM: &str = "main_pc_2_openrgb_client";
+
+/// `$XDG_RUNTIME_DIR/main_pc_2_openrgb_client.pid`, or in the temp dir without a runtime dir.
+pub fn default_pid_file() -> PathBuf {
+    runtime_dir().join(format!("{FILE_STEM}.pid"))
+}
+
+/// `$XDG_RUNTIME_DIR/main_pc_2_openrgb_client.log`, or in the temp dir without a runtime dir.
+pub fn default_log_file() -> PathBuf {
+    runtime_dir().join(format!("{FILE_STEM}.log"))
+}
+
+fn runtime_dir() -> PathBuf {
+    env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(env::temp_dir)
+}
+
+/// Forks into the background, the parent exits and only the detached child returns.
+/// Anything written to stdout and stderr, the log included, ends up in `log_file`.
+pub fn detach(pid_file: &Path, log_file: &Path) -> Result<(), Box<dyn Error>> {
+    let log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_file)?;
+
+    Daemonize::new()
+        .pid_file(pid_file)
+        // Keep relative config paths working.
+        .working_directory(env::current_dir()?)
+        .stdout(log.try_clone()?)
+        .stderr(log)
+        .start()?;
+
+    Ok(())
+}
+
+/// Asks the instance in `pid_file` to shut down gracefully, like the service manager would.
+pub fn stop(pid_file: &Path) -> Result<(), Box<dyn Error>> {
+    let pid = fs::read_to_string(pid_file)
+        .map_err(|e| format!("Can't read PID file {}: {e}", pid_file.display()))?;
+    let pid = Pid::from_raw(pid.trim().parse()?);
+
+    signal::kill(pid, signal::Signal::SIGTERM)?;
+
+    Ok(())
+}
+
+/// Removes the PID file on the way out, it's only meaningful while the process runs.
+pub fn remove_pid_file(pid_file: &Path) {
+    if let Err(e) = fs::remove_file(pid_file) {
+        warn!("Failed to remove PID file {}: {e}", pid_file.display());
+    }
+}
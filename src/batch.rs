@@ -0,0 +1,123 @@
+use openrgb::data::Color;
+use std::{collections::BTreeMap, time::Duration};
+use tokio::time::Instant;
+use tracing::debug;
+
+/// Flushed early once this many updates were queued, coalesced ones included.
+const MAX_QUEUED_UPDATES: usize = 64;
+
+/// A controller's colors waiting for the next flush.
+pub struct PendingUpdate {
+    pub controller_id: u32,
+    pub controller_name: String,
+    pub zone_led_counts: Vec<usize>,
+    pub colors: Vec<Color>,
+}
+
+/// Holds the color updates back and sends them together at most every flush interval. A
+/// controller updated twice before a flush is only sent its latest colors.
+pub struct UpdateBatch {
+    flush_interval: Duration,
+    last_flush: Instant,
+    pending: Vec<PendingUpdate>,
+    queued: usize,
+    /// How many flushes sent each number of updates.
+    batch_size_histogram: BTreeMap<usize, u64>,
+}
+
+impl UpdateBatch {
+    pub fn new(flush_interval: Duration) -> Self {
+        Self {
+            flush_interval,
+            last_flush: Instant::now(),
+            pending: Vec::new(),
+            queued: 0,
+            batch_size_histogram: BTreeMap::new(),
+        }
+    }
+
+    pub fn push(&mut self, update: PendingUpdate) {
+        self.queued += 1;
+
+        match self
+            .pending
+            .iter_mut()
+            .find(|pending| pending.controller_id == update.controller_id)
+        {
+            Some(pending) => *pending = update,
+            None => self.pending.push(update),
+        }
+    }
+
+    /// Whether the flush interval is over or the batch is full.
+    pub fn is_due(&self) -> bool {
+        !self.pending.is_empty()
+            && (self.last_flush.elapsed() >= self.flush_interval
+                || self.queued >= MAX_QUEUED_UPDATES)
+    }
+
+    /// Empties the batch, returning the updates to send.
+    pub fn take(&mut self) -> Vec<PendingUpdate> {
+        let updates = std::mem::take(&mut self.pending);
+        *self.batch_size_histogram.entry(updates.len()).or_default() += 1;
+        debug!(
+            "Flushing {} updates ({} queued), batch sizes so far: {:?}",
+            updates.len(),
+            self.queued,
+            self.batch_size_histogram
+        );
+
+        self.queued = 0;
+        self.last_flush = Instant::now();
+
+        updates
+    }
+
+    /// Drops the queued updates, e.g. when the connection they were meant for is gone.
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.queued = 0;
+    }
+
+    pub fn batch_size_histogram(&self) -> &BTreeMap<usize, u64> {
+        &self.batch_size_histogram
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(controller_id: u32, red: u8) -> PendingUpdate {
+        PendingUpdate {
+            controller_id,
+            controller_name: format!("Controller {controller_id}"),
+            zone_led_counts: vec![2],
+            colors: vec![Color::new(red, 0, 0); 2],
+        }
+    }
+
+    #[tokio::test]
+    async fn updates_within_an_interval_go_out_in_one_flush() {
+        let interval = Duration::from_millis(50);
+        let mut batch = UpdateBatch::new(interval);
+
+        batch.push(update(0, 1));
+        batch.push(update(1, 2));
+        // Only the latest colors of a controller updated twice.
+        batch.push(update(0, 3));
+        assert!(!batch.is_due());
+
+        tokio::time::sleep(interval).await;
+        assert!(batch.is_due());
+        let updates = batch.take();
+
+        let sent: Vec<(u32, Color)> = updates
+            .iter()
+            .map(|update| (update.controller_id, update.colors[0]))
+            .collect();
+        assert_eq!(sent, [(0, Color::new(3, 0, 0)), (1, Color::new(2, 0, 0))]);
+        assert_eq!(batch.batch_size_histogram(), &BTreeMap::from([(2, 1)]));
+        assert!(!batch.is_due());
+    }
+}
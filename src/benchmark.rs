@@ -0,0 +1,162 @@
+//! `--benchmark-leds`: how many updates per second a controller takes.
+
+use crate::pool::Stream;
+use openrgb::{data::Color, OpenRGB};
+use std::{error::Error, time::Duration};
+use tokio::time::Instant;
+
+/// The packet header, then the data size and the color count before the colors.
+const UPDATE_LEDS_OVERHEAD_BYTES: usize = 16 + 4 + 2;
+
+pub struct BenchmarkResult {
+    pub updates: usize,
+    pub duration: Duration,
+    /// Of every `update_leds` call, sorted.
+    pub latencies: Vec<Duration>,
+    pub bytes_sent: usize,
+}
+
+impl BenchmarkResult {
+    pub fn updates_per_second(&self) -> f64 {
+        self.updates as f64 / self.duration.as_secs_f64()
+    }
+
+    pub fn average_latency(&self) -> Duration {
+        let total: Duration = self.latencies.iter().sum();
+        total / self.latencies.len().max(1) as u32
+    }
+
+    pub fn p99_latency(&self) -> Duration {
+        p99_latency(&self.latencies)
+    }
+
+    /// The results as a two-column table.
+    pub fn table(&self) -> String {
+        let rows = [
+            ("Total updates", self.updates.to_string()),
+            ("Updates/sec", format!("{:.1}", self.updates_per_second())),
+            (
+                "Average latency",
+                format!("{:.3} ms", self.average_latency().as_secs_f64() * 1000.0),
+            ),
+            (
+                "p99 latency",
+                format!("{:.3} ms", self.p99_latency().as_secs_f64() * 1000.0),
+            ),
+            ("Bytes sent", self.bytes_sent.to_string()),
+        ];
+        let width = rows
+            .iter()
+            .map(|(name, _)| name.len())
+            .max()
+            .unwrap_or_default();
+
+        rows.iter()
+            .map(|(name, value)| format!("{name:<width$}  {value:>12}\n"))
+            .collect()
+    }
+}
+
+/// The 99th percentile of sorted `latencies`.
+pub fn p99_latency(latencies: &[Duration]) -> Duration {
+    let index = (latencies.len().saturating_sub(1) as f64 * 0.99).round() as usize;
+    latencies.get(index).copied().unwrap_or_default()
+}
+
+/// Sends `update_leds` to `controller_name` back to back for `duration`, alternating between
+/// white and black so the device has something to do, then puts its colors back.
+pub async fn run(
+    client: &OpenRGB<Stream>,
+    controller_name: &str,
+    duration: Duration,
+) -> Result<BenchmarkResult, Box<dyn Error>> {
+    let mut found = None;
+    for controller_id in 0..client.get_controller_count().await? {
+        let controller = client.get_controller(controller_id).await?;
+        if controller.name == controller_name {
+            found = Some((controller_id, controller));
+            break;
+        }
+    }
+    let (controller_id, controller) =
+        found.ok_or_else(|| format!("the server has no controller {controller_name:?}"))?;
+
+    let led_count = controller.leds.len();
+    let frames =
+        [Color::new(255, 255, 255), Color::new(0, 0, 0)].map(|color| vec![color; led_count]);
+
+    let mut latencies = Vec::new();
+    let started = Instant::now();
+    while started.elapsed() < duration {
+        let colors = frames[latencies.len() % frames.len()].clone();
+
+        let call_started = Instant::now();
+        let result = client.update_leds(controller_id, colors).await;
+        latencies.push(call_started.elapsed());
+
+        if let Err(e) = result {
+            client.update_leds(controller_id, controller.colors).await?;
+            return Err(e.into());
+        }
+    }
+    let elapsed = started.elapsed();
+
+    client.update_leds(controller_id, controller.colors).await?;
+
+    latencies.sort();
+    Ok(BenchmarkResult {
+        updates: latencies.len(),
+        duration: elapsed,
+        bytes_sent: latencies.len() * (UPDATE_LEDS_OVERHEAD_BYTES + 4 * led_count),
+        latencies,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        mock_server::{MockServer, UPDATE_LEDS},
+        pool::ConnectionPool,
+    };
+
+    #[tokio::test]
+    async fn runs_for_the_duration_and_prints_every_column() {
+        let server = MockServer::start(3, 1).await;
+        let pool = ConnectionPool::new([server.address()], "test");
+        let client = pool.acquire(0).await.unwrap();
+
+        let duration = Duration::from_millis(200);
+        let result = run(&client, "Controller 0", duration).await.unwrap();
+        assert!(result.duration >= duration, "{:?}", result.duration);
+        assert!(result.duration < duration * 2, "{:?}", result.duration);
+        assert!(result.updates > 0);
+        assert_eq!(result.latencies.len(), result.updates);
+
+        // The updates, then putting the colors back. They aren't answered, so they may still be on
+        // their way.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let update_count = server
+            .packets()
+            .iter()
+            .filter(|packet| packet.packet_id == UPDATE_LEDS)
+            .count();
+        assert_eq!(update_count, result.updates + 1);
+
+        let table = result.table();
+        let columns: Vec<&str> = table
+            .lines()
+            .map(|line| line.split("  ").next().unwrap().trim())
+            .collect();
+        assert_eq!(
+            columns,
+            [
+                "Total updates",
+                "Updates/sec",
+                "Average latency",
+                "p99 latency",
+                "Bytes sent"
+            ]
+        );
+    }
+}
@@ -0,0 +1,33 @@
+use main_pc_2_openrgb_client::color_history::read_record;
+use std::{env, error::Error, fs::File, io::BufReader};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let Some(path) = env::args().nth(1) else {
+        eprintln!("Usage: color_history_dump <path>");
+        std::process::exit(2);
+    };
+
+    let mut reader = BufReader::new(File::open(path)?);
+
+    println!(
+        "{:>15} {:>10} {:>5}  colors",
+        "timestamp_ms", "controller", "leds"
+    );
+    while let Some(record) = read_record(&mut reader)? {
+        let colors: Vec<String> = record
+            .colors
+            .iter()
+            .map(|color| format!("#{:02X}{:02X}{:02X}", color.r, color.g, color.b))
+            .collect();
+
+        println!(
+            "{:>15} {:>10} {:>5}  {}",
+            record.timestamp_ms,
+            record.controller_id,
+            record.colors.len(),
+            colors.join(" ")
+        );
+    }
+
+    Ok(())
+}
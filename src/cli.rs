@@ -0,0 +1,226 @@
+use crate::{
+    color::ColorSpace,
+    logging::{LogFormat, LoggerConfig},
+    metrics::TemperatureUnit,
+};
+use clap::{Parser, Subcommand};
+use clap_complete::Shell;
+#[cfg(unix)]
+use std::path::PathBuf;
+use tracing_subscriber::filter::LevelFilter;
+
+/// e.g. `0.1.0 (commit abc1234, built 2024-07-01T12:00:00Z)`, see `build.rs`.
+pub const VERSION: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    " (commit ",
+    env!("GIT_COMMIT"),
+    ", built ",
+    env!("BUILD_TIMESTAMP"),
+    ")"
+);
+
+#[derive(Parser)]
+#[command(version = VERSION, about)]
+pub struct Cli {
+    /// Log level (error, warn, info, debug or trace), overrides `RUST_LOG`.
+    #[arg(long, value_name = "LEVEL")]
+    pub log_level: Option<LevelFilter>,
+
+    #[arg(long, value_enum, default_value_t)]
+    pub log_format: LogFormat,
+
+    /// Leave the timestamps out of the log.
+    #[arg(long)]
+    pub log_without_timestamps: bool,
+
+    /// Leave the module each message comes from out of the log.
+    #[arg(long)]
+    pub log_without_target: bool,
+
+    /// Export the tracing spans to this OTLP collector, e.g. `http://localhost:4317`.
+    #[cfg(feature = "otel")]
+    #[arg(long, value_name = "URL")]
+    pub otlp_endpoint: Option<String>,
+
+    /// Unit temperatures are logged and exported in, overrides `temperature_unit` in the config.
+    #[arg(long, value_enum)]
+    pub temp_unit: Option<TemperatureUnit>,
+
+    /// Detach into the background, logging to the log file.
+    #[cfg(unix)]
+    #[arg(long)]
+    pub daemon: bool,
+
+    /// Stop the instance started with `--daemon`.
+    #[cfg(unix)]
+    #[arg(long, conflicts_with = "daemon")]
+    pub stop: bool,
+
+    /// PID file of the background instance [default: $XDG_RUNTIME_DIR/main_pc_2_openrgb_client.pid]
+    #[cfg(unix)]
+    #[arg(long)]
+    pub pid_file: Option<PathBuf>,
+
+    /// Log file of the background instance [default: $XDG_RUNTIME_DIR/main_pc_2_openrgb_client.log]
+    #[cfg(unix)]
+    #[arg(long, requires = "daemon")]
+    pub log_file: Option<PathBuf>,
+
+    /// List the sensors usable in `temperature` metrics, then exit.
+    #[arg(long)]
+    pub list_sensors: bool,
+
+    /// Exit at startup when a metric in the config isn't available on this machine.
+    #[arg(long)]
+    pub strict: bool,
+
+    /// Exit when the OpenRGB server has no controllers for this many seconds.
+    #[arg(long, value_name = "SECS", num_args = 0..=1, default_missing_value = "30")]
+    pub require_devices: Option<u64>,
+
+    /// Extract the N most frequent colors from the color history into a palette file, then exit.
+    #[arg(long, num_args = 2, value_names = ["N", "OUTPUT"])]
+    pub extract_palette: Option<Vec<String>>,
+
+    /// Tune the gradient of a controller in the config interactively, then exit.
+    #[arg(long, value_name = "CONTROLLER_NAME")]
+    pub edit_gradient: Option<String>,
+
+    /// Measure how many updates per second a controller takes, then exit.
+    #[arg(long, value_name = "CONTROLLER_NAME")]
+    pub benchmark_leds: Option<String>,
+
+    /// Send random colors to every controller as fast as possible and print the update rate and
+    /// errors every second, then exit. Fails when over 1 % of the updates failed.
+    #[arg(long, conflicts_with = "benchmark_leds")]
+    pub stress_test: bool,
+
+    /// Render a single frame with the current metrics, then exit.
+    #[arg(long)]
+    pub once: bool,
+
+    /// With `--once`, put the controllers back in the modes they had before afterwards.
+    #[arg(long, requires = "once")]
+    pub restore: bool,
+
+    /// Print the completion script for a shell, then exit. Controller names are completed with
+    /// those on the OpenRGB server, or those in the config file when it isn't running.
+    #[arg(long, value_name = "SHELL")]
+    pub generate_completions: Option<Shell>,
+
+    /// How long `--benchmark-leds` and `--stress-test` run.
+    #[arg(long, default_value_t = 10)]
+    pub duration_secs: u64,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl Cli {
+    pub fn logger_config(&self) -> LoggerConfig {
+        LoggerConfig {
+            level: self.log_level,
+            format: self.log_format,
+            timestamps: !self.log_without_timestamps,
+            target: !self.log_without_target,
+            #[cfg(feature = "otel")]
+            otlp_endpoint: self.otlp_endpoint.clone(),
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Flash a controller a few times to find out which device it is, then restore its colors.
+    Identify {
+        /// The controller's index on the OpenRGB server.
+        index: u32,
+    },
+
+    /// Walk a white LED through every controller, then flash them red, green and blue, to check
+    /// LED counts, ordering and channel order. Restores their colors afterwards.
+    Test,
+
+    /// Print a gradient as a bar of colored cells, to tune its stops without the hardware.
+    PreviewGradient {
+        /// Stops written as `#RRGGBB:position`, e.g. `#000000:0.0,#FF0000:0.5,#FFFF00:1.0`.
+        #[arg(
+            long,
+            required_unless_present = "colormap",
+            conflicts_with = "colormap"
+        )]
+        stops: Option<String>,
+
+        /// A matplotlib colormap instead of stops: viridis, plasma, inferno or magma.
+        #[arg(long)]
+        colormap: Option<String>,
+
+        /// Width of the bar in characters.
+        #[arg(long, default_value_t = 80)]
+        width: usize,
+
+        #[arg(long, value_enum, default_value_t)]
+        color_space: ColorSpace,
+    },
+
+    /// Manage the Windows service.
+    #[cfg(windows)]
+    Service {
+        #[command(subcommand)]
+        action: ServiceAction,
+    },
+}
+
+#[cfg(windows)]
+#[derive(Subcommand)]
+pub enum ServiceAction {
+    /// Register the service so it starts at boot.
+    Install,
+    /// Stop and remove the service.
+    Uninstall,
+    /// Run as the service, only meant to be started by the service control manager.
+    Run,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{error::ErrorKind, CommandFactory};
+
+    #[test]
+    fn version_has_the_commit_and_build_time() {
+        let error = Cli::try_parse_from([env!("CARGO_PKG_NAME"), "--version"])
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), ErrorKind::DisplayVersion);
+        let output = Cli::command().render_version();
+
+        // e.g. `main_pc_2_openrgb_client 0.1.0 (commit abc1234, built 2024-07-01T12:00:00Z)`.
+        let rest = output
+            .trim_end()
+            .strip_prefix(concat!(env!("CARGO_PKG_NAME"), " "))
+            .unwrap();
+        let (version, rest) = rest.split_once(" (commit ").unwrap();
+        let parts: Vec<&str> = version.split('.').collect();
+        assert_eq!(parts.len(), 3, "{version}");
+        assert!(
+            parts.iter().all(|part| part.parse::<u32>().is_ok()),
+            "{version}"
+        );
+
+        let (commit, built) = rest
+            .strip_suffix(')')
+            .unwrap()
+            .split_once(", built ")
+            .unwrap();
+        assert!(
+            commit == "unknown"
+                || commit.len() >= 7 && commit.chars().all(|c| c.is_ascii_hexdigit()),
+            "{commit}"
+        );
+        assert!(
+            chrono::NaiveDateTime::parse_from_str(built, "%Y-%m-%dT%H:%M:%SZ").is_ok(),
+            "{built}"
+        );
+    }
+}
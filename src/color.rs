@@ -0,0 +1,354 @@
+mod space;
+mod temperature;
+
+use openrgb::data::Color;
+use serde::{Deserialize, Serialize};
+
+pub use space::ColorSpace;
+pub use temperature::{
+    color_temperature_to_rgb, effective_kelvin, white_point, ColorTemperatureSchedule,
+};
+
+pub const WHITE_COLOR: Color = Color::new(127, 127, 127);
+pub const RED_COLOR: Color = Color::new(127, 0, 0);
+
+pub fn lerp(value: f32, start: f32, end: f32) -> f32 {
+    let value = value.clamp(0.0, 1.0);
+    start + value * (end - start)
+}
+
+pub fn lerp_color(value: f32, start_color: &Color, end_color: &Color) -> Color {
+    Color::new(
+        lerp(value, start_color.r as f32, end_color.r as f32).round() as u8,
+        lerp(value, start_color.g as f32, end_color.g as f32).round() as u8,
+        lerp(value, start_color.b as f32, end_color.b as f32).round() as u8,
+    )
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlendMode {
+    /// Crossfades to the top color.
+    #[default]
+    #[serde(alias = "normal")]
+    Mix,
+    Add,
+    Max,
+    Multiply,
+    /// The inverse of multiplying the inverses, brightens.
+    Screen,
+    /// Multiplies dark base colors and screens bright ones, boosting contrast.
+    Overlay,
+}
+
+/// Combines `top` over `base` with `mode`, then crossfades from `base` to the result by `mix`.
+pub fn blend_color(base: &Color, top: &Color, mode: BlendMode, mix: f32) -> Color {
+    let blend_channel = |base: u8, top: u8| -> f32 {
+        let (base, top) = (base as f32, top as f32);
+        let combined = match mode {
+            BlendMode::Mix => top,
+            BlendMode::Add => base + top,
+            BlendMode::Max => base.max(top),
+            BlendMode::Multiply => base * top / 255.0,
+            BlendMode::Screen => 255.0 - (255.0 - base) * (255.0 - top) / 255.0,
+            BlendMode::Overlay if base < 128.0 => 2.0 * base * top / 255.0,
+            BlendMode::Overlay => 255.0 - 2.0 * (255.0 - base) * (255.0 - top) / 255.0,
+        };
+
+        lerp(mix, base, combined.clamp(0.0, 255.0)).round()
+    };
+
+    Color::new(
+        blend_channel(base.r, top.r) as u8,
+        blend_channel(base.g, top.g) as u8,
+        blend_channel(base.b, top.b) as u8,
+    )
+}
+
+pub struct ColorMixer;
+
+impl ColorMixer {
+    /// Blends `b` over `a` LED by LED, see `blend_color`. The result is as long as the shorter one.
+    pub fn blend(a: &[Color], b: &[Color], mode: BlendMode, mix: f32) -> Vec<Color> {
+        a.iter()
+            .zip(b)
+            .map(|(a, b)| blend_color(a, b, mode, mix))
+            .collect()
+    }
+}
+
+/// A color at a position along a palette, from 0.0 to 1.0.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ColorStop {
+    pub position: f32,
+    #[serde(with = "hex_color")]
+    pub color: Color,
+}
+
+/// The color at `position` along `stops`, which are sorted by position.
+pub fn sample_palette(stops: &[ColorStop], position: f32) -> Color {
+    lerp_color_stops(stops, position, ColorSpace::Rgb)
+}
+
+/// `sample_palette`, interpolating in `color_space`.
+pub fn lerp_color_stops(stops: &[ColorStop], position: f32, color_space: ColorSpace) -> Color {
+    let Some(first) = stops.first() else {
+        return Color::new(0, 0, 0);
+    };
+
+    let mut previous = first;
+    for stop in stops {
+        if position <= stop.position {
+            let span = stop.position - previous.position;
+            let t = if span > 0.0 {
+                (position - previous.position) / span
+            } else {
+                1.0
+            };
+            return color_space.lerp(t, &previous.color, &stop.color);
+        }
+        previous = stop;
+    }
+
+    previous.color
+}
+
+/// Interpolates the corresponding stops of two palettes with the same number of stops.
+pub fn blend_palettes(a: &[ColorStop], b: &[ColorStop], t: f32) -> Vec<ColorStop> {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| ColorStop {
+            position: lerp(t, a.position, b.position),
+            color: lerp_color(t, &a.color, &b.color),
+        })
+        .collect()
+}
+
+pub fn generate_gradient_led_colors(
+    value: f32,
+    start_color: &Color,
+    end_color: &Color,
+    size: usize,
+    color_space: ColorSpace,
+) -> Vec<Color> {
+    let scaled_value = value * size as f32;
+
+    (0..size)
+        .map(|index| {
+            color_space.lerp(
+                (scaled_value - index as f32).clamp(0.0, 1.0),
+                start_color,
+                end_color,
+            )
+        })
+        .collect()
+}
+
+pub fn generate_block_led_colors(
+    value: f32,
+    start_color: &Color,
+    end_color: &Color,
+    size: usize,
+    color_space: ColorSpace,
+) -> Vec<Color> {
+    vec![color_space.lerp(value, start_color, end_color); size]
+}
+
+/// Scales each channel by its factor in `white_balance` (red, green, blue), clamping to the
+/// channel range.
+pub fn apply_white_balance(colors: &mut [Color], white_balance: [f32; 3]) {
+    scale_colors(colors, white_balance);
+}
+
+/// Multiplies the red, green and blue channels of `colors` by `factors`, clamping to the channel
+/// range. Used for brightness and the color temperature schedule.
+pub fn scale_colors(colors: &mut [Color], factors: [f32; 3]) {
+    let [red, green, blue] = factors;
+    let scale =
+        |channel: u8, factor: f32| (channel as f32 * factor).round().clamp(0.0, 255.0) as u8;
+
+    for color in colors {
+        *color = Color::new(
+            scale(color.r, red),
+            scale(color.g, green),
+            scale(color.b, blue),
+        );
+    }
+}
+
+/// Parses a color written as `#RRGGBB`.
+pub fn parse_hex_color(text: &str) -> Option<Color> {
+    let hex = text.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+
+    let channel = |index: usize| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok();
+
+    Some(Color::new(channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// (De)serializes a color as `#RRGGBB`, for use with `#[serde(with = "hex_color")]`.
+pub mod hex_color {
+    use super::parse_hex_color;
+    use openrgb::data::Color;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(color: &Color, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&format!("#{:02X}{:02X}{:02X}", color.r, color.g, color.b))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Color, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let text = String::deserialize(deserializer)?;
+
+        parse_hex_color(&text)
+            .ok_or_else(|| de::Error::custom(format!("invalid color {text:?}, expected #RRGGBB")))
+    }
+
+    /// For `Option<Color>` fields, which need `#[serde(default)]` too.
+    pub mod option {
+        use openrgb::data::Color;
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S>(color: &Option<Color>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            match color {
+                Some(color) => super::serialize(color, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Color>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            #[derive(Deserialize)]
+            struct HexColor(#[serde(with = "super")] Color);
+
+            Ok(Option::<HexColor>::deserialize(deserializer)?.map(|HexColor(color)| color))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn white_balance_of_one_is_a_no_op() {
+        let original = vec![Color::new(0, 0, 0), Color::new(12, 128, 255)];
+        let mut colors = original.clone();
+
+        apply_white_balance(&mut colors, [1.0, 1.0, 1.0]);
+
+        assert_eq!(colors, original);
+    }
+
+    #[test]
+    fn white_balance_clamps_to_the_channel_range() {
+        let mut colors = vec![Color::new(200, 100, 200)];
+
+        apply_white_balance(&mut colors, [2.0, -1.0, 0.5]);
+
+        assert_eq!(colors, vec![Color::new(255, 0, 100)]);
+    }
+
+    #[test]
+    fn scale_colors_scales_every_channel() {
+        let mut colors = vec![Color::new(200, 100, 50)];
+
+        scale_colors(&mut colors, [0.5; 3]);
+
+        assert_eq!(colors, vec![Color::new(100, 50, 25)]);
+    }
+
+    #[test]
+    fn color_stop_round_trips_within_an_led_step() {
+        let stop = ColorStop {
+            position: 0.3,
+            color: Color::new(0x12, 0xAB, 0xFF),
+        };
+
+        let json = serde_json::to_string(&stop).unwrap();
+        assert!(json.contains("\"#12ABFF\""));
+        let parsed: ColorStop = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.color, stop.color);
+        assert!((parsed.position - stop.position).abs() < 1.0 / 255.0);
+
+        let toml = toml::to_string(&stop).unwrap();
+        let parsed: ColorStop = toml::from_str(&toml).unwrap();
+        assert_eq!(parsed.color, stop.color);
+        assert!((parsed.position - stop.position).abs() < 1.0 / 255.0);
+    }
+
+    #[test]
+    fn blended_palettes_start_and_end_at_their_palettes() {
+        let stop = |position, color| ColorStop { position, color };
+        let a = [
+            stop(0.0, Color::new(0, 0, 0)),
+            stop(0.5, Color::new(255, 0, 0)),
+            stop(1.0, Color::new(255, 255, 0)),
+        ];
+        let b = [
+            stop(0.0, Color::new(0, 0, 255)),
+            stop(0.25, Color::new(0, 255, 0)),
+            stop(1.0, Color::new(255, 255, 255)),
+        ];
+
+        assert_eq!(blend_palettes(&a, &b, 0.0), a);
+        assert_eq!(blend_palettes(&a, &b, 1.0), b);
+    }
+
+    #[test]
+    fn blend_modes_follow_their_definitions() {
+        let base = Color::new(100, 200, 50);
+        let top = Color::new(50, 100, 255);
+
+        for (mode, expected) in [
+            (BlendMode::Mix, Color::new(50, 100, 255)),
+            // Clamped at 255.
+            (BlendMode::Add, Color::new(150, 255, 255)),
+            (BlendMode::Max, Color::new(100, 200, 255)),
+            // base * top / 255
+            (BlendMode::Multiply, Color::new(20, 78, 50)),
+            // 255 - (255 - base) * (255 - top) / 255
+            (BlendMode::Screen, Color::new(130, 222, 255)),
+            // Multiplied twice below 128, screened twice from 128 on.
+            (BlendMode::Overlay, Color::new(39, 188, 100)),
+        ] {
+            assert_eq!(blend_color(&base, &top, mode, 1.0), expected, "{mode:?}");
+            assert_eq!(blend_color(&base, &top, mode, 0.0), base, "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn mixer_crossfades_each_led() {
+        let a = [Color::new(100, 200, 50), Color::new(0, 0, 0)];
+        let b = [Color::new(50, 100, 255), Color::new(255, 255, 255)];
+
+        assert_eq!(
+            ColorMixer::blend(&a, &b, BlendMode::Mix, 0.5),
+            vec![Color::new(75, 150, 153), Color::new(128, 128, 128)]
+        );
+        assert_eq!(
+            ColorMixer::blend(&a, &b[..1], BlendMode::Add, 1.0),
+            vec![Color::new(150, 255, 255)]
+        );
+    }
+
+    #[test]
+    fn invalid_hex_colors_are_rejected() {
+        assert_eq!(parse_hex_color("#010203"), Some(Color::new(1, 2, 3)));
+        assert_eq!(parse_hex_color("010203"), None);
+        assert_eq!(parse_hex_color("#0102"), None);
+        assert_eq!(parse_hex_color("#01020G"), None);
+    }
+}
@@ -0,0 +1,218 @@
+//! Interpolation in other color spaces than plain sRGB.
+
+use super::lerp;
+use clap::ValueEnum;
+use openrgb::data::Color;
+use serde::{Deserialize, Serialize};
+
+/// The color space gradients are interpolated in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorSpace {
+    /// Straight between the sRGB channels, cheap but dull halfway between complementary colors.
+    #[default]
+    Rgb,
+    /// Around the hue circle the short way, keeping the colors saturated.
+    Hsv,
+    /// CIELAB, perceptually even steps.
+    Lab,
+    /// OKLab, perceptually even steps without CIELAB's hue shifts, e.g. blue turning purple.
+    Oklab,
+}
+
+impl ColorSpace {
+    /// The color at `value`, from 0.0 to 1.0, between `start` and `end`.
+    pub fn lerp(self, value: f32, start: &Color, end: &Color) -> Color {
+        match self {
+            ColorSpace::Rgb => super::lerp_color(value, start, end),
+            ColorSpace::Hsv => {
+                let (start, end) = (rgb_to_hsv(start), rgb_to_hsv(end));
+                let mut hue_delta = end[0] - start[0];
+                if hue_delta > 180.0 {
+                    hue_delta -= 360.0;
+                } else if hue_delta < -180.0 {
+                    hue_delta += 360.0;
+                }
+
+                hsv_to_rgb([
+                    (start[0] + hue_delta * value.clamp(0.0, 1.0)).rem_euclid(360.0),
+                    lerp(value, start[1], end[1]),
+                    lerp(value, start[2], end[2]),
+                ])
+            }
+            ColorSpace::Lab => {
+                let (start, end) = (rgb_to_lab(start), rgb_to_lab(end));
+                lab_to_rgb([0, 1, 2].map(|channel| lerp(value, start[channel], end[channel])))
+            }
+            ColorSpace::Oklab => {
+                let (start, end) = (rgb_to_oklab(start), rgb_to_oklab(end));
+                oklab_to_rgb([0, 1, 2].map(|channel| lerp(value, start[channel], end[channel])))
+            }
+        }
+    }
+}
+
+/// Hue in degrees, saturation and value from 0.0 to 1.0.
+fn rgb_to_hsv(color: &Color) -> [f32; 3] {
+    let [r, g, b] = [color.r, color.g, color.b].map(|channel| channel as f32 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+
+    [hue, saturation, max]
+}
+
+fn hsv_to_rgb([hue, saturation, value]: [f32; 3]) -> Color {
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - ((hue / 60.0).rem_euclid(2.0) - 1.0).abs());
+    let (r, g, b) = match hue as u32 / 60 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = value - chroma;
+
+    to_color([r + m, g + m, b + m])
+}
+
+// D65 white point.
+const WHITE: [f32; 3] = [0.950_47, 1.0, 1.088_83];
+
+fn rgb_to_lab(color: &Color) -> [f32; 3] {
+    let [r, g, b] =
+        [color.r, color.g, color.b].map(|channel| srgb_to_linear(channel as f32 / 255.0));
+    let xyz = [
+        0.412_456_4 * r + 0.357_576_1 * g + 0.180_437_5 * b,
+        0.212_672_9 * r + 0.715_152_2 * g + 0.072_175 * b,
+        0.019_333_9 * r + 0.119_192 * g + 0.950_304_1 * b,
+    ];
+
+    let f = |t: f32| {
+        if t > 216.0 / 24389.0 {
+            t.cbrt()
+        } else {
+            (24389.0 / 27.0 * t + 16.0) / 116.0
+        }
+    };
+    let [fx, fy, fz] = [0, 1, 2].map(|axis| f(xyz[axis] / WHITE[axis]));
+
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+fn lab_to_rgb([l, a, b]: [f32; 3]) -> Color {
+    let fy = (l + 16.0) / 116.0;
+    let fx = fy + a / 500.0;
+    let fz = fy - b / 200.0;
+
+    let f_inverse = |t: f32| {
+        if t.powi(3) > 216.0 / 24389.0 {
+            t.powi(3)
+        } else {
+            (116.0 * t - 16.0) / (24389.0 / 27.0)
+        }
+    };
+    let [x, y, z] = [fx, fy, fz].map(f_inverse);
+    let [x, y, z] = [x * WHITE[0], y * WHITE[1], z * WHITE[2]];
+
+    let linear = [
+        3.240_454_2 * x - 1.537_138_5 * y - 0.498_531_4 * z,
+        -0.969_266 * x + 1.876_010_8 * y + 0.041_556 * z,
+        0.055_643_4 * x - 0.204_025_9 * y + 1.057_225_2 * z,
+    ];
+
+    to_color(linear.map(linear_to_srgb))
+}
+
+fn rgb_to_oklab(color: &Color) -> [f32; 3] {
+    let [r, g, b] =
+        [color.r, color.g, color.b].map(|channel| srgb_to_linear(channel as f32 / 255.0));
+    let lms = [
+        0.412_221_5 * r + 0.536_332_5 * g + 0.051_446 * b,
+        0.211_903_5 * r + 0.680_699_5 * g + 0.107_397 * b,
+        0.088_302_5 * r + 0.281_718_8 * g + 0.629_978_7 * b,
+    ];
+    let [l, m, s] = lms.map(f32::cbrt);
+
+    [
+        0.210_454_3 * l + 0.793_617_8 * m - 0.004_072_047 * s,
+        1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s,
+        0.025_904_04 * l + 0.782_771_8 * m - 0.808_675_8 * s,
+    ]
+}
+
+fn oklab_to_rgb([l, a, b]: [f32; 3]) -> Color {
+    let lms = [
+        l + 0.396_337_8 * a + 0.215_803_76 * b,
+        l - 0.105_561_35 * a - 0.063_854_17 * b,
+        l - 0.089_484_18 * a - 1.291_485_5 * b,
+    ];
+    let [l, m, s] = lms.map(|channel| channel.powi(3));
+
+    let linear = [
+        4.076_741_7 * l - 3.307_711_6 * m + 0.230_969_94 * s,
+        -1.268_438 * l + 2.609_757_4 * m - 0.341_319_4 * s,
+        -0.004_196_086 * l - 0.703_418_6 * m + 1.707_614_7 * s,
+    ];
+
+    to_color(linear.map(linear_to_srgb))
+}
+
+fn srgb_to_linear(channel: f32) -> f32 {
+    if channel <= 0.040_45 {
+        channel / 12.92
+    } else {
+        ((channel + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(channel: f32) -> f32 {
+    if channel <= 0.003_130_8 {
+        channel * 12.92
+    } else {
+        1.055 * channel.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// From channels between 0.0 and 1.0.
+fn to_color(channels: [f32; 3]) -> Color {
+    let [r, g, b] = channels.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+    Color::new(r, g, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oklab_ends_at_its_endpoints() {
+        let colors = [
+            Color::new(0, 0, 0),
+            Color::new(255, 255, 255),
+            Color::new(255, 0, 0),
+            Color::new(0, 255, 0),
+            Color::new(0, 0, 255),
+            Color::new(0x44, 0x01, 0x54),
+        ];
+
+        for start in &colors {
+            for end in &colors {
+                assert_eq!(ColorSpace::Oklab.lerp(0.0, start, end), *start);
+                assert_eq!(ColorSpace::Oklab.lerp(1.0, start, end), *end);
+            }
+        }
+    }
+}
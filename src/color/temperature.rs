@@ -0,0 +1,102 @@
+use chrono::{NaiveTime, Timelike};
+use openrgb::data::Color;
+use serde::{Deserialize, Serialize};
+
+const SECONDS_PER_DAY: f32 = 24.0 * 60.0 * 60.0;
+
+/// The white point over the day: `day_kelvin` from `daylight_start` to `daylight_end`, warming
+/// linearly to `night_kelvin` by midnight and holding it until the morning.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColorTemperatureSchedule {
+    pub daylight_start: NaiveTime,
+    pub daylight_end: NaiveTime,
+    #[serde(default = "default_day_kelvin")]
+    pub day_kelvin: u32,
+    #[serde(default = "default_night_kelvin")]
+    pub night_kelvin: u32,
+}
+
+fn default_day_kelvin() -> u32 {
+    6500
+}
+
+fn default_night_kelvin() -> u32 {
+    2700
+}
+
+/// The color temperature `schedule` sets at `time`.
+pub fn effective_kelvin(time: NaiveTime, schedule: &ColorTemperatureSchedule) -> u32 {
+    if time < schedule.daylight_start {
+        return schedule.night_kelvin;
+    }
+    if time < schedule.daylight_end {
+        return schedule.day_kelvin;
+    }
+
+    let evening = SECONDS_PER_DAY - schedule.daylight_end.num_seconds_from_midnight() as f32;
+    let progress = (time - schedule.daylight_end).num_seconds() as f32 / evening.max(1.0);
+    super::lerp(
+        progress,
+        schedule.day_kelvin as f32,
+        schedule.night_kelvin as f32,
+    )
+    .round() as u32
+}
+
+/// The color of a black body at `kelvin`, after Tanner Helland's fit of the CIE data. Good from
+/// 1000 K to 40000 K.
+pub fn color_temperature_to_rgb(kelvin: u32) -> Color {
+    let temperature = kelvin.clamp(1000, 40000) as f32 / 100.0;
+
+    let red = if temperature <= 66.0 {
+        255.0
+    } else {
+        329.698_73 * (temperature - 60.0).powf(-0.133_204_76)
+    };
+    let green = if temperature <= 66.0 {
+        99.470_8 * temperature.ln() - 161.119_57
+    } else {
+        288.122_16 * (temperature - 60.0).powf(-0.075_514_85)
+    };
+    let blue = if temperature >= 66.0 {
+        255.0
+    } else if temperature <= 19.0 {
+        0.0
+    } else {
+        138.517_73 * (temperature - 10.0).ln() - 305.044_8
+    };
+
+    let channel = |value: f32| value.round().clamp(0.0, 255.0) as u8;
+    Color::new(channel(red), channel(green), channel(blue))
+}
+
+/// The `white_balance` factors that tint white to the color of `kelvin`.
+pub fn white_point(kelvin: u32) -> [f32; 3] {
+    let color = color_temperature_to_rgb(kelvin);
+    [color.r, color.g, color.b].map(|channel| channel as f32 / 255.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn warms_after_sunset_until_midnight() {
+        let schedule = ColorTemperatureSchedule {
+            daylight_start: NaiveTime::from_hms_opt(7, 0, 0).unwrap(),
+            daylight_end: NaiveTime::from_hms_opt(19, 0, 0).unwrap(),
+            day_kelvin: 6500,
+            night_kelvin: 2700,
+        };
+        let at = |hour, minute| {
+            effective_kelvin(NaiveTime::from_hms_opt(hour, minute, 0).unwrap(), &schedule)
+        };
+
+        assert_eq!(at(7, 0), 6500); // Sunrise.
+        assert_eq!(at(12, 0), 6500); // Midday.
+        assert_eq!(at(19, 0), 6500); // Sunset.
+        assert_eq!(at(21, 30), 4600); // Halfway to midnight.
+        assert_eq!(at(0, 0), 2700); // Midnight.
+        assert_eq!(at(6, 59), 2700);
+    }
+}
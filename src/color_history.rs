@@ -0,0 +1,132 @@
+//! Append-only binary log of every color update.
+//!
+//! Each record is little-endian: an 8-byte Unix timestamp in milliseconds, a 4-byte controller ID,
+//! a 2-byte LED count and then 3 bytes (R, G, B) per LED.
+
+use openrgb::data::Color;
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, ErrorKind, Read, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const HEADER_SIZE: usize = 8 + 4 + 2;
+
+pub struct ColorHistory {
+    file: File,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorRecord {
+    pub timestamp_ms: u64,
+    pub controller_id: u32,
+    pub colors: Vec<Color>,
+}
+
+impl ColorHistory {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(Self { file })
+    }
+
+    pub fn record(&mut self, controller_id: u32, colors: &[Color]) -> io::Result<()> {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        let led_count = u16::try_from(colors.len())
+            .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "too many LEDs to record"))?;
+
+        // Written in a single call so a record is never interleaved or split by a short write.
+        let mut buffer = Vec::with_capacity(HEADER_SIZE + colors.len() * 3);
+        buffer.extend_from_slice(&timestamp_ms.to_le_bytes());
+        buffer.extend_from_slice(&controller_id.to_le_bytes());
+        buffer.extend_from_slice(&led_count.to_le_bytes());
+        for color in colors {
+            buffer.extend_from_slice(&[color.r, color.g, color.b]);
+        }
+
+        self.file.write_all(&buffer)
+    }
+}
+
+/// Reads the next record, or `None` at the end of the log.
+pub fn read_record(reader: &mut impl Read) -> io::Result<Option<ColorRecord>> {
+    let mut header = [0; HEADER_SIZE];
+    match reader.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+
+    let timestamp_ms = u64::from_le_bytes(header[0..8].try_into().unwrap());
+    let controller_id = u32::from_le_bytes(header[8..12].try_into().unwrap());
+    let led_count = u16::from_le_bytes(header[12..14].try_into().unwrap());
+
+    let mut rgb = vec![0; led_count as usize * 3];
+    reader.read_exact(&mut rgb)?;
+    let colors = rgb
+        .chunks_exact(3)
+        .map(|channels| Color::new(channels[0], channels[1], channels[2]))
+        .collect();
+
+    Ok(Some(ColorRecord {
+        timestamp_ms,
+        controller_id,
+        colors,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, process};
+
+    #[test]
+    fn reads_back_what_was_recorded() {
+        let path = env::temp_dir().join(format!("color_history_test_{}.bin", process::id()));
+        let updates = [
+            (0, vec![Color::new(255, 0, 0); 3]),
+            (7, Vec::new()),
+            (1, vec![Color::new(1, 2, 3), Color::new(4, 5, 6)]),
+        ];
+
+        let before = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let mut history = ColorHistory::open(&path).unwrap();
+        for (controller_id, colors) in &updates {
+            history.record(*controller_id, colors).unwrap();
+        }
+        drop(history);
+
+        let mut file = File::open(&path).unwrap();
+        let mut records = Vec::new();
+        while let Some(record) = read_record(&mut file).unwrap() {
+            records.push(record);
+        }
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(records.len(), updates.len());
+        for (record, (controller_id, colors)) in records.iter().zip(&updates) {
+            assert!(record.timestamp_ms >= before);
+            assert_eq!(record.controller_id, *controller_id);
+            assert_eq!(&record.colors, colors);
+        }
+    }
+
+    #[test]
+    fn fails_on_a_truncated_record() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&0u64.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&2u16.to_le_bytes());
+        bytes.extend_from_slice(&[1, 2, 3]);
+
+        assert!(read_record(&mut bytes.as_slice()).is_err());
+        assert_eq!(read_record(&mut [].as_slice()).unwrap(), None);
+    }
+}
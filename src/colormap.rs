@@ -0,0 +1,163 @@
+//! Matplotlib's perceptually uniform colormaps.
+
+use crate::color::{ColorSpace, ColorStop};
+use openrgb::data::Color;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// Entries of the OKLab lookup tables, converting between color spaces every sample is costly.
+const LUT_SIZE: usize = 256;
+
+/// Per colormap, in the order of `Colormap::index`.
+static OKLAB_LUTS: [OnceLock<Vec<Color>>; 4] = [const { OnceLock::new() }; 4];
+
+// Each map sampled at 10 evenly spaced points, from 0.0 to 1.0.
+static VIRIDIS: [Color; 10] = [
+    Color::new(0x44, 0x01, 0x54),
+    Color::new(0x48, 0x28, 0x78),
+    Color::new(0x3E, 0x4A, 0x89),
+    Color::new(0x31, 0x68, 0x8E),
+    Color::new(0x26, 0x82, 0x8E),
+    Color::new(0x1F, 0x9E, 0x89),
+    Color::new(0x35, 0xB7, 0x79),
+    Color::new(0x6D, 0xCD, 0x59),
+    Color::new(0xB4, 0xDE, 0x2C),
+    Color::new(0xFD, 0xE7, 0x25),
+];
+
+static PLASMA: [Color; 10] = [
+    Color::new(0x0D, 0x08, 0x87),
+    Color::new(0x47, 0x03, 0x9F),
+    Color::new(0x73, 0x01, 0xA8),
+    Color::new(0x9C, 0x17, 0x9E),
+    Color::new(0xBD, 0x37, 0x86),
+    Color::new(0xD8, 0x57, 0x6B),
+    Color::new(0xED, 0x79, 0x53),
+    Color::new(0xFA, 0x9E, 0x3B),
+    Color::new(0xFD, 0xC9, 0x26),
+    Color::new(0xF0, 0xF9, 0x21),
+];
+
+static INFERNO: [Color; 10] = [
+    Color::new(0x00, 0x00, 0x04),
+    Color::new(0x1B, 0x0C, 0x42),
+    Color::new(0x4B, 0x0C, 0x6B),
+    Color::new(0x78, 0x1C, 0x6D),
+    Color::new(0xA5, 0x2C, 0x60),
+    Color::new(0xCF, 0x44, 0x46),
+    Color::new(0xED, 0x69, 0x25),
+    Color::new(0xFB, 0x9A, 0x06),
+    Color::new(0xF7, 0xD0, 0x3C),
+    Color::new(0xFC, 0xFF, 0xA4),
+];
+
+static MAGMA: [Color; 10] = [
+    Color::new(0x00, 0x00, 0x04),
+    Color::new(0x18, 0x0F, 0x3E),
+    Color::new(0x45, 0x10, 0x77),
+    Color::new(0x72, 0x1F, 0x81),
+    Color::new(0x9F, 0x2F, 0x7F),
+    Color::new(0xCD, 0x40, 0x71),
+    Color::new(0xF1, 0x60, 0x5D),
+    Color::new(0xFD, 0x95, 0x67),
+    Color::new(0xFE, 0xC9, 0x8D),
+    Color::new(0xFC, 0xFD, 0xBF),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Colormap {
+    Viridis,
+    Plasma,
+    Inferno,
+    Magma,
+}
+
+impl Colormap {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "viridis" => Some(Colormap::Viridis),
+            "plasma" => Some(Colormap::Plasma),
+            "inferno" => Some(Colormap::Inferno),
+            "magma" => Some(Colormap::Magma),
+            _ => None,
+        }
+    }
+
+    /// The colormap called `name` in matplotlib as palette stops, e.g. for `palette_cycle`.
+    pub fn from_matplotlib_name(name: &str) -> Option<Vec<ColorStop>> {
+        let table = Self::from_name(name)?.table();
+        let last = (table.len() - 1) as f32;
+
+        Some(
+            table
+                .iter()
+                .enumerate()
+                .map(|(index, &color)| ColorStop {
+                    position: index as f32 / last,
+                    color,
+                })
+                .collect(),
+        )
+    }
+
+    /// The color at `value`, from 0.0 to 1.0, interpolated between the table entries around it in
+    /// `color_space`. OKLab is looked up in a precomputed table.
+    pub fn sample(self, value: f32, color_space: ColorSpace) -> Color {
+        if color_space == ColorSpace::Oklab {
+            let lut = OKLAB_LUTS[self.index()].get_or_init(|| {
+                (0..LUT_SIZE)
+                    .map(|entry| {
+                        self.interpolate(entry as f32 / (LUT_SIZE - 1) as f32, color_space)
+                    })
+                    .collect()
+            });
+
+            return lut[(value.clamp(0.0, 1.0) * (LUT_SIZE - 1) as f32).round() as usize];
+        }
+
+        self.interpolate(value, color_space)
+    }
+
+    fn interpolate(self, value: f32, color_space: ColorSpace) -> Color {
+        let table = self.table();
+        let position = value.clamp(0.0, 1.0) * (table.len() - 1) as f32;
+        let index = (position.floor() as usize).min(table.len() - 2);
+
+        color_space.lerp(position - index as f32, &table[index], &table[index + 1])
+    }
+
+    fn index(self) -> usize {
+        match self {
+            Colormap::Viridis => 0,
+            Colormap::Plasma => 1,
+            Colormap::Inferno => 2,
+            Colormap::Magma => 3,
+        }
+    }
+
+    fn table(self) -> &'static [Color] {
+        match self {
+            Colormap::Viridis => &VIRIDIS,
+            Colormap::Plasma => &PLASMA,
+            Colormap::Inferno => &INFERNO,
+            Colormap::Magma => &MAGMA,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn viridis_ends_at_its_first_and_last_entries() {
+        let (first, last) = (Color::new(0x44, 0x01, 0x54), Color::new(0xFD, 0xE7, 0x25));
+
+        // Interpolated directly, and looked up in the table.
+        for color_space in [ColorSpace::Rgb, ColorSpace::Oklab] {
+            assert_eq!(Colormap::Viridis.sample(0.0, color_space), first);
+            assert_eq!(Colormap::Viridis.sample(1.0, color_space), last);
+        }
+    }
+}
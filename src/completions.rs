@@ -0,0 +1,87 @@
+//! `--generate-completions`: shell completion scripts, completing controller names too.
+
+use crate::{cli::Cli, config::Config, pool::ConnectionPool};
+use clap::{builder::PossibleValuesParser, CommandFactory};
+use clap_complete::Shell;
+use std::io::Write;
+
+/// The arguments that take a controller name.
+const CONTROLLER_NAME_ARGS: [&str; 2] = ["edit_gradient", "benchmark_leds"];
+
+/// The names of the controllers on the OpenRGB server of the config file, or of those in the
+/// config file when the server isn't running.
+pub async fn controller_names() -> Vec<String> {
+    let config = Config::load().unwrap_or_default();
+    let pool = ConnectionPool::new([config.server_address.clone()], &config.client_name);
+    if let Ok(client) = pool.open(0).await {
+        let mut names = Vec::new();
+        let count = client.get_controller_count().await.unwrap_or_default();
+        for controller_id in 0..count {
+            if let Ok(controller) = client.get_controller(controller_id).await {
+                names.push(controller.name);
+            }
+        }
+        if !names.is_empty() {
+            return names;
+        }
+    }
+
+    config
+        .controllers
+        .into_iter()
+        .map(|controller| controller.name)
+        .collect()
+}
+
+/// Writes the completion script for `shell` to `out`, completing `controller_names` where a
+/// controller name goes.
+pub fn generate(shell: Shell, controller_names: &[String], out: &mut impl Write) {
+    let mut command = Cli::command();
+    if !controller_names.is_empty() {
+        for arg in CONTROLLER_NAME_ARGS {
+            command = command.mut_arg(arg, |arg| {
+                arg.value_parser(PossibleValuesParser::new(controller_names.to_vec()))
+            });
+        }
+    }
+
+    clap_complete::generate(shell, &mut command, env!("CARGO_PKG_NAME"), out);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script(shell: Shell, controller_names: &[String]) -> String {
+        let mut out = Vec::new();
+        generate(shell, controller_names, &mut out);
+
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn bash_completes_the_subcommands_and_flags() {
+        let script = script(Shell::Bash, &[]);
+
+        assert!(!script.is_empty());
+        assert!(script.contains(env!("CARGO_PKG_NAME")));
+        for word in [
+            "identify",
+            "test",
+            "preview-gradient",
+            "--generate-completions",
+            "--edit-gradient",
+        ] {
+            assert!(script.contains(word), "no {word} in the script");
+        }
+    }
+
+    #[test]
+    fn completes_controller_names() {
+        let names = ["LeftFan".to_string(), "RightFan".to_string()];
+
+        let script = script(Shell::Bash, &names);
+
+        assert!(script.contains("LeftFan RightFan"));
+    }
+}
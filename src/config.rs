@@ -0,0 +1,942 @@
+mod migration;
+
+use crate::{
+    color::{hex_color, ColorSpace, ColorTemperatureSchedule, RED_COLOR, WHITE_COLOR},
+    debounce::Debounce,
+    effect::Effect,
+    frame_rate::AdaptiveFrameRateConfig,
+    health::HealthMonitorConfig,
+    influxdb::InfluxDbConfig,
+    metrics::{AdaptiveSampleRateConfig, Metric, Smoothing, SmoothingProfile, TemperatureUnit},
+    priority::Priority,
+    reconnect::ReconnectConfig,
+    schedule::ScheduleEntry,
+    sink::ControllerInfo,
+    sync::SyncConfig,
+    transition::{EffectTransition, SmoothTransition},
+    zones::ZoneResize,
+};
+use openrgb::data::Color;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    env,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+use sysinfo::System;
+use tracing::{debug, info, warn};
+
+pub use migration::{migrate_config, CURRENT_VERSION};
+
+const CONFIG_PATH_VAR: &str = "MAIN_PC_2_OPENRGB_CLIENT_CONFIG";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Config {
+    /// The schema the file is written for, older ones are migrated on load.
+    #[serde(default = "default_config_version")]
+    pub config_version: u32,
+
+    #[serde(default)]
+    pub controllers: Vec<ControllerConfig>,
+
+    /// Records every color update to this file, see `color_history`.
+    pub color_history_path: Option<PathBuf>,
+
+    #[serde(default)]
+    pub health_monitor: HealthMonitorConfig,
+
+    #[serde(default)]
+    pub reconnect: ReconnectConfig,
+
+    /// Alternative controller mappings, switched to by the schedule.
+    #[serde(default)]
+    pub profiles: HashMap<String, Profile>,
+
+    #[serde(default)]
+    pub schedule: Vec<ScheduleEntry>,
+
+    /// Controllers lit as one, see `GroupConfig`.
+    #[serde(default)]
+    pub groups: Vec<GroupConfig>,
+
+    /// Samples less often while the CPU usage is steady, tuned by `adaptive_sampling`.
+    #[serde(default)]
+    pub adaptive_sample_rate: bool,
+
+    #[serde(default)]
+    pub adaptive_sampling: AdaptiveSampleRateConfig,
+
+    /// How temperatures are logged and exported, the `temperature` metrics are configured in
+    /// Celsius anyway.
+    #[serde(default)]
+    pub temperature_unit: TemperatureUnit,
+
+    /// How the CPU usage is smoothed, over the last 5 seconds unless it's a median.
+    #[serde(default)]
+    pub smoothing_profile: SmoothingProfile,
+
+    /// When at most this many LEDs of a controller changed since the last frame, only those are
+    /// sent, one by one, and unchanged controllers aren't sent at all. 0 always sends every LED.
+    #[serde(default)]
+    pub single_led_update_threshold: usize,
+
+    /// Sends only the OpenRGB zones whose colors changed since the last frame, for controllers
+    /// with several zones. Unchanged controllers aren't sent at all.
+    #[serde(default)]
+    pub zone_updates: bool,
+
+    #[serde(default)]
+    pub zero_led_controllers: ZeroLedControllers,
+
+    /// Holds the color updates back and sends them together at most this often, 0 sends them
+    /// right away.
+    #[serde(default)]
+    pub batch_flush_interval_ms: u64,
+
+    /// Renders less often while every controller's metric is low.
+    pub adaptive_frame_rate: Option<AdaptiveFrameRateConfig>,
+
+    #[serde(default)]
+    pub scheduler_priority: Priority,
+
+    /// How long a protocol call may take before the frame fails and the client reconnects. A
+    /// controller whose call took longer is skipped for a while after that.
+    #[serde(default = "default_protocol_timeout_ms")]
+    pub protocol_timeout_ms: u64,
+
+    /// How long getting or updating a single controller may take, instead of
+    /// `protocol_timeout_ms`.
+    pub per_controller_timeout_ms: Option<u64>,
+
+    /// `host:port` of the OpenRGB server, e.g. the local end of an SSH forward to a remote one.
+    #[serde(default = "default_server_address")]
+    pub server_address: String,
+
+    /// The name the OpenRGB server lists this client under.
+    #[serde(default = "default_client_name")]
+    pub client_name: String,
+
+    /// A Unix socket answering commands like `status`, see `control`. Unix only.
+    pub control_socket: Option<PathBuf>,
+
+    /// How the zones go over to their effect in a newly activated profile, cutting when unset.
+    pub effect_transition: Option<EffectTransition>,
+
+    /// Locks the animations to another machine's, see `sync`.
+    pub sync: Option<SyncConfig>,
+
+    /// Fails at startup when a referenced metric isn't available, see `metrics::self_test`.
+    #[serde(default)]
+    pub strict_metrics: bool,
+
+    /// Gamma correction of every controller without a `gamma` of its own.
+    pub gamma: Option<f32>,
+
+    /// Exits when the server has had no controllers for this long. Waits for them otherwise.
+    pub require_devices_timeout_secs: Option<u64>,
+
+    /// Sends the metric values to InfluxDB, see `influxdb`.
+    pub influxdb: Option<InfluxDbConfig>,
+
+    /// Warms the white point in the evening, for the controllers with
+    /// `use_color_temperature_schedule`.
+    pub color_temperature_schedule: Option<ColorTemperatureSchedule>,
+}
+
+fn default_protocol_timeout_ms() -> u64 {
+    5000
+}
+
+fn default_config_version() -> u32 {
+    1
+}
+
+fn default_server_address() -> String {
+    "localhost:6742".to_string()
+}
+
+fn default_client_name() -> String {
+    "main_pc_2_openrgb_client".to_string()
+}
+
+/// What to do with controllers that report no LEDs even in Direct mode.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ZeroLedControllers {
+    /// Checks again every frame, for devices that get their LEDs late.
+    #[default]
+    Retry,
+    /// Ignores them until the next connection.
+    Skip,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Profile {
+    #[serde(default)]
+    pub controllers: Vec<ControllerConfig>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ControllerConfig {
+    pub name: String,
+    pub metric: Metric,
+
+    /// Leaves the controller untouched. Controllers without a mapping are a startup error.
+    #[serde(default)]
+    pub disabled: bool,
+
+    /// Rendered in order. A zone without `led_count` takes the remaining LEDs.
+    #[serde(default)]
+    pub zones: Vec<Zone>,
+
+    /// Fades to new colors instead of cutting to them.
+    pub smooth_transition: Option<SmoothTransition>,
+
+    /// Red, green and blue scaling factors applied when writing, to correct the LEDs' color cast.
+    pub white_balance: Option<[f32; 3]>,
+
+    /// Tints the colors to the white point of the top-level `color_temperature_schedule`.
+    #[serde(default)]
+    pub use_color_temperature_schedule: bool,
+
+    /// Dims the controller, from 0.0 to 1.0.
+    pub brightness: Option<f32>,
+
+    /// Written as it is instead of black, after dimming and gamma correction, for LEDs that still
+    /// glow at black or to keep unlit LEDs dimly on.
+    #[serde(default, with = "hex_color::option")]
+    pub off_color: Option<Color>,
+
+    /// Gamma correction, instead of the top-level `gamma`, for LEDs that respond differently.
+    pub gamma: Option<f32>,
+
+    #[serde(default)]
+    pub dimming: Dimming,
+
+    /// Shifts the colors this many LEDs along, wrapping around, for fan rings whose first LED
+    /// isn't where the effect should start. Applied before `invert_leds`.
+    #[serde(default)]
+    pub rotate: i64,
+
+    /// Runs the controller's animations this many seconds ahead of the others, to stagger them
+    /// on purpose. They are in step otherwise.
+    #[serde(default)]
+    pub phase_offset_secs: f64,
+
+    /// Reverses the LED order, for devices mounted upside-down.
+    #[serde(default)]
+    pub invert_leds: bool,
+
+    /// Maps the metric value to `1.0 - value`, so gradients fill from the other end.
+    #[serde(default)]
+    pub invert_metric: bool,
+
+    /// What to show when `metric` isn't available on this machine.
+    #[serde(default)]
+    pub metric_unavailable: MetricUnavailable,
+
+    /// What to do when the zones don't add up to the controller's LED count.
+    #[serde(default)]
+    pub led_count_mismatch: LedCountMismatch,
+
+    /// Zone sizes set on the server at startup, before rendering.
+    #[serde(default)]
+    pub resize_zone: Vec<ZoneResize>,
+
+    /// Resizes the controller's resizable zones to the `led_count` of the zones here at startup.
+    #[serde(default)]
+    pub resize_zones: bool,
+
+    /// Holds back writes for a while after one, for controllers that misbehave when written often.
+    pub debounce: Option<Debounce>,
+
+    /// What the effects blend between colors in, e.g. along a gradient.
+    #[serde(default)]
+    pub color_space: ColorSpace,
+}
+
+/// What to do with a controller whose zones add up to more or fewer LEDs than it has.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LedCountMismatch {
+    /// Drops the extra colors, the missing LEDs stay black.
+    #[default]
+    Truncate,
+    /// Drops the extra colors, the missing LEDs get `color`.
+    Pad {
+        #[serde(with = "hex_color")]
+        color: Color,
+    },
+    /// Leaves the controller alone.
+    Skip,
+}
+
+/// How a controller's `brightness` is applied.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Dimming {
+    /// Scales every color, which loses precision at low brightness.
+    #[default]
+    ChannelScale,
+    /// Sets the brightness of the controller's mode, for modes that have one, and scales the
+    /// colors otherwise.
+    ModeBrightness,
+}
+
+/// What a controller shows while its metric is unavailable, so one config works across machines
+/// with different hardware.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MetricUnavailable {
+    /// Leaves the controller alone.
+    #[default]
+    Disable,
+    Solid {
+        #[serde(with = "hex_color")]
+        color: Color,
+    },
+    /// Renders the zones with another metric, and leaves the controller alone if that one is
+    /// unavailable too.
+    Substitute { metric: Metric },
+}
+
+/// Controllers that show the same effect driven by the same metric, e.g. the case fans and the RAM.
+/// Members keep the zone layout and the other options of their own mapping, but every zone renders
+/// `shared_effect`. Members without a mapping render it across all their LEDs.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GroupConfig {
+    pub name: String,
+    pub members: Vec<String>,
+    pub metric: Metric,
+    pub shared_effect: Effect,
+    /// The color space of members without a mapping, the others use the one of their mapping.
+    #[serde(default)]
+    pub color_space: ColorSpace,
+}
+
+impl GroupConfig {
+    /// The mapping of a member without one of its own: a single zone across all its LEDs, which
+    /// renders `shared_effect` like the zones of any member.
+    pub fn member_mapping(&self, controller: &str) -> ControllerConfig {
+        ControllerConfig {
+            name: controller.to_string(),
+            metric: self.metric.clone(),
+            zones: vec![Zone {
+                name: None,
+                led_count: None,
+                metric: None,
+                effect: Effect::Solid {
+                    color: Color::new(0, 0, 0),
+                },
+            }],
+            color_space: self.color_space,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Zone {
+    /// The controller's zone with this name, ignoring case, instead of the next `led_count` LEDs.
+    pub name: Option<String>,
+    pub led_count: Option<usize>,
+    /// Drives this zone instead of the controller's metric.
+    pub metric: Option<Metric>,
+    pub effect: Effect,
+}
+
+impl Config {
+    /// Loads the config file, falling back to the built-in mapping when there is none.
+    pub fn load() -> Result<Self, Box<dyn Error>> {
+        let Some(path) = config_path().filter(|path| path.exists()) else {
+            info!("No config file found, using the built-in mapping");
+            return Ok(Self::default());
+        };
+
+        info!("Loading config from {}", path.display());
+        let text = fs::read_to_string(&path)?;
+
+        let mut table: toml::Table = toml::from_str(&text)?;
+        let version = table
+            .get("config_version")
+            .and_then(toml::Value::as_integer)
+            .unwrap_or(1);
+        let version =
+            u32::try_from(version).map_err(|_| format!("invalid config_version {version}"))?;
+        if version != CURRENT_VERSION {
+            table = migrate_file(&path, table, version)?;
+        }
+
+        if let Some(toml::Value::Table(mut machines)) = table.remove("machine") {
+            let hostname = System::host_name().unwrap_or_default();
+            if let Some(toml::Value::Table(machine)) = machines.remove(&hostname) {
+                info!("Applying the config section of machine {hostname:?}");
+                merge_machine_section(&mut table, machine);
+            }
+        }
+
+        let config = Self::deserialize(toml::Value::Table(table))?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Where the config file is, whether or not it exists.
+    pub fn path() -> Option<PathBuf> {
+        config_path()
+    }
+
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        for entry in &self.schedule {
+            if !self.profiles.contains_key(&entry.profile) {
+                return Err(format!(
+                    "schedule entry {:?} references unknown profile {:?}",
+                    entry.cron_expr, entry.profile
+                )
+                .into());
+            }
+        }
+
+        let profile_controllers = self
+            .profiles
+            .values()
+            .flat_map(|profile| &profile.controllers);
+        for controller in self.controllers.iter().chain(profile_controllers.clone()) {
+            for zone in &controller.zones {
+                zone.effect
+                    .validate()
+                    .map_err(|e| format!("controller {:?}: {e}", controller.name))?;
+                if let Some(led_count) = zone.led_count {
+                    zone.effect
+                        .check_led_count(led_count)
+                        .map_err(|e| format!("controller {:?}: {e}", controller.name))?;
+                }
+
+                if let Effect::MirrorOf {
+                    source_controller, ..
+                } = &zone.effect
+                {
+                    let known = self
+                        .controllers
+                        .iter()
+                        .chain(profile_controllers.clone())
+                        .any(|source| &source.name == source_controller);
+                    if !known || source_controller == &controller.name {
+                        return Err(format!(
+                            "controller {:?} mirrors {source_controller:?}, which isn't another configured controller",
+                            controller.name
+                        )
+                        .into());
+                    }
+                }
+            }
+        }
+        for group in &self.groups {
+            group
+                .shared_effect
+                .validate()
+                .map_err(|e| format!("group {:?}: {e}", group.name))?;
+
+            if matches!(group.shared_effect, Effect::MirrorOf { .. }) {
+                return Err(format!("group {:?} can't use a mirror_of effect", group.name).into());
+            }
+        }
+
+        let controller_gammas = self
+            .controllers
+            .iter()
+            .chain(profile_controllers.clone())
+            .filter_map(|controller| controller.gamma);
+        for gamma in self.gamma.into_iter().chain(controller_gammas) {
+            if !gamma.is_finite() || gamma <= 0.0 {
+                return Err(format!("gamma {gamma} must be a positive number").into());
+            }
+        }
+
+        if let Some(schedule) = &self.color_temperature_schedule {
+            if schedule.daylight_start >= schedule.daylight_end {
+                return Err(
+                    "color_temperature_schedule daylight_start must be before daylight_end".into(),
+                );
+            }
+        }
+
+        let mut grouped = HashMap::new();
+        for group in &self.groups {
+            for member in &group.members {
+                if let Some(other) = grouped.insert(member, &group.name) {
+                    return Err(format!(
+                        "controller {member:?} is in both group {other:?} and group {:?}",
+                        group.name
+                    )
+                    .into());
+                }
+            }
+        }
+
+        for metric in self.metrics() {
+            match metric {
+                Metric::Hysteresis { high, low, .. } if low >= high => {
+                    return Err(format!("hysteresis low {low} must be below high {high}").into());
+                }
+                Metric::Smoothed {
+                    smoothing: Smoothing::Ema { alpha },
+                    ..
+                } if !(*alpha > 0.0 && *alpha <= 1.0) => {
+                    return Err(format!("ema alpha {alpha} must be above 0 and at most 1").into());
+                }
+                Metric::Smoothed {
+                    smoothing: Smoothing::Sma { window: 0 },
+                    ..
+                } => {
+                    return Err("smoothing window must not be 0".into());
+                }
+                Metric::Smoothed {
+                    smoothing: Smoothing::Median { window },
+                    ..
+                } if window % 2 == 0 => {
+                    return Err(format!("median window {window} must be odd").into());
+                }
+                Metric::Delta { normalize_by, .. } | Metric::AbsDelta { normalize_by, .. }
+                    if *normalize_by <= 0.0 =>
+                {
+                    return Err(format!("delta normalize_by {normalize_by} must be above 0").into());
+                }
+                Metric::Temperature {
+                    sensor,
+                    min_celsius,
+                    max_celsius,
+                } if min_celsius >= max_celsius => {
+                    return Err(format!(
+                        "temperature {sensor:?} min_celsius {min_celsius} must be below max_celsius {max_celsius}"
+                    )
+                    .into());
+                }
+                _ => {}
+            }
+        }
+
+        if let SmoothingProfile::Median { window } = self.smoothing_profile {
+            if window % 2 == 0 {
+                return Err(format!("smoothing_profile median window {window} must be odd").into());
+            }
+        }
+
+        let adaptive_sampling = &self.adaptive_sampling;
+        if adaptive_sampling.min_sleep_ms > adaptive_sampling.max_sleep_ms {
+            return Err(format!(
+                "adaptive sampling min_sleep_ms {} must not exceed max_sleep_ms {}",
+                adaptive_sampling.min_sleep_ms, adaptive_sampling.max_sleep_ms
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+
+    /// Every gamma in the config, for `GammaTables`.
+    pub fn gammas(&self) -> Vec<f32> {
+        let profile_controllers = self
+            .profiles
+            .values()
+            .flat_map(|profile| &profile.controllers);
+
+        self.gamma
+            .into_iter()
+            .chain(
+                self.controllers
+                    .iter()
+                    .chain(profile_controllers)
+                    .filter_map(|controller| controller.gamma),
+            )
+            .collect()
+    }
+
+    /// The controller mappings of `profile`, or the top-level ones when there is no such profile.
+    pub fn controllers(&self, profile: Option<&str>) -> &[ControllerConfig] {
+        profile
+            .and_then(|profile| self.profiles.get(profile))
+            .map(|profile| profile.controllers.as_slice())
+            .unwrap_or(&self.controllers)
+    }
+
+    /// Checks that every controller on the server, listed by name, is mapped in some profile or
+    /// grouped, so none is left showing whatever it showed before by mistake.
+    pub fn check_controllers(&self, names: &[String]) -> Result<(), Box<dyn Error>> {
+        let profile_controllers = self
+            .profiles
+            .values()
+            .flat_map(|profile| &profile.controllers);
+        let unknown: Vec<&String> = names
+            .iter()
+            .filter(|name| {
+                !self
+                    .controllers
+                    .iter()
+                    .any(|mapping| &mapping.name == *name)
+                    && !profile_controllers
+                        .clone()
+                        .any(|mapping| &mapping.name == *name)
+                    && self.group(name).is_none()
+            })
+            .collect();
+
+        if !unknown.is_empty() {
+            return Err(format!(
+                "controllers {unknown:?} aren't configured, map them (a `constant` metric holds a fixed color) or set `disabled = true`"
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+
+    /// Fails when a mapping of `controller` names a zone it doesn't have.
+    pub fn check_zone_names(&self, controller: &ControllerInfo) -> Result<(), Box<dyn Error>> {
+        let profile_controllers = self
+            .profiles
+            .values()
+            .flat_map(|profile| &profile.controllers);
+        let zone_names = self
+            .controllers
+            .iter()
+            .chain(profile_controllers)
+            .filter(|mapping| mapping.name == controller.name)
+            .flat_map(|mapping| &mapping.zones)
+            .filter_map(|zone| zone.name.as_deref());
+
+        for zone_name in zone_names {
+            if controller.zone_range(zone_name).is_none() {
+                return Err(format!(
+                    "{} has no zone named {zone_name:?}, its zones are {:?}",
+                    controller.name, controller.zone_names
+                )
+                .into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The group `controller` is a member of.
+    pub fn group(&self, controller: &str) -> Option<&GroupConfig> {
+        self.groups
+            .iter()
+            .find(|group| group.members.iter().any(|member| member == controller))
+    }
+
+    /// Whether anything changes between metric samples, e.g. an animated effect or a transition,
+    /// so frames are needed more often than samples.
+    pub fn is_animated(&self) -> bool {
+        let profile_controllers = self
+            .profiles
+            .values()
+            .flat_map(|profile| &profile.controllers);
+        let controller_animated =
+            self.controllers
+                .iter()
+                .chain(profile_controllers)
+                .any(|controller| {
+                    controller.smooth_transition.is_some()
+                        || controller
+                            .zones
+                            .iter()
+                            .any(|zone| zone.effect.is_animated())
+                });
+
+        self.effect_transition.is_some()
+            || controller_animated
+            || self
+                .groups
+                .iter()
+                .any(|group| group.shared_effect.is_animated())
+    }
+
+    /// Every metric referenced by the controller mappings and groups, including the ones nested in effects
+    /// and the ones derived metrics are computed from, which come before them.
+    pub fn metrics(&self) -> Vec<&Metric> {
+        let mut referenced = Vec::new();
+        let profile_controllers = self
+            .profiles
+            .values()
+            .flat_map(|profile| &profile.controllers);
+        for controller in self.controllers.iter().chain(profile_controllers) {
+            referenced.push(&controller.metric);
+            if let MetricUnavailable::Substitute { metric } = &controller.metric_unavailable {
+                referenced.push(metric);
+            }
+            for zone in &controller.zones {
+                referenced.extend(&zone.metric);
+                zone.effect.collect_metrics(&mut referenced);
+            }
+        }
+        for group in &self.groups {
+            referenced.push(&group.metric);
+            group.shared_effect.collect_metrics(&mut referenced);
+        }
+
+        let mut metrics = Vec::new();
+        for metric in referenced {
+            metric.flatten_into(&mut metrics);
+        }
+
+        metrics
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let gradient = Effect::Gradient {
+            start_color: WHITE_COLOR,
+            end_color: RED_COLOR,
+        };
+        let block = Effect::Block {
+            start_color: WHITE_COLOR,
+            end_color: RED_COLOR,
+        };
+
+        // The fans on the ports in cooler colors than the ring.
+        let port_block = Effect::Block {
+            start_color: Color::new(0, 0, 127),
+            end_color: Color::new(0, 127, 127),
+        };
+        let mut commander_core_zones = vec![Zone {
+            name: None,
+            led_count: Some(24), // Ring.
+            metric: None,
+            effect: gradient.clone(),
+        }];
+        commander_core_zones.extend((0..6).map(|_| Zone {
+            name: None,
+            led_count: Some(5), // Ports (fans).
+            metric: None,
+            effect: port_block.clone(),
+        }));
+
+        Self {
+            config_version: CURRENT_VERSION,
+            controllers: vec![
+                ControllerConfig {
+                    name: "Corsair Dominator Platinum".to_string(),
+                    metric: Metric::MemoryUsage,
+                    zones: vec![Zone {
+                        name: None,
+                        led_count: None,
+                        metric: None,
+                        effect: gradient,
+                    }],
+                    ..Default::default()
+                },
+                ControllerConfig {
+                    name: "Corsair Commander Core".to_string(),
+                    metric: Metric::CpuUsage,
+                    zones: commander_core_zones,
+                    ..Default::default()
+                },
+                ControllerConfig {
+                    name: "G502 HERO Gaming Mouse".to_string(),
+                    metric: Metric::CpuUsage,
+                    zones: vec![Zone {
+                        name: None,
+                        led_count: None,
+                        metric: None,
+                        effect: block,
+                    }],
+                    ..Default::default()
+                },
+                ControllerConfig {
+                    name: "MSI X670E GAMING PLUS WIFI (MS-7E16)".to_string(),
+                    metric: Metric::CpuUsage,
+                    disabled: true,
+                    ..Default::default()
+                },
+            ],
+            color_history_path: None,
+            health_monitor: HealthMonitorConfig::default(),
+            reconnect: ReconnectConfig::default(),
+            profiles: HashMap::new(),
+            schedule: vec![],
+            groups: vec![],
+            adaptive_sample_rate: false,
+            adaptive_sampling: AdaptiveSampleRateConfig::default(),
+            temperature_unit: TemperatureUnit::default(),
+            smoothing_profile: SmoothingProfile::default(),
+            single_led_update_threshold: 0,
+            zone_updates: false,
+            batch_flush_interval_ms: 0,
+            zero_led_controllers: ZeroLedControllers::default(),
+            adaptive_frame_rate: None,
+            scheduler_priority: Priority::default(),
+            protocol_timeout_ms: default_protocol_timeout_ms(),
+            per_controller_timeout_ms: None,
+            server_address: default_server_address(),
+            client_name: default_client_name(),
+            control_socket: None,
+            effect_transition: None,
+            sync: None,
+            strict_metrics: false,
+            gamma: None,
+            require_devices_timeout_secs: None,
+            influxdb: None,
+            color_temperature_schedule: None,
+        }
+    }
+}
+
+/// Migrates the config file at `path` from `version`, writing the result back when the migration
+/// changed more than the version, with the original kept next to it.
+fn migrate_file(
+    path: &Path,
+    table: toml::Table,
+    version: u32,
+) -> Result<toml::Table, Box<dyn Error>> {
+    let toml::Value::Table(migrated) = migrate_config(toml::Value::Table(table.clone()), version)?
+    else {
+        unreachable!("migrating keeps the root a table");
+    };
+
+    let without_version = |table: &toml::Table| {
+        let mut table = table.clone();
+        table.remove("config_version");
+        table
+    };
+    if without_version(&migrated) == without_version(&table) {
+        debug!("Nothing to migrate in config version {version}");
+        return Ok(migrated);
+    }
+
+    let backup = path.with_extension(format!("toml.v{version}"));
+    fs::copy(path, &backup)?;
+    fs::write(path, toml::to_string(&migrated)?)?;
+    warn!(
+        "{} was written for config version {version}, migrated it to version {CURRENT_VERSION}. The original is in {}",
+        path.display(),
+        backup.display()
+    );
+
+    Ok(migrated)
+}
+
+/// Merges a `[machine."hostname"]` section over the base config: tables are merged key by key,
+/// controllers replace the base mapping of the same name or are added, anything else is replaced.
+fn merge_machine_section(base: &mut toml::Table, machine: toml::Table) {
+    for (key, value) in machine {
+        let replacement = match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base_table)), toml::Value::Table(table)) => {
+                merge_machine_section(base_table, table);
+                None
+            }
+            (Some(toml::Value::Array(base_array)), toml::Value::Array(array))
+                if key == "controllers" =>
+            {
+                for controller in array {
+                    let name = controller.get("name").cloned();
+                    match base_array
+                        .iter_mut()
+                        .find(|known| name.is_some() && known.get("name") == name.as_ref())
+                    {
+                        Some(known) => *known = controller,
+                        None => base_array.push(controller),
+                    }
+                }
+                None
+            }
+            (_, value) => Some(value),
+        };
+
+        if let Some(value) = replacement {
+            base.insert(key, value);
+        }
+    }
+}
+
+fn config_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os(CONFIG_PATH_VAR) {
+        return Some(PathBuf::from(path));
+    }
+
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(
+        config_dir
+            .join("main_pc_2_openrgb_client")
+            .join("config.toml"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn default_config_round_trips_through_toml() {
+        let text = toml::to_string(&Config::default()).unwrap();
+        let config: Config = toml::from_str(&text).unwrap();
+
+        assert_eq!(toml::to_string(&config).unwrap(), text);
+    }
+
+    #[test]
+    fn controller_config_round_trips_through_toml() {
+        let controller = ControllerConfig {
+            name: "Strip".to_string(),
+            metric: Metric::Constant(0.5),
+            zones: vec![Zone {
+                name: Some("Top".to_string()),
+                led_count: Some(12),
+                metric: Some(Metric::MemoryUsage),
+                effect: Effect::Solid {
+                    color: Color::new(1, 2, 3),
+                },
+            }],
+            off_color: Some(Color::new(1, 0, 0)),
+            brightness: Some(0.3),
+            ..Default::default()
+        };
+
+        let text = toml::to_string(&controller).unwrap();
+        assert!(text.contains("off_color = \"#010000\""));
+        let parsed: ControllerConfig = toml::from_str(&text).unwrap();
+
+        assert_eq!(parsed.off_color, Some(Color::new(1, 0, 0)));
+        assert!((parsed.brightness.unwrap() - 0.3).abs() < 1.0 / 255.0);
+        assert_eq!(toml::to_string(&parsed).unwrap(), text);
+    }
+
+    #[test]
+    fn types_serialize_to_expected_json() {
+        let effect = Effect::Gradient {
+            start_color: WHITE_COLOR,
+            end_color: RED_COLOR,
+        };
+        let expected = json!({
+            "type": "gradient",
+            "start_color": "#7F7F7F",
+            "end_color": "#7F0000",
+        });
+        assert_eq!(serde_json::to_value(&effect).unwrap(), expected);
+        let parsed: Effect = serde_json::from_value(expected).unwrap();
+        assert!(matches!(
+            parsed,
+            Effect::Gradient { start_color, end_color }
+                if start_color == WHITE_COLOR && end_color == RED_COLOR
+        ));
+
+        for (metric, expected) in [
+            (Metric::CpuUsage, json!("cpu_usage")),
+            (Metric::Constant(0.25), json!({ "constant": 0.25 })),
+            (
+                Metric::GpuUtil { index: 1 },
+                json!({ "gpu_util": { "index": 1 } }),
+            ),
+        ] {
+            assert_eq!(serde_json::to_value(&metric).unwrap(), expected);
+            assert_eq!(serde_json::from_value::<Metric>(expected).unwrap(), metric);
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! Upgrades config files written for an older schema, one version at a time.
+
+use std::error::Error;
+use toml::Value;
+
+/// Files without a `config_version` are version 1.
+pub const CURRENT_VERSION: u32 = 2;
+
+/// `MIGRATIONS[n]` upgrades version `n + 1` to `n + 2`.
+const MIGRATIONS: [fn(&mut Value); CURRENT_VERSION as usize - 1] = [v1_to_v2];
+
+/// `raw` upgraded from `from_version` to `CURRENT_VERSION`, with its `config_version` set.
+pub fn migrate_config(mut raw: Value, from_version: u32) -> Result<Value, Box<dyn Error>> {
+    if from_version == 0 || from_version > CURRENT_VERSION {
+        return Err(format!(
+            "unknown config_version {from_version}, this version of the client reads up to {CURRENT_VERSION}"
+        )
+        .into());
+    }
+
+    for migration in &MIGRATIONS[from_version as usize - 1..] {
+        migration(&mut raw);
+    }
+    if let Value::Table(table) = &mut raw {
+        table.insert(
+            "config_version".to_string(),
+            Value::Integer(CURRENT_VERSION.into()),
+        );
+    }
+
+    Ok(raw)
+}
+
+/// The `alpha` of `blend` effects became `mix`. A `mix` that's there already wins, the `alpha`
+/// next to it would be a duplicate of it, which is still read as an alias.
+fn v1_to_v2(raw: &mut Value) {
+    match raw {
+        Value::Table(table) => {
+            if table.get("type").and_then(Value::as_str) == Some("blend") {
+                if let Some(alpha) = table.remove("alpha") {
+                    table.entry("mix").or_insert(alpha);
+                }
+            }
+            table.values_mut().for_each(v1_to_v2);
+        }
+        Value::Array(values) => values.iter_mut().for_each(v1_to_v2),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renames_blend_alpha_to_mix() {
+        let v1: toml::Table = toml::from_str(
+            r##"
+            [[controllers]]
+            name = "Strip"
+
+            [[controllers.zones]]
+            effect = { type = "blend", alpha = 0.25, a = { type = "solid", color = "#FF0000" }, b = { type = "blend", alpha = 0.5, mix = 0.75, a = { type = "solid", color = "#000000" }, b = { type = "solid", color = "#FFFFFF" } } }
+
+            [[controllers.zones]]
+            metric = { smoothed = { metric = "cpu_usage", smoothing = { ema = { alpha = 0.3 } } } }
+            effect = { type = "solid", color = "#00FF00" }
+            "##,
+        )
+        .unwrap();
+
+        let v2 = migrate_config(Value::Table(v1), 1).unwrap();
+
+        let zones = &v2["controllers"][0]["zones"];
+        let blend = &zones[0]["effect"];
+        assert_eq!(blend.get("alpha"), None);
+        assert_eq!(blend["mix"].as_float(), Some(0.25));
+        // Already has a `mix`, which wins.
+        assert_eq!(blend["b"]["mix"].as_float(), Some(0.75));
+        assert_eq!(blend["b"].get("alpha"), None);
+        // Not a blend effect.
+        let smoothing = &zones[1]["metric"]["smoothed"]["smoothing"];
+        assert_eq!(smoothing["ema"]["alpha"].as_float(), Some(0.3));
+        assert_eq!(v2["config_version"].as_integer(), Some(2));
+    }
+
+    #[test]
+    fn rejects_unknown_versions() {
+        let raw = Value::Table(toml::Table::new());
+
+        assert!(migrate_config(raw.clone(), 0).is_err());
+        assert!(migrate_config(raw.clone(), CURRENT_VERSION + 1).is_err());
+        assert_eq!(
+            migrate_config(raw, CURRENT_VERSION).unwrap()["config_version"].as_integer(),
+            Some(CURRENT_VERSION.into())
+        );
+    }
+}
@@ -0,0 +1,71 @@
+//! A Unix socket taking one command per line, for status widgets and scripts.
+//!
+//! Commands:
+//! - `status`: the `Status` as one line of JSON.
+
+use crate::status::SharedStatus;
+use std::{error::Error, fs, io::ErrorKind, path::Path};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+/// Answers commands on the socket at `path` until `shutdown` is cancelled, then removes it.
+pub async fn serve(
+    path: &Path,
+    status: SharedStatus,
+    shutdown: CancellationToken,
+) -> Result<(), Box<dyn Error>> {
+    // Left behind by an instance that didn't exit cleanly.
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+
+    let listener = UnixListener::bind(path)?;
+    info!("Listening for commands on {}", path.display());
+
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, _) = accepted?;
+                tokio::spawn({
+                    let status = status.clone();
+                    async move {
+                        if let Err(e) = handle_connection(stream, status).await {
+                            debug!("Control connection failed: {e}");
+                        }
+                    }
+                });
+            }
+            _ = shutdown.cancelled() => break,
+        }
+    }
+
+    if let Err(e) = fs::remove_file(path) {
+        warn!("Failed to remove {}: {e}", path.display());
+    }
+
+    Ok(())
+}
+
+async fn handle_connection(stream: UnixStream, status: SharedStatus) -> Result<(), Box<dyn Error>> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        let mut response = match line.trim() {
+            "status" => serde_json::to_string(&*status.read().unwrap())?,
+            command => {
+                serde_json::json!({ "error": format!("unknown command {command:?}") }).to_string()
+            }
+        };
+        response.push('\n');
+
+        writer.write_all(response.as_bytes()).await?;
+    }
+
+    Ok(())
+}
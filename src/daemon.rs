@@ -0,0 +1,320 @@
+#[cfg(unix)]
+use crate::control;
+use crate::{
+    config::Config,
+    health::HealthMonitor,
+    influxdb,
+    metrics::Metric,
+    modes::{self, OriginalModes},
+    pool::{ConnectionPool, PooledConnection, Stream},
+    priority,
+    reconnect::ReconnectConfig,
+    render::{Renderer, RequiredDevicesMissing},
+    schedule::{ActiveProfile, SceneScheduler},
+    sensors::SensorRegistry,
+    systemd,
+    watchdog::Watchdog,
+    zones,
+};
+use openrgb::OpenRGB;
+use std::{
+    error::Error,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+use tokio::{sync::mpsc, time::Instant};
+use tokio_retry::Retry;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+/// How often the client tells it's alive while waiting to reconnect, well within the health
+/// monitor's and the watchdogs' timeouts.
+const KEEP_ALIVE_PERIOD: Duration = Duration::from_secs(1);
+
+/// Drives the controllers until `shutdown` is cancelled, restarting the render loop when it stalls.
+pub async fn run(config: Config, shutdown: CancellationToken) -> Result<(), Box<dyn Error>> {
+    let health_monitor_config = config.health_monitor.clone();
+    let reconnect = config.reconnect.clone();
+
+    if config
+        .metrics()
+        .iter()
+        .any(|metric| matches!(metric, Metric::Temperature { .. }))
+    {
+        SensorRegistry::discover().check_config(&config);
+    }
+
+    // The render loop runs on this thread, the runtime's worker threads keep the default.
+    priority::apply(config.scheduler_priority);
+
+    let active_profile = Arc::new(RwLock::new(ActiveProfile::default()));
+    if !config.schedule.is_empty() {
+        let scheduler = SceneScheduler::new(&config.schedule, active_profile.clone())?;
+        tokio::spawn(scheduler.run());
+    }
+
+    let pool = ConnectionPool::new([config.server_address.clone()], &config.client_name);
+    let control_socket = config.control_socket.clone();
+    let influxdb = config.influxdb.clone();
+    let mut renderer = Renderer::new(config, active_profile)?;
+    renderer.self_test().await?;
+
+    if let Some(path) = control_socket {
+        serve_control_socket(path, &renderer, &shutdown);
+    }
+
+    if let Some(influxdb) = influxdb {
+        let metric_bus = renderer.metric_bus();
+        let metrics = metric_bus.subscribe_all(renderer.config().metrics());
+        let temperatures = metric_bus.subscribe_temperatures(renderer.config().metrics());
+        let unit = renderer.config().temperature_unit;
+        let shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            if let Err(e) = influxdb::export(influxdb, metrics, temperatures, unit, shutdown).await
+            {
+                error!("InfluxDB export failed: {e}");
+            }
+        });
+    }
+
+    let watchdog = (health_monitor_config.watchdog_timeout_secs > 0).then(|| {
+        Watchdog::spawn(Duration::from_secs(
+            health_monitor_config.watchdog_timeout_secs,
+        ))
+    });
+
+    let mut original_modes = None;
+    let mut restarts = 0;
+    loop {
+        let (heartbeat_sender, heartbeat_receiver) = mpsc::channel(1);
+        let stall = CancellationToken::new();
+        let health_monitor = tokio::spawn(
+            HealthMonitor::new(&health_monitor_config).watch(heartbeat_receiver, stall.clone()),
+        );
+
+        tokio::select! {
+            result = render_loop(&mut renderer, &pool, &reconnect, &mut original_modes, heartbeat_sender, watchdog.as_ref(), &shutdown) => {
+                return result;
+            }
+            _ = stall.cancelled() => {}
+        }
+
+        // The stalled connection went back to the pool, don't reuse it.
+        pool.disconnect(0);
+
+        // A restart that got going again doesn't count towards giving up.
+        if health_monitor.await? {
+            restarts = 0;
+        }
+        restarts += 1;
+
+        if restarts > health_monitor_config.max_restarts {
+            error!("Render loop stalled {restarts} times in a row, giving up");
+
+            systemd::notify_dying();
+
+            return Err("render loop stalled".into());
+        }
+
+        error!(
+            "Render loop stalled, restarting ({restarts}/{})",
+            health_monitor_config.max_restarts
+        );
+    }
+}
+
+/// Renders a single frame for `--once`, going through the same setup as a connection of the
+/// render loop. With `restore` the controllers go back to their original modes afterwards.
+pub async fn render_once(mut config: Config, restore: bool) -> Result<(), Box<dyn Error>> {
+    // A batch would hold the only frame back.
+    config.batch_flush_interval_ms = 0;
+
+    let pool = ConnectionPool::new([config.server_address.clone()], &config.client_name);
+    let mut renderer = Renderer::new(config, Arc::default())?;
+    let client = pool.acquire(0).await?;
+
+    let original_modes = OriginalModes::capture(&client).await?;
+    renderer.check_controllers(&*client).await?;
+    zones::resize_zones(&client, renderer.config()).await?;
+    let empty_controllers = modes::ensure_direct_modes(&client).await?;
+    renderer.set_empty_controllers(empty_controllers);
+    let mode_dimmed = modes::apply_mode_brightness(&client, renderer.config()).await?;
+    renderer.set_mode_dimmed(mode_dimmed);
+
+    // The first sample waits to measure the CPU usage over a whole sample interval, so it's valid.
+    renderer.render_frame(&*client).await?;
+    info!("Rendered one frame");
+
+    if restore {
+        original_modes.restore(&client).await;
+    }
+
+    Ok(())
+}
+
+async fn render_loop(
+    renderer: &mut Renderer,
+    pool: &ConnectionPool,
+    reconnect: &ReconnectConfig,
+    original_modes: &mut Option<OriginalModes>,
+    heartbeat: mpsc::Sender<()>,
+    watchdog: Option<&Watchdog>,
+    shutdown: &CancellationToken,
+) -> Result<(), Box<dyn Error>> {
+    let mut ready = false;
+    loop {
+        let client = tokio::select! {
+            client = connect_to_open_rgb_server(pool, reconnect, &heartbeat, watchdog) => client?,
+            _ = shutdown.cancelled() => return Ok(()),
+        };
+        info!(
+            "Connected to OpenRGB server! Protocol version: {}",
+            client.get_protocol_version()
+        );
+        renderer.set_connected_servers(vec![pool.address(0).to_string()]);
+
+        renderer.forget_sent_colors();
+
+        // Only on the first connection, later ones would see the modes the client left behind.
+        if original_modes.is_none() {
+            *original_modes = Some(OriginalModes::capture(&client).await?);
+        }
+        renderer.check_controllers(&*client).await?;
+        zones::resize_zones(&client, renderer.config()).await?;
+        let empty_controllers = modes::ensure_direct_modes(&client).await?;
+        renderer.set_empty_controllers(empty_controllers);
+        let mode_dimmed = modes::apply_mode_brightness(&client, renderer.config()).await?;
+        renderer.set_mode_dimmed(mode_dimmed);
+
+        let error = loop {
+            tokio::select! {
+                result = renderer.render_frame(&*client) => {
+                    match result {
+                        Err(e) if e.is::<RequiredDevicesMissing>() => return Err(e),
+                        Err(e) => break e,
+                        Ok(()) => {}
+                    }
+                }
+                _ = shutdown.cancelled() => {
+                    if let Some(original_modes) = original_modes {
+                        original_modes.restore(&client).await;
+                    }
+
+                    return Ok(());
+                }
+            }
+
+            keep_alive(&heartbeat, watchdog);
+            if !ready {
+                systemd::notify_ready();
+                ready = true;
+            }
+
+            tokio::task::yield_now().await;
+        };
+
+        warn!("Failed to render a frame, reconnecting: {error}");
+        renderer.set_connected_servers(Vec::new());
+        client.invalidate();
+    }
+}
+
+#[cfg(unix)]
+fn serve_control_socket(path: PathBuf, renderer: &Renderer, shutdown: &CancellationToken) {
+    let status = renderer.status();
+    let shutdown = shutdown.clone();
+    tokio::spawn(async move {
+        if let Err(e) = control::serve(&path, status, shutdown).await {
+            error!("Control socket {} failed: {e}", path.display());
+        }
+    });
+}
+
+#[cfg(not(unix))]
+fn serve_control_socket(_path: PathBuf, _renderer: &Renderer, _shutdown: &CancellationToken) {
+    warn!("control_socket is only supported on Unix, ignoring it");
+}
+
+/// Connects to the server of `config` for a one-off command, retrying like the render loop.
+pub async fn connect(config: &Config) -> Result<OpenRGB<Stream>, Box<dyn Error>> {
+    let pool = ConnectionPool::new([config.server_address.clone()], &config.client_name);
+
+    Ok(Retry::spawn(config.reconnect.delays(), || async {
+        info!("Connecting to OpenRGB server...");
+        pool.open(0).await
+    })
+    .await?)
+}
+
+#[tracing::instrument(skip(pool, reconnect, heartbeat, watchdog), level = "debug")]
+async fn connect_to_open_rgb_server<'a>(
+    pool: &'a ConnectionPool,
+    reconnect: &ReconnectConfig,
+    heartbeat: &mpsc::Sender<()>,
+    watchdog: Option<&Watchdog>,
+) -> Result<PooledConnection<'a>, Box<dyn Error>> {
+    let mut delays = reconnect.delays();
+    loop {
+        info!("Connecting to OpenRGB server...");
+        keep_alive(heartbeat, watchdog);
+        let error = match pool.acquire(0).await {
+            Ok(client) => return Ok(client),
+            Err(e) => e,
+        };
+
+        let Some(delay) = delays.next() else {
+            return Err(error);
+        };
+        // Waiting for the server to come up is progress too, however long the backoff.
+        let deadline = Instant::now() + delay;
+        while Instant::now() < deadline {
+            tokio::time::sleep_until(deadline.min(Instant::now() + KEEP_ALIVE_PERIOD)).await;
+            keep_alive(heartbeat, watchdog);
+        }
+    }
+}
+
+/// Tells the health monitor and the watchdogs the client is still making progress.
+fn keep_alive(heartbeat: &mpsc::Sender<()>, watchdog: Option<&Watchdog>) {
+    // Full means the health monitor has a heartbeat pending already.
+    let _ = heartbeat.try_send(());
+    if let Some(watchdog) = watchdog {
+        watchdog.kick();
+    }
+    systemd::notify_watchdog();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reconnect::RetryStrategy;
+
+    #[tokio::test]
+    async fn keeps_alive_while_waiting_to_reconnect() {
+        // Nothing listens there.
+        let pool = ConnectionPool::new(["127.0.0.1:1".to_string()], "test");
+        let reconnect = ReconnectConfig {
+            strategy: RetryStrategy::Fixed,
+            interval_ms: 2500,
+            max_attempts: 2,
+            ..ReconnectConfig::default()
+        };
+        let (heartbeat, mut heartbeats) = mpsc::channel(16);
+
+        let start = Instant::now();
+        assert!(
+            connect_to_open_rgb_server(&pool, &reconnect, &heartbeat, None)
+                .await
+                .is_err()
+        );
+        assert!(start.elapsed() >= Duration::from_millis(2500));
+
+        // The two attempts, and at least once a second of the wait between them.
+        let mut count = 0;
+        while heartbeats.try_recv().is_ok() {
+            count += 1;
+        }
+        assert!(count >= 2 + 2, "{count} heartbeats");
+    }
+}
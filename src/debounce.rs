@@ -0,0 +1,64 @@
+use openrgb::data::Color;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, time::Duration};
+use tokio::time::Instant;
+
+/// Rate limits the writes to a controller: after one, further writes are held back for
+/// `cooldown_ms` unless an LED changed by more than `significant_change` on any channel. Unlike a
+/// fixed update interval, a steady controller is written as soon as it changes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Debounce {
+    pub cooldown_ms: u64,
+    /// From 0 to 255, 255 holds back every write during the cooldown.
+    #[serde(default = "default_significant_change")]
+    pub significant_change: u8,
+}
+
+fn default_significant_change() -> u8 {
+    32
+}
+
+/// The last write to every debounced controller.
+#[derive(Default)]
+pub struct Debouncer {
+    last_writes: HashMap<u32, (Instant, Vec<Color>)>,
+}
+
+impl Debouncer {
+    /// Whether to write `colors` to the controller now, remembering them as written if so.
+    pub fn allow(&mut self, debounce: &Debounce, controller_id: u32, colors: &[Color]) -> bool {
+        let now = Instant::now();
+
+        if let Some((written, last_colors)) = self.last_writes.get(&controller_id) {
+            let cooling_down =
+                now.duration_since(*written) < Duration::from_millis(debounce.cooldown_ms);
+            if cooling_down
+                && last_colors.len() == colors.len()
+                && max_channel_change(last_colors, colors) <= debounce.significant_change
+            {
+                return false;
+            }
+        }
+
+        self.last_writes
+            .insert(controller_id, (now, colors.to_vec()));
+        true
+    }
+
+    /// Forgets the writes, e.g. after reconnecting.
+    pub fn clear(&mut self) {
+        self.last_writes.clear();
+    }
+}
+
+fn max_channel_change(a: &[Color], b: &[Color]) -> u8 {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| {
+            a.r.abs_diff(b.r)
+                .max(a.g.abs_diff(b.g))
+                .max(a.b.abs_diff(b.b))
+        })
+        .max()
+        .unwrap_or_default()
+}
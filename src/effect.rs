@@ -0,0 +1,1221 @@
+#[cfg(feature = "lua")]
+mod lua;
+#[cfg(feature = "wasm")]
+mod wasm;
+
+use crate::{
+    color::{
+        blend_palettes, generate_block_led_colors, generate_gradient_led_colors, hex_color,
+        lerp_color_stops, scale_colors, BlendMode, ColorMixer, ColorSpace, ColorStop, RED_COLOR,
+        WHITE_COLOR,
+    },
+    colormap::Colormap,
+    metrics::{Metric, MetricValues},
+};
+use openrgb::data::Color;
+use serde::{Deserialize, Serialize};
+#[cfg(any(feature = "lua", feature = "wasm"))]
+use std::path::PathBuf;
+use std::{
+    collections::VecDeque,
+    error::Error,
+    f64::consts::TAU,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Effect {
+    /// Fills the LEDs one after the other as the value rises.
+    Gradient {
+        #[serde(with = "hex_color")]
+        start_color: Color,
+        #[serde(with = "hex_color")]
+        end_color: Color,
+    },
+
+    /// Paints every LED with the same color.
+    Block {
+        #[serde(with = "hex_color")]
+        start_color: Color,
+        #[serde(with = "hex_color")]
+        end_color: Color,
+    },
+
+    /// Paints every LED with the color at the value along one of matplotlib's colormaps.
+    Colormap { colormap: Colormap },
+
+    /// Paints every LED with the color of the band the value is in, e.g. cool, warm, hot and
+    /// critical for a temperature. A band starts at its `threshold`, in the units of
+    /// `min`..`max` (by default 0.0..1.0, the value itself), so a temperature metric's bands can
+    /// be given in degrees with its `min_celsius` and `max_celsius`. The last `blend_width` below
+    /// a threshold blends into that band's color.
+    Bands {
+        bands: Vec<Band>,
+        #[serde(default)]
+        blend_width: f32,
+        #[serde(default)]
+        min: f32,
+        #[serde(default = "default_bands_max")]
+        max: f32,
+    },
+
+    /// Holds every LED at a fixed color, whatever the value.
+    Solid {
+        #[serde(with = "hex_color")]
+        color: Color,
+    },
+
+    /// A bright head at the value position with a tail fading behind it, over a gradient from
+    /// `start_color` to `end_color` at the value. With a `speed`, in LEDs per second at a value of
+    /// 1.0, the head travels along an otherwise black strip instead and starts over once the tail
+    /// has left the far end.
+    Comet {
+        tail_length: usize,
+        #[serde(with = "hex_color")]
+        head_color: Color,
+        #[serde(with = "hex_color")]
+        tail_color: Color,
+        #[serde(default = "default_comet_start_color", with = "hex_color")]
+        start_color: Color,
+        #[serde(default = "default_comet_end_color", with = "hex_color")]
+        end_color: Color,
+        speed: Option<f32>,
+        #[serde(skip)]
+        phase: Phase,
+    },
+
+    /// Fades `color` in and out, `rate_hz` breaths per second at a value of 1.0, so it breathes
+    /// faster as the value rises.
+    Breathing {
+        rate_hz: f32,
+        #[serde(with = "hex_color")]
+        color: Color,
+        #[serde(skip)]
+        phase: Phase,
+    },
+
+    /// A scrolling graph of the value: every sample the colors move one LED towards the start and
+    /// the last LED shows the current value along `colormap`.
+    Scroll {
+        colormap: Colormap,
+        #[serde(skip)]
+        history: ValueHistory,
+    },
+
+    /// Splits the LEDs in two, each part driven by its own metric and effect. The first part gets
+    /// the first `split_at` LEDs, half of them by default, where on odd LED counts the second
+    /// part gets the extra LED.
+    Split {
+        first: Box<MetricEffect>,
+        second: Box<MetricEffect>,
+        split_at: Option<usize>,
+    },
+
+    /// Divides a physically continuous strip into named segments, each driven by its own metric
+    /// and effect. The segments must cover the LEDs in order, without gaps or overlaps.
+    Segmented { segments: Vec<ZoneSegment> },
+
+    /// Like `segmented`, but the layers may overlap or leave gaps, e.g. a fire on a fan's inner
+    /// ring under a gauge on its outer one. Later layers are drawn over earlier ones, LEDs no
+    /// layer covers are black. Each layer's effect keeps its own animation state.
+    Layered { layers: Vec<ZoneSegment> },
+
+    /// Renders two effects and combines their colors per LED.
+    Blend {
+        a: Box<Effect>,
+        b: Box<Effect>,
+        #[serde(default)]
+        mode: BlendMode,
+        /// How much of `b` is blended over `a`, from 0.0 to 1.0.
+        #[serde(alias = "alpha")]
+        mix: f32,
+    },
+
+    /// Flashes `color` while `alert` is on (usually a `hysteresis` metric), `base` otherwise.
+    Alert {
+        alert: Metric,
+        #[serde(with = "hex_color")]
+        color: Color,
+        flash_rate_hz: f32,
+        base: Box<Effect>,
+    },
+
+    /// Renders `base`, then lights the last LED a gradient at `marker`'s value would fill with
+    /// `color`, e.g. a `cpu_usage_window` max as a peak-hold dot over a meter of the average.
+    PeakMarker {
+        marker: Metric,
+        #[serde(with = "hex_color")]
+        color: Color,
+        base: Box<Effect>,
+    },
+
+    /// For `delta` metrics: `steady_color` while the value holds, blending into `rising_color` as
+    /// it rises and into `falling_color` as it falls.
+    #[serde(alias = "delta_color")]
+    Trend {
+        #[serde(with = "hex_color", alias = "decrease_color")]
+        falling_color: Color,
+        #[serde(with = "hex_color", alias = "neutral_color")]
+        steady_color: Color,
+        #[serde(with = "hex_color", alias = "increase_color")]
+        rising_color: Color,
+    },
+
+    /// A heatmap of the logical CPUs, spread evenly over the LEDs in the order the OS lists them.
+    CpuCores {
+        #[serde(with = "hex_color")]
+        start_color: Color,
+        #[serde(with = "hex_color")]
+        end_color: Color,
+    },
+
+    /// Colors computed by a Lua script. The script returns a function, called every tick with
+    /// `{ value, led_count, time_secs }`, which returns a `{ r, g, b }` table per LED.
+    #[cfg(feature = "lua")]
+    LuaScript {
+        path: PathBuf,
+        #[serde(skip)]
+        script: lua::LuaScriptCache,
+    },
+
+    /// Colors computed by a WebAssembly module, see the README for its interface.
+    #[cfg(feature = "wasm")]
+    Wasm {
+        path: PathBuf,
+        #[serde(skip)]
+        module: wasm::WasmModuleCache,
+    },
+
+    /// Spreads a palette over the LEDs, holding each of `palettes` for `hold_secs` and then
+    /// morphing into the next one over `transition_secs`, round and round.
+    PaletteCycle {
+        palettes: Vec<Vec<ColorStop>>,
+        transition_secs: f32,
+        hold_secs: f32,
+    },
+
+    /// Plays `steps` one after the other from when it's first shown, at startup or when its
+    /// profile is switched to, e.g. as a boot sequence. Afterwards the steps start over when
+    /// `repeat` is set, otherwise `base` takes over (or the last step stays on without one).
+    Macro {
+        steps: Vec<MacroStep>,
+        #[serde(default)]
+        repeat: bool,
+        base: Option<Box<Effect>>,
+        #[serde(skip)]
+        started: MacroStart,
+    },
+
+    /// Copies the colors `source_controller` was last rendered with, e.g. for the other side of a
+    /// symmetric build. `horizontal_flip` reverses the LEDs of each zone and `vertical_flip` the
+    /// order of the zones. Only works as a zone's effect, and the LED counts must match.
+    MirrorOf {
+        source_controller: String,
+        #[serde(default)]
+        horizontal_flip: bool,
+        #[serde(default)]
+        vertical_flip: bool,
+    },
+}
+
+fn default_bands_max() -> f32 {
+    1.0
+}
+
+fn default_comet_start_color() -> Color {
+    WHITE_COLOR
+}
+
+fn default_comet_end_color() -> Color {
+    RED_COLOR
+}
+
+/// One band of a `Bands` effect.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Band {
+    /// Only for the reader of the config, e.g. "hot".
+    pub name: Option<String>,
+    pub threshold: f32,
+    #[serde(with = "hex_color")]
+    pub color: Color,
+}
+
+/// An effect driven by a metric other than the controller's.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricEffect {
+    pub metric: Metric,
+    pub effect: Effect,
+}
+
+/// How far an animation whose speed changes with the value has come. Shared by the clones of an
+/// effect, so grouped controllers stay in step.
+#[derive(Clone, Default)]
+pub struct Phase(Arc<Mutex<PhaseState>>);
+
+#[derive(Default)]
+struct PhaseState {
+    time_secs: f64,
+    position: f64,
+}
+
+impl fmt::Debug for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Phase")
+    }
+}
+
+impl Phase {
+    /// Moves the position on at `rate` per second since the last call and returns it. Calls
+    /// within the same tick don't move it any further.
+    pub fn advance(&self, time_secs: f64, rate: f64) -> f64 {
+        let mut state = self.0.lock().unwrap();
+        let elapsed = (time_secs - state.time_secs).max(0.0);
+        state.time_secs = time_secs;
+        state.position += elapsed * rate;
+
+        state.position
+    }
+}
+
+/// The values a `scroll` effect showed, newest last. Shared by the clones of an effect like
+/// `Phase`.
+#[derive(Clone, Default)]
+pub struct ValueHistory(Arc<Mutex<ValueHistoryState>>);
+
+#[derive(Default)]
+struct ValueHistoryState {
+    tick: Option<u64>,
+    values: VecDeque<f32>,
+}
+
+impl fmt::Debug for ValueHistory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ValueHistory")
+    }
+}
+
+impl ValueHistory {
+    /// Appends `value`, once per sample `tick`, and returns the last `len` values, oldest first.
+    pub fn push(&self, tick: u64, value: f32, len: usize) -> Vec<f32> {
+        let mut state = self.0.lock().unwrap();
+        if state.tick != Some(tick) {
+            state.tick = Some(tick);
+            state.values.push_back(value);
+        }
+        while state.values.len() > len {
+            state.values.pop_front();
+        }
+
+        state.values.iter().copied().collect()
+    }
+}
+
+/// When a `macro` effect was first shown, which its steps count from. Shared by the clones of an
+/// effect like `Phase`.
+#[derive(Clone, Default)]
+pub struct MacroStart(Arc<Mutex<Option<f64>>>);
+
+impl fmt::Debug for MacroStart {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("MacroStart")
+    }
+}
+
+impl MacroStart {
+    /// The seconds since the first call after creating or resetting it.
+    pub fn elapsed(&self, time_secs: f64) -> f64 {
+        let mut started = self.0.lock().unwrap();
+
+        time_secs - *started.get_or_insert(time_secs)
+    }
+
+    pub fn reset(&self) {
+        *self.0.lock().unwrap() = None;
+    }
+}
+
+/// LEDs `start` to `end`, both included, of a `Segmented` effect.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ZoneSegment {
+    pub name: String,
+    pub start: usize,
+    pub end: usize,
+    pub metric: Metric,
+    pub effect: Effect,
+}
+
+/// One step of a `Macro` effect.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MacroStep {
+    pub effect: Effect,
+    pub duration_ms: u64,
+}
+
+/// Referenced by `cpu_cores` effects, so the sampler reads the per-core utilization.
+static CPU_CORE: Metric = Metric::CpuCore { index: 0 };
+
+impl Effect {
+    /// The colors of `led_count` LEDs at `value`, blending between colors in `color_space`.
+    pub fn render(
+        &self,
+        value: f32,
+        metrics: &MetricValues,
+        led_count: usize,
+        color_space: ColorSpace,
+    ) -> Vec<Color> {
+        match self {
+            Effect::Gradient {
+                start_color,
+                end_color,
+            } => {
+                generate_gradient_led_colors(value, start_color, end_color, led_count, color_space)
+            }
+            Effect::Block {
+                start_color,
+                end_color,
+            } => generate_block_led_colors(value, start_color, end_color, led_count, color_space),
+            Effect::Bands {
+                bands,
+                blend_width,
+                min,
+                max,
+            } => {
+                let native = min + value * (max - min);
+                vec![band_color(bands, *blend_width, native, color_space); led_count]
+            }
+            Effect::Solid { color } => vec![*color; led_count],
+            Effect::Colormap { colormap } => {
+                vec![colormap.sample(value, color_space); led_count]
+            }
+            Effect::Trend {
+                falling_color,
+                steady_color,
+                rising_color,
+            } => {
+                let color = if value < 0.0 {
+                    color_space.lerp(-value, steady_color, falling_color)
+                } else {
+                    color_space.lerp(value, steady_color, rising_color)
+                };
+                vec![color; led_count]
+            }
+            Effect::Comet {
+                tail_length,
+                head_color,
+                tail_color,
+                start_color,
+                end_color,
+                speed: None,
+                ..
+            } => generate_comet_led_colors(
+                value,
+                *tail_length,
+                (head_color, tail_color),
+                (start_color, end_color),
+                led_count,
+                color_space,
+            ),
+            Effect::Comet {
+                tail_length,
+                head_color,
+                tail_color,
+                speed: Some(speed),
+                phase,
+                ..
+            } => {
+                let position = phase.advance(metrics.time_secs, (value * speed) as f64);
+                generate_travelling_comet_led_colors(
+                    position,
+                    *tail_length,
+                    head_color,
+                    tail_color,
+                    led_count,
+                    color_space,
+                )
+            }
+            Effect::Breathing {
+                rate_hz,
+                color,
+                phase,
+            } => {
+                let breaths = phase.advance(metrics.time_secs, (value * rate_hz) as f64);
+                // Dark at the start of a breath, brightest halfway through.
+                let brightness = (1.0 - (TAU * breaths).cos()) as f32 / 2.0;
+
+                let mut colors = vec![*color; led_count];
+                scale_colors(&mut colors, [brightness; 3]);
+                colors
+            }
+            Effect::Scroll { colormap, history } => {
+                let values = history.push(metrics.tick, value, led_count);
+
+                let mut colors = vec![Color::new(0, 0, 0); led_count - values.len()];
+                colors.extend(
+                    values
+                        .into_iter()
+                        .map(|value| colormap.sample(value, color_space)),
+                );
+                colors
+            }
+            Effect::Split {
+                first,
+                second,
+                split_at,
+            } => {
+                let first_count = split_at.unwrap_or(led_count / 2).min(led_count);
+
+                let mut colors = first.render(metrics, first_count, color_space);
+                colors.extend(second.render(metrics, led_count - first_count, color_space));
+
+                colors
+            }
+            Effect::Segmented { segments } => {
+                generate_segmented_colors(segments, metrics, led_count, color_space)
+            }
+            Effect::Layered { layers } => {
+                generate_layered_colors(layers, metrics, led_count, color_space)
+            }
+            Effect::Blend { a, b, mode, mix } => {
+                let a = a.render(value, metrics, led_count, color_space);
+                let b = b.render(value, metrics, led_count, color_space);
+
+                ColorMixer::blend(&a, &b, *mode, *mix)
+            }
+            Effect::Alert {
+                alert,
+                color,
+                flash_rate_hz,
+                base,
+            } => {
+                let flash_on = (metrics.time_secs * *flash_rate_hz as f64).fract() < 0.5;
+                if metrics.get(alert).unwrap_or_default() >= 0.5 && flash_on {
+                    vec![*color; led_count]
+                } else {
+                    base.render(value, metrics, led_count, color_space)
+                }
+            }
+            Effect::PeakMarker {
+                marker,
+                color,
+                base,
+            } => {
+                let mut colors = base.render(value, metrics, led_count, color_space);
+                let marker_value = metrics.get(marker).unwrap_or_default();
+                if let Some(led) =
+                    ((marker_value * led_count as f32).ceil() as usize).checked_sub(1)
+                {
+                    colors[led.min(led_count - 1)] = *color;
+                }
+
+                colors
+            }
+            Effect::CpuCores {
+                start_color,
+                end_color,
+            } => generate_cpu_cores_led_colors(
+                &metrics.cpu_cores,
+                start_color,
+                end_color,
+                led_count,
+                color_space,
+            ),
+            #[cfg(feature = "lua")]
+            Effect::LuaScript { path, script } => {
+                script.render(path, value, metrics.time_secs, led_count)
+            }
+            #[cfg(feature = "wasm")]
+            Effect::Wasm { path, module } => {
+                module.render(path, value, metrics.time_secs, led_count)
+            }
+            Effect::PaletteCycle {
+                palettes,
+                transition_secs,
+                hold_secs,
+            } => {
+                let palette =
+                    current_palette(palettes, *transition_secs, *hold_secs, metrics.time_secs);
+                let last = led_count.saturating_sub(1).max(1) as f32;
+
+                (0..led_count)
+                    .map(|led| lerp_color_stops(&palette, led as f32 / last, color_space))
+                    .collect()
+            }
+            Effect::Macro {
+                steps,
+                repeat,
+                base,
+                started,
+            } => {
+                let elapsed_secs = started.elapsed(metrics.time_secs);
+                let effect = current_macro_step(steps, *repeat, elapsed_secs)
+                    .map(|step| &step.effect)
+                    .or(base.as_deref())
+                    .or(steps.last().map(|step| &step.effect));
+
+                match effect {
+                    Some(effect) => effect.render(value, metrics, led_count, color_space),
+                    None => vec![Color::new(0, 0, 0); led_count],
+                }
+            }
+            // Resolved by the renderer, which knows the other controllers' colors.
+            Effect::MirrorOf { .. } => vec![Color::new(0, 0, 0); led_count],
+        }
+    }
+
+    /// Starts the timelines of the effect and the ones nested in it over, e.g. of a `macro` in a
+    /// profile that was just switched to.
+    pub fn restart(&self) {
+        if let Effect::Macro { started, .. } = self {
+            started.reset();
+        }
+
+        for child in self.children() {
+            child.restart();
+        }
+    }
+
+    /// Whether the effect, or one nested in it, changes with time rather than only with its
+    /// metric, so it needs frames between samples.
+    pub fn is_animated(&self) -> bool {
+        let animated = match self {
+            Effect::Comet { speed, .. } => speed.is_some(),
+            Effect::Breathing { .. }
+            | Effect::Alert { .. }
+            | Effect::PaletteCycle { .. }
+            | Effect::Macro { .. } => true,
+            #[cfg(feature = "lua")]
+            Effect::LuaScript { .. } => true,
+            #[cfg(feature = "wasm")]
+            Effect::Wasm { .. } => true,
+            _ => false,
+        };
+
+        animated || self.children().into_iter().any(Effect::is_animated)
+    }
+
+    /// The effects nested in this one.
+    fn children(&self) -> Vec<&Effect> {
+        match self {
+            Effect::Split { first, second, .. } => vec![&first.effect, &second.effect],
+            Effect::Segmented { segments: layers } | Effect::Layered { layers } => {
+                layers.iter().map(|layer| &layer.effect).collect()
+            }
+            Effect::Blend { a, b, .. } => vec![a.as_ref(), b.as_ref()],
+            Effect::Alert { base, .. } | Effect::PeakMarker { base, .. } => vec![base.as_ref()],
+            Effect::Macro { steps, base, .. } => steps
+                .iter()
+                .map(|step| &step.effect)
+                .chain(base.as_deref())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Checks what serde can't, recursing into nested effects.
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        match self {
+            Effect::Segmented { segments } => {
+                let mut next = 0;
+                for segment in segments {
+                    if segment.start != next || segment.end < segment.start {
+                        return Err(format!(
+                            "segment {:?} must start at LED {next} and not end before it starts",
+                            segment.name
+                        )
+                        .into());
+                    }
+                    next = segment.end + 1;
+
+                    segment
+                        .effect
+                        .check_led_count(segment.end - segment.start + 1)?;
+                }
+            }
+            Effect::Layered { layers } => {
+                for layer in layers {
+                    if layer.end < layer.start {
+                        return Err(format!(
+                            "layer {:?} must not end before it starts",
+                            layer.name
+                        )
+                        .into());
+                    }
+
+                    layer.effect.check_led_count(layer.end - layer.start + 1)?;
+                }
+            }
+            Effect::Bands {
+                bands, blend_width, ..
+            } => {
+                if bands.is_empty() {
+                    return Err("bands needs at least one band".into());
+                }
+                if bands
+                    .windows(2)
+                    .any(|pair| pair[0].threshold >= pair[1].threshold)
+                {
+                    return Err("bands thresholds must be in ascending order".into());
+                }
+                if *blend_width < 0.0 {
+                    return Err(
+                        format!("bands blend_width {blend_width} must not be negative").into(),
+                    );
+                }
+            }
+            Effect::PaletteCycle { palettes, .. } => {
+                if palettes.is_empty() {
+                    return Err("palette_cycle needs at least one palette".into());
+                }
+                if palettes.iter().any(Vec::is_empty) {
+                    return Err("palette_cycle palettes need at least one stop".into());
+                }
+                if palettes
+                    .iter()
+                    .any(|palette| palette.len() != palettes[0].len())
+                {
+                    return Err(
+                        "palette_cycle palettes must all have the same number of stops".into(),
+                    );
+                }
+            }
+            _ => {}
+        }
+
+        for child in self.children() {
+            // Nested effects are rendered without the other controllers' colors.
+            if matches!(child, Effect::MirrorOf { .. }) {
+                return Err(
+                    "mirror_of only works as a zone's effect, not nested in another".into(),
+                );
+            }
+            child.validate()?;
+        }
+
+        Ok(())
+    }
+
+    /// Fails when the effect can't be drawn on `led_count` LEDs, e.g. a `split` past the end.
+    pub fn check_led_count(&self, led_count: usize) -> Result<(), Box<dyn Error>> {
+        match self {
+            Effect::Split {
+                split_at: Some(split_at),
+                ..
+            } if *split_at > led_count => {
+                Err(format!("split_at {split_at} is past the end of its {led_count} LEDs").into())
+            }
+            // `validate` checked that they follow each other without gaps.
+            Effect::Segmented { segments }
+                if segments.last().map_or(0, |segment| segment.end + 1) != led_count =>
+            {
+                Err(format!("the segments must end at the last of its {led_count} LEDs").into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn collect_metrics<'a>(&'a self, metrics: &mut Vec<&'a Metric>) {
+        match self {
+            Effect::Split { first, second, .. } => {
+                for half in [first, second] {
+                    metrics.push(&half.metric);
+                    half.effect.collect_metrics(metrics);
+                }
+            }
+            Effect::Segmented { segments: layers } | Effect::Layered { layers } => {
+                for layer in layers {
+                    metrics.push(&layer.metric);
+                    layer.effect.collect_metrics(metrics);
+                }
+            }
+            Effect::Blend { a, b, .. } => {
+                a.collect_metrics(metrics);
+                b.collect_metrics(metrics);
+            }
+            Effect::Alert { alert, base, .. } => {
+                metrics.push(alert);
+                base.collect_metrics(metrics);
+            }
+            Effect::PeakMarker { marker, base, .. } => {
+                metrics.push(marker);
+                base.collect_metrics(metrics);
+            }
+            Effect::CpuCores { .. } => metrics.push(&CPU_CORE),
+            Effect::Macro { steps, base, .. } => {
+                for step in steps {
+                    step.effect.collect_metrics(metrics);
+                }
+                if let Some(base) = base {
+                    base.collect_metrics(metrics);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl MetricEffect {
+    pub fn render(
+        &self,
+        metrics: &MetricValues,
+        led_count: usize,
+        color_space: ColorSpace,
+    ) -> Vec<Color> {
+        self.effect.render(
+            metrics.get(&self.metric).unwrap_or_default(),
+            metrics,
+            led_count,
+            color_space,
+        )
+    }
+}
+
+/// Concatenates the segments' colors, cut to `size` LEDs. LEDs past the last segment are black.
+fn generate_segmented_colors(
+    segments: &[ZoneSegment],
+    metrics: &MetricValues,
+    size: usize,
+    color_space: ColorSpace,
+) -> Vec<Color> {
+    let mut colors = Vec::with_capacity(size);
+    for segment in segments {
+        let value = metrics.get(&segment.metric).unwrap_or_default();
+        colors.extend(segment.effect.render(
+            value,
+            metrics,
+            segment.end - segment.start + 1,
+            color_space,
+        ));
+    }
+    colors.resize(size, Color::new(0, 0, 0));
+
+    colors
+}
+
+/// The color of the last band whose threshold `value` reached, or of the first band below them
+/// all, blended into the next band's color within `blend_width` of its threshold.
+fn band_color(bands: &[Band], blend_width: f32, value: f32, color_space: ColorSpace) -> Color {
+    let current = bands
+        .iter()
+        .rposition(|band| value >= band.threshold)
+        .unwrap_or_default();
+    let color = bands[current].color;
+
+    match bands.get(current + 1) {
+        Some(next) if blend_width > 0.0 && value > next.threshold - blend_width => {
+            let progress = (value - (next.threshold - blend_width)) / blend_width;
+            color_space.lerp(progress, &color, &next.color)
+        }
+        _ => color,
+    }
+}
+
+/// Draws each layer over the LEDs `start` to `end` it covers, cut to `size` LEDs.
+fn generate_layered_colors(
+    layers: &[ZoneSegment],
+    metrics: &MetricValues,
+    size: usize,
+    color_space: ColorSpace,
+) -> Vec<Color> {
+    let mut colors = vec![Color::new(0, 0, 0); size];
+    for layer in layers {
+        let value = metrics.get(&layer.metric).unwrap_or_default();
+        let layer_colors =
+            layer
+                .effect
+                .render(value, metrics, layer.end - layer.start + 1, color_space);
+
+        for (led, color) in (layer.start..size).zip(layer_colors) {
+            colors[led] = color;
+        }
+    }
+
+    colors
+}
+
+fn generate_cpu_cores_led_colors(
+    cpu_cores: &[f32],
+    start_color: &Color,
+    end_color: &Color,
+    size: usize,
+    color_space: ColorSpace,
+) -> Vec<Color> {
+    if cpu_cores.is_empty() {
+        return vec![*start_color; size];
+    }
+
+    (0..size)
+        .map(|led| {
+            let core = led * cpu_cores.len() / size;
+            color_space.lerp(cpu_cores[core], start_color, end_color)
+        })
+        .collect()
+}
+
+fn generate_travelling_comet_led_colors(
+    position: f64,
+    tail_length: usize,
+    head_color: &Color,
+    tail_color: &Color,
+    size: usize,
+    color_space: ColorSpace,
+) -> Vec<Color> {
+    let mut colors = vec![Color::new(0, 0, 0); size];
+
+    // The head runs on past the end until the tail is gone too.
+    let track_length = size + tail_length;
+    if track_length == 0 {
+        return colors;
+    }
+    let head = position.rem_euclid(track_length as f64) as usize;
+
+    for distance in 0..=tail_length.min(head) {
+        if let Some(color) = colors.get_mut(head - distance) {
+            *color = if distance == 0 {
+                *head_color
+            } else {
+                color_space.lerp(distance as f32 / tail_length as f32, head_color, tail_color)
+            };
+        }
+    }
+
+    colors
+}
+
+/// The palette showing at `time_secs`, blended between two during a transition.
+fn current_palette(
+    palettes: &[Vec<ColorStop>],
+    transition_secs: f32,
+    hold_secs: f32,
+    time_secs: f64,
+) -> Vec<ColorStop> {
+    let period = (hold_secs + transition_secs) as f64;
+    if palettes.len() < 2 || period <= 0.0 {
+        return palettes.first().cloned().unwrap_or_default();
+    }
+
+    let cycle = (time_secs / period) as usize;
+    let current = &palettes[cycle % palettes.len()];
+    let next = &palettes[(cycle + 1) % palettes.len()];
+
+    let into_period = (time_secs % period) as f32;
+    if into_period < hold_secs {
+        return current.clone();
+    }
+
+    blend_palettes(current, next, (into_period - hold_secs) / transition_secs)
+}
+
+/// The step playing `elapsed_secs` into the macro, `None` once a non-repeating macro is over.
+fn current_macro_step(steps: &[MacroStep], repeat: bool, elapsed_secs: f64) -> Option<&MacroStep> {
+    let total_ms: u64 = steps.iter().map(|step| step.duration_ms).sum();
+    if total_ms == 0 {
+        return None;
+    }
+
+    let mut elapsed_ms = (elapsed_secs * 1000.0) as u64;
+    if repeat {
+        elapsed_ms %= total_ms;
+    }
+
+    steps.iter().find(|step| {
+        if elapsed_ms < step.duration_ms {
+            return true;
+        }
+
+        elapsed_ms -= step.duration_ms;
+        false
+    })
+}
+
+/// The comet's `(head_color, tail_color)` over the base gradient's `(start_color, end_color)`.
+fn generate_comet_led_colors(
+    value: f32,
+    tail_length: usize,
+    (head_color, tail_color): (&Color, &Color),
+    (start_color, end_color): (&Color, &Color),
+    size: usize,
+    color_space: ColorSpace,
+) -> Vec<Color> {
+    let mut colors = generate_gradient_led_colors(value, start_color, end_color, size, color_space);
+    if size == 0 {
+        return colors;
+    }
+
+    let head = (value.clamp(0.0, 1.0) * (size - 1) as f32).round() as usize;
+    colors[head] = *head_color;
+
+    for distance in 1..=tail_length.min(head) {
+        colors[head - distance] =
+            color_space.lerp(distance as f32 / tail_length as f32, head_color, tail_color);
+    }
+
+    colors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(r: u8) -> Effect {
+        Effect::Solid {
+            color: Color::new(r, 0, 0),
+        }
+    }
+
+    fn render_at(effect: &Effect, time_secs: f64) -> Color {
+        let metrics = MetricValues {
+            time_secs,
+            ..Default::default()
+        };
+
+        effect.render(0.0, &metrics, 1, ColorSpace::Rgb)[0]
+    }
+
+    #[test]
+    fn segments_cover_their_leds_in_order() {
+        let segment = |name: &str, start, end, metric, end_color| ZoneSegment {
+            name: name.to_string(),
+            start,
+            end,
+            metric: Metric::Constant(metric),
+            effect: Effect::Block {
+                start_color: Color::new(0, 0, 0),
+                end_color,
+            },
+        };
+        let effect = Effect::Segmented {
+            segments: vec![
+                segment("top", 0, 7, 1.0, Color::new(100, 0, 0)),
+                segment("middle", 8, 15, 0.5, Color::new(0, 200, 0)),
+                segment("bottom", 16, 23, 1.0, Color::new(0, 0, 100)),
+            ],
+        };
+        effect.validate().unwrap();
+        effect.check_led_count(24).unwrap();
+        assert!(effect.check_led_count(23).is_err());
+        assert!(effect.check_led_count(30).is_err());
+
+        let colors = effect.render(0.0, &MetricValues::default(), 24, ColorSpace::Rgb);
+
+        assert_eq!(colors.len(), 24);
+        assert_eq!(colors[7], Color::new(100, 0, 0));
+        assert_eq!(colors[8], Color::new(0, 100, 0));
+        assert_eq!(colors[15], Color::new(0, 100, 0));
+        assert_eq!(colors[16], Color::new(0, 0, 100));
+    }
+
+    #[test]
+    fn macro_steps_count_from_when_it_is_first_shown() {
+        let effect = Effect::Macro {
+            steps: vec![
+                MacroStep {
+                    effect: solid(1),
+                    duration_ms: 2000,
+                },
+                MacroStep {
+                    effect: solid(2),
+                    duration_ms: 1000,
+                },
+            ],
+            repeat: false,
+            base: Some(Box::new(solid(3))),
+            started: MacroStart::default(),
+        };
+
+        // Shown for the first time long after startup.
+        assert_eq!(render_at(&effect, 100.0), Color::new(1, 0, 0));
+        assert_eq!(render_at(&effect, 101.999), Color::new(1, 0, 0));
+        assert_eq!(render_at(&effect, 102.0), Color::new(2, 0, 0));
+        assert_eq!(render_at(&effect, 103.0), Color::new(3, 0, 0));
+        assert_eq!(render_at(&effect, 500.0), Color::new(3, 0, 0));
+
+        effect.restart();
+        assert_eq!(render_at(&effect, 600.0), Color::new(1, 0, 0));
+        assert_eq!(render_at(&effect, 602.5), Color::new(2, 0, 0));
+    }
+
+    #[test]
+    fn rejects_nested_mirrors_and_empty_palettes() {
+        let mirror = Effect::MirrorOf {
+            source_controller: "Left".to_string(),
+            horizontal_flip: true,
+            vertical_flip: false,
+        };
+        assert!(mirror.validate().is_ok());
+
+        let nested = Effect::Layered {
+            layers: vec![ZoneSegment {
+                name: "Ring".to_string(),
+                start: 0,
+                end: 3,
+                metric: Metric::CpuUsage,
+                effect: Effect::Blend {
+                    a: Box::new(solid(1)),
+                    b: Box::new(mirror),
+                    mode: BlendMode::Mix,
+                    mix: 0.5,
+                },
+            }],
+        };
+        assert!(nested.validate().is_err());
+
+        let palette_cycle = Effect::PaletteCycle {
+            palettes: vec![Vec::new()],
+            transition_secs: 1.0,
+            hold_secs: 1.0,
+        };
+        assert!(palette_cycle.validate().is_err());
+    }
+
+    #[test]
+    fn nested_macros_restart_too() {
+        let effect = Effect::Blend {
+            a: Box::new(solid(0)),
+            b: Box::new(Effect::Macro {
+                steps: vec![MacroStep {
+                    effect: solid(1),
+                    duration_ms: 1000,
+                }],
+                repeat: false,
+                base: Some(Box::new(solid(2))),
+                started: MacroStart::default(),
+            }),
+            mode: BlendMode::Mix,
+            mix: 1.0,
+        };
+
+        assert_eq!(render_at(&effect, 10.0), Color::new(1, 0, 0));
+        assert_eq!(render_at(&effect, 20.0), Color::new(2, 0, 0));
+        effect.restart();
+        assert_eq!(render_at(&effect, 30.0), Color::new(1, 0, 0));
+    }
+
+    #[test]
+    fn blend_reads_alpha_as_mix() {
+        let effect: Effect = serde_json::from_str(
+            r##"{
+                "type": "blend",
+                "alpha": 0.25,
+                "a": { "type": "solid", "color": "#000000" },
+                "b": { "type": "solid", "color": "#FFFFFF" }
+            }"##,
+        )
+        .unwrap();
+
+        assert!(matches!(effect, Effect::Blend { mix, .. } if mix == 0.25));
+    }
+
+    #[test]
+    fn layers_animate_independently() {
+        let red = Color::new(255, 0, 0);
+        let layer = |start, end, rate_hz| ZoneSegment {
+            name: format!("{rate_hz} Hz"),
+            start,
+            end,
+            metric: Metric::Constant(1.0),
+            effect: Effect::Breathing {
+                rate_hz,
+                color: red,
+                phase: Phase::default(),
+            },
+        };
+        let effect = Effect::Layered {
+            layers: vec![layer(0, 1, 1.0), layer(2, 3, 2.0)],
+        };
+        let render = |time_secs| {
+            let metrics = MetricValues {
+                time_secs,
+                ..Default::default()
+            };
+            effect.render(1.0, &metrics, 4, ColorSpace::Rgb)
+        };
+        let black = Color::new(0, 0, 0);
+
+        assert_eq!(render(0.0), [black; 4]);
+        // Half a breath of the 1 Hz layer, a whole one of the 2 Hz layer. Each layer's phase moves
+        // on by itself, a shared one would only move for the first.
+        assert_eq!(render(0.5), [red, red, black, black]);
+    }
+
+    #[test]
+    fn trend_blends_away_from_steady() {
+        let effect: Effect = serde_json::from_str(
+            r##"{
+                "type": "delta_color",
+                "decrease_color": "#00FF00",
+                "neutral_color": "#000000",
+                "increase_color": "#FF0000"
+            }"##,
+        )
+        .unwrap();
+        let render = |value| effect.render(value, &MetricValues::default(), 1, ColorSpace::Rgb)[0];
+
+        assert_eq!(render(-1.0), Color::new(0, 255, 0));
+        assert_eq!(render(0.0), Color::new(0, 0, 0));
+        assert_eq!(render(1.0), Color::new(255, 0, 0));
+    }
+
+    #[test]
+    fn scroll_advances_once_per_sample() {
+        let effect = Effect::Scroll {
+            colormap: Colormap::Viridis,
+            history: ValueHistory::default(),
+        };
+        let render = |tick, time_secs, value| {
+            let metrics = MetricValues {
+                tick,
+                time_secs,
+                ..Default::default()
+            };
+            effect.render(value, &metrics, 3, ColorSpace::Rgb)
+        };
+        let black = Color::new(0, 0, 0);
+
+        let first = render(1, 0.5, 1.0);
+        assert_eq!(first[..2], [black; 2]);
+        // Another controller with a phase offset renders the same sample at another time.
+        assert_eq!(render(1, 2.5, 1.0), first);
+
+        let second = render(2, 1.0, 0.0);
+        assert_eq!(second[..1], [black]);
+        assert_eq!(second[1], first[2]);
+        assert_ne!(second[2], first[2]);
+    }
+
+    #[test]
+    fn comet_runs_over_its_base_gradient() {
+        let head_color = Color::new(255, 255, 255);
+        let tail_color = Color::new(0, 0, 64);
+        let start_color = Color::new(0, 255, 0);
+        let end_color = Color::new(255, 0, 0);
+        let effect = Effect::Comet {
+            tail_length: 3,
+            head_color,
+            tail_color,
+            start_color,
+            end_color,
+            speed: None,
+            phase: Phase::default(),
+        };
+
+        // The head at 0.5 * 10 = LED 5.
+        let colors = effect.render(0.5, &MetricValues::default(), 11, ColorSpace::Rgb);
+
+        assert_eq!(colors[5], head_color);
+        // Fading into the tail color, which the last tail LED gets.
+        assert_eq!(
+            colors[4],
+            ColorSpace::Rgb.lerp(1.0 / 3.0, &head_color, &tail_color)
+        );
+        assert_eq!(
+            colors[3],
+            ColorSpace::Rgb.lerp(2.0 / 3.0, &head_color, &tail_color)
+        );
+        assert_eq!(colors[2], tail_color);
+        // Behind the tail and ahead of the head, the base gradient.
+        assert_eq!(colors[..2], [end_color; 2]);
+        assert_eq!(colors[6..], [start_color; 5]);
+    }
+}
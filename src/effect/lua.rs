@@ -0,0 +1,133 @@
+use mlua::{Function, Lua, LuaOptions, StdLib, Table, Value};
+use openrgb::data::Color;
+use std::{
+    error::Error,
+    fmt, fs,
+    path::Path,
+    sync::{Arc, OnceLock},
+};
+use tracing::warn;
+
+/// The script of a `lua_script` effect, compiled on first use and kept for the following ticks.
+#[derive(Clone, Default)]
+pub struct LuaScriptCache(Arc<OnceLock<Option<LuaScript>>>);
+
+impl fmt::Debug for LuaScriptCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("LuaScriptCache")
+    }
+}
+
+impl LuaScriptCache {
+    /// Runs the script at `path`. LEDs it doesn't return a color for, or all of them when it
+    /// fails, are black.
+    pub fn render(&self, path: &Path, value: f32, time_secs: f64, led_count: usize) -> Vec<Color> {
+        let script = self.0.get_or_init(|| match LuaScript::load(path) {
+            Ok(script) => Some(script),
+            Err(e) => {
+                warn!("Failed to load Lua script {}: {e}", path.display());
+                None
+            }
+        });
+
+        let mut colors = match script
+            .as_ref()
+            .map(|script| script.call(value, time_secs, led_count))
+        {
+            Some(Ok(colors)) => colors,
+            Some(Err(e)) => {
+                warn!("Lua script {} failed: {e}", path.display());
+                Vec::new()
+            }
+            None => Vec::new(),
+        };
+        colors.resize(led_count, Color::new(0, 0, 0));
+
+        colors
+    }
+}
+
+struct LuaScript {
+    lua: Lua,
+    function: Function,
+}
+
+impl LuaScript {
+    fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let source = fs::read_to_string(path)?;
+
+        // Without io, os, package and debug, scripts can't reach the filesystem or the network.
+        let lua = Lua::new_with(
+            StdLib::TABLE | StdLib::STRING | StdLib::MATH,
+            LuaOptions::default(),
+        )?;
+        {
+            let globals = lua.globals();
+            for name in ["dofile", "loadfile", "load", "require"] {
+                globals.set(name, Value::Nil)?;
+            }
+        }
+
+        // The script returns the function called every tick.
+        let function = lua
+            .load(source)
+            .set_name(path.display().to_string())
+            .eval::<Function>()?;
+
+        Ok(Self { lua, function })
+    }
+
+    fn call(&self, value: f32, time_secs: f64, led_count: usize) -> mlua::Result<Vec<Color>> {
+        let input = self.lua.create_table()?;
+        input.set("value", value)?;
+        input.set("led_count", led_count as u32)?;
+        input.set("time_secs", time_secs)?;
+
+        let leds: Vec<Table> = self.function.call(input)?;
+        leds.iter()
+            .map(|led| Ok(Color::new(led.get("r")?, led.get("g")?, led.get("b")?)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    #[test]
+    fn renders_the_colors_the_script_returns() {
+        let path = env::temp_dir().join(format!("lua_script_test_{}.lua", process::id()));
+        fs::write(
+            &path,
+            r#"
+            return function(input)
+                local leds = {}
+                for i = 1, input.led_count do
+                    leds[i] = { r = 255, g = 0, b = 0 }
+                end
+                return leds
+            end
+            "#,
+        )
+        .unwrap();
+
+        let cache = LuaScriptCache::default();
+        let first = cache.render(&path, 0.5, 0.0, 4);
+        fs::remove_file(&path).unwrap();
+        // Compiled once, so it keeps running after the file is gone.
+        let second = cache.render(&path, 0.5, 1.0, 4);
+
+        assert_eq!(first, [Color::new(255, 0, 0); 4]);
+        assert_eq!(second, first);
+    }
+
+    #[test]
+    fn a_missing_script_renders_black() {
+        let path = env::temp_dir().join(format!("lua_script_missing_{}.lua", process::id()));
+
+        let colors = LuaScriptCache::default().render(&path, 0.5, 0.0, 2);
+
+        assert_eq!(colors, [Color::new(0, 0, 0); 2]);
+    }
+}
@@ -0,0 +1,146 @@
+use openrgb::data::Color;
+use std::{
+    error::Error,
+    fmt,
+    path::Path,
+    sync::{Arc, Mutex, OnceLock},
+};
+use tracing::warn;
+use wasmtime::{Engine, Instance, Memory, Module, Store, TypedFunc};
+
+/// The module of a `wasm` effect, instantiated on first use and kept for the following ticks.
+#[derive(Clone, Default)]
+pub struct WasmModuleCache(Arc<OnceLock<Option<Mutex<WasmModule>>>>);
+
+impl fmt::Debug for WasmModuleCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("WasmModuleCache")
+    }
+}
+
+impl WasmModuleCache {
+    /// Runs the module at `path`. All LEDs are black when it fails.
+    pub fn render(&self, path: &Path, value: f32, time_secs: f64, led_count: usize) -> Vec<Color> {
+        let module = self.0.get_or_init(|| match WasmModule::load(path) {
+            Ok(module) => Some(Mutex::new(module)),
+            Err(e) => {
+                warn!("Failed to load WASM module {}: {e}", path.display());
+                None
+            }
+        });
+
+        let colors = module
+            .as_ref()
+            .map(|module| module.lock().unwrap().call(value, time_secs, led_count));
+        match colors {
+            Some(Ok(colors)) => colors,
+            Some(Err(e)) => {
+                warn!("WASM module {} failed: {e}", path.display());
+                vec![Color::new(0, 0, 0); led_count]
+            }
+            None => vec![Color::new(0, 0, 0); led_count],
+        }
+    }
+}
+
+struct WasmModule {
+    store: Store<()>,
+    compute: TypedFunc<(f32, u32, f64), ()>,
+    memory: Memory,
+}
+
+impl WasmModule {
+    fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let engine = Engine::default();
+        // Either a binary module or its text format.
+        let module = Module::from_file(&engine, path)?;
+
+        // No imports, the module can't do anything but compute.
+        let mut store = Store::new(&engine, ());
+        let instance = Instance::new(&mut store, &module, &[])?;
+        let compute = instance.get_typed_func(&mut store, "compute")?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or("the module exports no memory")?;
+
+        Ok(Self {
+            store,
+            compute,
+            memory,
+        })
+    }
+
+    /// Calls `compute`, which leaves `[r0, g0, b0, r1, g1, b1, ...]` at the start of the memory.
+    fn call(
+        &mut self,
+        value: f32,
+        time_secs: f64,
+        led_count: usize,
+    ) -> Result<Vec<Color>, Box<dyn Error>> {
+        self.compute
+            .call(&mut self.store, (value, led_count as u32, time_secs))?;
+
+        let output = self
+            .memory
+            .data(&self.store)
+            .get(..led_count * 3)
+            .ok_or("the memory is smaller than the output")?;
+
+        Ok(output
+            .chunks_exact(3)
+            .map(|rgb| Color::new(rgb[0], rgb[1], rgb[2]))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, process};
+
+    /// Lights every LED red at `value * 255`.
+    const RED_BY_VALUE: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (func (export "compute") (param $value f32) (param $led_count i32) (param $time f64)
+                (local $offset i32)
+                (local $red i32)
+                (local.set $red
+                    (i32.trunc_f32_u (f32.mul (local.get $value) (f32.const 255))))
+                (block $done
+                    (loop $next
+                        (br_if $done
+                            (i32.ge_u (local.get $offset)
+                                (i32.mul (local.get $led_count) (i32.const 3))))
+                        (i32.store8 (local.get $offset) (local.get $red))
+                        (i32.store8 (i32.add (local.get $offset) (i32.const 1)) (i32.const 0))
+                        (i32.store8 (i32.add (local.get $offset) (i32.const 2)) (i32.const 0))
+                        (local.set $offset (i32.add (local.get $offset) (i32.const 3)))
+                        (br $next)))))
+    "#;
+
+    #[test]
+    fn renders_what_the_module_computes() {
+        let path = env::temp_dir().join(format!("wasm_module_test_{}.wat", process::id()));
+        fs::write(&path, RED_BY_VALUE).unwrap();
+
+        let cache = WasmModuleCache::default();
+        let full = cache.render(&path, 1.0, 0.0, 3);
+        let half = cache.render(&path, 0.5, 0.1, 2);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(full, [Color::new(255, 0, 0); 3]);
+        assert_eq!(half, [Color::new(127, 0, 0); 2]);
+    }
+
+    #[test]
+    fn a_module_without_compute_renders_black() {
+        let path = env::temp_dir().join(format!("wasm_module_empty_{}.wat", process::id()));
+        fs::write(&path, r#"(module (memory (export "memory") 1))"#).unwrap();
+
+        let colors = WasmModuleCache::default().render(&path, 1.0, 0.0, 2);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(colors, [Color::new(0, 0, 0); 2]);
+    }
+}
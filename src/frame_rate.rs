@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::time::Instant;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AdaptiveFrameRateConfig {
+    /// The machine counts as idle while every controller's metric is below this.
+    pub activity_floor: f32,
+    /// How long it has to be idle before the frame rate drops.
+    pub idle_after_secs: f32,
+    /// Frame interval when idle. Frames never take less than the metric sampling.
+    pub max_interval_ms: u64,
+}
+
+impl Default for AdaptiveFrameRateConfig {
+    fn default() -> Self {
+        Self {
+            activity_floor: 0.1,
+            idle_after_secs: 30.0,
+            max_interval_ms: 2000,
+        }
+    }
+}
+
+/// Slows the render loop down while the machine is idle, and speeds it up again at the first sign
+/// of activity, so the client barely does any work during long idle periods.
+pub struct AdaptiveFrameRate {
+    config: AdaptiveFrameRateConfig,
+    idle_since: Option<Instant>,
+    interval: Duration,
+}
+
+impl AdaptiveFrameRate {
+    pub fn new(config: &AdaptiveFrameRateConfig) -> Self {
+        Self {
+            config: config.clone(),
+            idle_since: None,
+            interval: Duration::ZERO,
+        }
+    }
+
+    /// Takes the highest metric value driving a controller and returns the frame interval.
+    pub fn update(&mut self, activity: f32) -> Duration {
+        if activity >= self.config.activity_floor {
+            self.idle_since = None;
+            self.interval = Duration::ZERO;
+            return self.interval;
+        }
+
+        let idle_since = *self.idle_since.get_or_insert_with(Instant::now);
+        if idle_since.elapsed().as_secs_f32() >= self.config.idle_after_secs {
+            // Ramp down gradually rather than jumping straight to the slowest rate.
+            let max_interval = Duration::from_millis(self.config.max_interval_ms);
+            self.interval = (self.interval * 2)
+                .max(Duration::from_millis(250))
+                .min(max_interval);
+        }
+
+        self.interval
+    }
+}
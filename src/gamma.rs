@@ -0,0 +1,45 @@
+use openrgb::data::Color;
+use std::collections::HashMap;
+
+/// Gamma correction lookup tables, one per distinct gamma, so correcting a color is three lookups.
+#[derive(Default)]
+pub struct GammaTables {
+    /// By the bits of the gamma.
+    tables: HashMap<u32, [u8; 256]>,
+}
+
+impl GammaTables {
+    /// Precomputes the tables of `gammas`.
+    pub fn new(gammas: impl IntoIterator<Item = f32>) -> Self {
+        let mut tables = Self::default();
+        for gamma in gammas {
+            tables.table(gamma);
+        }
+
+        tables
+    }
+
+    /// Raises every channel, from 0.0 to 1.0, to the power of `gamma`.
+    pub fn apply(&mut self, gamma: f32, colors: &mut [Color]) {
+        if gamma == 1.0 {
+            return;
+        }
+
+        let table = self.table(gamma);
+        for color in colors {
+            *color = Color::new(
+                table[color.r as usize],
+                table[color.g as usize],
+                table[color.b as usize],
+            );
+        }
+    }
+
+    fn table(&mut self, gamma: f32) -> &[u8; 256] {
+        self.tables.entry(gamma.to_bits()).or_insert_with(|| {
+            std::array::from_fn(|channel| {
+                ((channel as f32 / 255.0).powf(gamma) * 255.0).round() as u8
+            })
+        })
+    }
+}
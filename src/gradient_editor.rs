@@ -0,0 +1,494 @@
+//! `--edit-gradient`: tunes the color stops of a controller's gradient in the terminal, trying them
+//! on the hardware and saving them to the config without a restart in between.
+
+use crate::{
+    color::{lerp_color_stops, ColorSpace, ColorStop},
+    config::Config,
+    effect::Effect,
+    pool::ConnectionPool,
+    preview::gradient_bar,
+};
+use crossterm::{
+    cursor::MoveTo,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind},
+    execute,
+    terminal::{self, Clear, ClearType},
+};
+use openrgb::data::Color;
+use std::{
+    error::Error,
+    fs,
+    io::{self, Write},
+};
+
+const BAR_WIDTH: usize = 60;
+const POSITION_STEP: f32 = 0.01;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Channel {
+    Red,
+    Green,
+    Blue,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EditorCommand {
+    AddStop,
+    RemoveStop,
+    SelectPrevious,
+    SelectNext,
+    /// Moves the selected stop by this much.
+    MoveStop(f32),
+    /// Changes a channel of the selected stop's color by this much.
+    AdjustChannel(Channel, i16),
+    /// Sets a channel of the selected stop's color, from 0 to 9 for 0 to 255.
+    SetChannel(Channel, u8),
+    Apply,
+    Save,
+    Quit,
+}
+
+/// Turns key presses into commands. A channel key (`r`, `g` or `b`) waits for the next key: `+`
+/// and `-` change the channel by 1, a digit sets it.
+#[derive(Debug, Default)]
+pub struct CommandParser {
+    channel: Option<Channel>,
+}
+
+impl CommandParser {
+    /// The command the keys so far make up, if any.
+    pub fn feed(&mut self, key: KeyCode) -> Option<EditorCommand> {
+        if let Some(channel) = self.channel.take() {
+            return match key {
+                KeyCode::Char('+') => Some(EditorCommand::AdjustChannel(channel, 1)),
+                KeyCode::Char('-') => Some(EditorCommand::AdjustChannel(channel, -1)),
+                KeyCode::Char(digit @ '0'..='9') => {
+                    Some(EditorCommand::SetChannel(channel, digit as u8 - b'0'))
+                }
+                // Anything else starts over.
+                key => self.feed(key),
+            };
+        }
+
+        match key {
+            KeyCode::Char('r') => self.channel = Some(Channel::Red),
+            KeyCode::Char('g') => self.channel = Some(Channel::Green),
+            KeyCode::Char('b') => self.channel = Some(Channel::Blue),
+            KeyCode::Char('+') => return Some(EditorCommand::AddStop),
+            KeyCode::Char('-') => return Some(EditorCommand::RemoveStop),
+            KeyCode::Up | KeyCode::BackTab => return Some(EditorCommand::SelectPrevious),
+            KeyCode::Down | KeyCode::Tab => return Some(EditorCommand::SelectNext),
+            KeyCode::Left => return Some(EditorCommand::MoveStop(-POSITION_STEP)),
+            KeyCode::Right => return Some(EditorCommand::MoveStop(POSITION_STEP)),
+            KeyCode::Enter => return Some(EditorCommand::Apply),
+            KeyCode::Char('S') => return Some(EditorCommand::Save),
+            KeyCode::Char('q') | KeyCode::Esc => return Some(EditorCommand::Quit),
+            _ => {}
+        }
+
+        None
+    }
+}
+
+/// The stops being edited. A `gradient` effect only has its two end colors, so their number and
+/// positions are fixed.
+pub struct ColorStopEditor {
+    stops: Vec<ColorStop>,
+    selected: usize,
+    fixed_stops: bool,
+}
+
+impl ColorStopEditor {
+    pub fn new(stops: Vec<ColorStop>, fixed_stops: bool) -> Self {
+        Self {
+            stops,
+            selected: 0,
+            fixed_stops,
+        }
+    }
+
+    pub fn stops(&self) -> &[ColorStop] {
+        &self.stops
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// Applies an editing command, failing with what's wrong with it.
+    pub fn apply(&mut self, command: EditorCommand) -> Result<(), String> {
+        match command {
+            EditorCommand::AddStop => {
+                if self.fixed_stops {
+                    return Err("a gradient effect has exactly two stops".to_string());
+                }
+                let selected = self.stops[self.selected];
+                let next_position = self
+                    .stops
+                    .get(self.selected + 1)
+                    .map_or(1.0, |next| next.position);
+                let position = (selected.position + next_position) / 2.0;
+                let color = lerp_color_stops(&self.stops, position, ColorSpace::Rgb);
+
+                self.selected += 1;
+                self.stops
+                    .insert(self.selected, ColorStop { position, color });
+            }
+            EditorCommand::RemoveStop => {
+                if self.fixed_stops || self.stops.len() <= 2 {
+                    return Err("a gradient needs at least two stops".to_string());
+                }
+                self.stops.remove(self.selected);
+                self.selected = self.selected.min(self.stops.len() - 1);
+            }
+            EditorCommand::SelectPrevious => {
+                self.selected = self.selected.checked_sub(1).unwrap_or(self.stops.len() - 1);
+            }
+            EditorCommand::SelectNext => self.selected = (self.selected + 1) % self.stops.len(),
+            EditorCommand::MoveStop(step) => {
+                if self.fixed_stops {
+                    return Err("the stops of a gradient effect are at its ends".to_string());
+                }
+                // Between the neighbors, so the stops stay sorted.
+                let min = self
+                    .selected
+                    .checked_sub(1)
+                    .map_or(0.0, |previous| self.stops[previous].position);
+                let max = self
+                    .stops
+                    .get(self.selected + 1)
+                    .map_or(1.0, |next| next.position);
+                let stop = &mut self.stops[self.selected];
+                stop.position = (stop.position + step).clamp(min, max);
+            }
+            EditorCommand::AdjustChannel(channel, step) => {
+                let value = self.channel_mut(channel);
+                *value = (*value as i16 + step).clamp(0, 255) as u8;
+            }
+            EditorCommand::SetChannel(channel, digit) => {
+                *self.channel_mut(channel) = (digit.min(9) as u16 * 255 / 9) as u8;
+            }
+            EditorCommand::Apply | EditorCommand::Save | EditorCommand::Quit => {}
+        }
+
+        Ok(())
+    }
+
+    fn channel_mut(&mut self, channel: Channel) -> &mut u8 {
+        let color = &mut self.stops[self.selected].color;
+        match channel {
+            Channel::Red => &mut color.r,
+            Channel::Green => &mut color.g,
+            Channel::Blue => &mut color.b,
+        }
+    }
+}
+
+/// The first zone of `controller_name` with a gradient to edit: a `gradient` effect, or the first
+/// palette of a `palette_cycle`.
+fn find_gradient(
+    config: &Config,
+    controller_name: &str,
+) -> Result<(usize, Effect), Box<dyn Error>> {
+    let mapping = config
+        .controllers
+        .iter()
+        .find(|mapping| mapping.name == controller_name)
+        .ok_or_else(|| format!("no controller {controller_name:?} in the config"))?;
+
+    mapping
+        .zones
+        .iter()
+        .enumerate()
+        .find(|(_, zone)| {
+            matches!(
+                zone.effect,
+                Effect::Gradient { .. } | Effect::PaletteCycle { .. }
+            )
+        })
+        .map(|(zone_index, zone)| (zone_index, zone.effect.clone()))
+        .ok_or_else(|| {
+            format!("{controller_name} has no gradient or palette_cycle zone to edit").into()
+        })
+}
+
+fn stops_of(effect: &Effect) -> Vec<ColorStop> {
+    match effect {
+        Effect::Gradient {
+            start_color,
+            end_color,
+        } => vec![
+            ColorStop {
+                position: 0.0,
+                color: *start_color,
+            },
+            ColorStop {
+                position: 1.0,
+                color: *end_color,
+            },
+        ],
+        Effect::PaletteCycle { palettes, .. } => palettes.first().cloned().unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+fn with_stops(effect: &Effect, stops: &[ColorStop]) -> Effect {
+    let mut effect = effect.clone();
+    match &mut effect {
+        Effect::Gradient {
+            start_color,
+            end_color,
+        } => {
+            *start_color = stops[0].color;
+            *end_color = stops[stops.len() - 1].color;
+        }
+        Effect::PaletteCycle { palettes, .. } => match palettes.first_mut() {
+            Some(palette) => *palette = stops.to_vec(),
+            None => palettes.push(stops.to_vec()),
+        },
+        _ => {}
+    }
+
+    effect
+}
+
+/// Runs the editor on the first gradient of `controller_name` until `q` is pressed.
+pub async fn run(config: &Config, controller_name: &str) -> Result<(), Box<dyn Error>> {
+    let (zone_index, effect) = find_gradient(config, controller_name)?;
+    let mut stops = stops_of(&effect);
+    if stops.len() < 2 {
+        stops = vec![
+            ColorStop {
+                position: 0.0,
+                color: Color::new(0, 0, 0),
+            },
+            ColorStop {
+                position: 1.0,
+                color: Color::new(255, 255, 255),
+            },
+        ];
+    }
+    let mut editor = ColorStopEditor::new(stops, matches!(effect, Effect::Gradient { .. }));
+
+    terminal::enable_raw_mode()?;
+    let result = edit(config, &mut editor, &effect, controller_name, zone_index).await;
+    terminal::disable_raw_mode()?;
+    println!();
+
+    result
+}
+
+async fn edit(
+    config: &Config,
+    editor: &mut ColorStopEditor,
+    effect: &Effect,
+    controller_name: &str,
+    zone_index: usize,
+) -> Result<(), Box<dyn Error>> {
+    let mut parser = CommandParser::default();
+    let mut message = String::from(
+        "+/- add/remove stop, up/down select, left/right move, r/g/b then +/-/0-9 change color, \
+         Enter apply, S save, q quit",
+    );
+
+    loop {
+        draw(editor, &message)?;
+
+        let Event::Key(KeyEvent {
+            code,
+            kind: KeyEventKind::Press,
+            ..
+        }) = tokio::task::spawn_blocking(event::read).await??
+        else {
+            continue;
+        };
+        let Some(command) = parser.feed(code) else {
+            continue;
+        };
+
+        message = match command {
+            EditorCommand::Quit => return Ok(()),
+            EditorCommand::Apply => {
+                match apply_to_hardware(config, controller_name, editor.stops()).await {
+                    Ok(()) => format!("Applied to {controller_name}"),
+                    Err(e) => format!("Failed to apply: {e}"),
+                }
+            }
+            EditorCommand::Save => {
+                match save(
+                    controller_name,
+                    zone_index,
+                    &with_stops(effect, editor.stops()),
+                ) {
+                    Ok(()) => "Saved to the config".to_string(),
+                    Err(e) => format!("Failed to save: {e}"),
+                }
+            }
+            command => match editor.apply(command) {
+                Ok(()) => String::new(),
+                Err(e) => e,
+            },
+        };
+    }
+}
+
+fn draw(editor: &ColorStopEditor, message: &str) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    execute!(stdout, MoveTo(0, 0), Clear(ClearType::All))?;
+
+    write!(
+        stdout,
+        "{}\r\n",
+        gradient_bar(editor.stops(), BAR_WIDTH, ColorSpace::Rgb)
+    )?;
+    for (index, stop) in editor.stops().iter().enumerate() {
+        let marker = if index == editor.selected() { '>' } else { ' ' };
+        write!(
+            stdout,
+            "{marker} {:.2}  #{:02X}{:02X}{:02X}  \x1b[48;2;{};{};{}m    \x1b[0m\r\n",
+            stop.position,
+            stop.color.r,
+            stop.color.g,
+            stop.color.b,
+            stop.color.r,
+            stop.color.g,
+            stop.color.b
+        )?;
+    }
+    write!(stdout, "\r\n{message}\r\n")?;
+
+    stdout.flush()
+}
+
+/// Spreads the stops over every LED of the controller, connecting once rather than retrying so
+/// the editor doesn't hang on a server that's down.
+async fn apply_to_hardware(
+    config: &Config,
+    controller_name: &str,
+    stops: &[ColorStop],
+) -> Result<(), Box<dyn Error>> {
+    let pool = ConnectionPool::new([config.server_address.clone()], &config.client_name);
+    let client = pool.open(0).await?;
+
+    for controller_id in 0..client.get_controller_count().await? {
+        let controller = client.get_controller(controller_id).await?;
+        if controller.name != controller_name {
+            continue;
+        }
+
+        let last = (controller.leds.len().saturating_sub(1)).max(1) as f32;
+        let colors = (0..controller.leds.len())
+            .map(|led| lerp_color_stops(stops, led as f32 / last, ColorSpace::Rgb))
+            .collect();
+
+        return Ok(client.update_leds(controller_id, colors).await?);
+    }
+
+    Err(format!("the server has no controller {controller_name:?}").into())
+}
+
+/// Writes `effect` over the zone's effect in the config file. The file is rewritten, so its
+/// comments are lost.
+fn save(controller_name: &str, zone_index: usize, effect: &Effect) -> Result<(), Box<dyn Error>> {
+    let path = Config::path().ok_or("no config file to save to")?;
+    let mut table: toml::Table = toml::from_str(&fs::read_to_string(&path)?)?;
+
+    let zone = table
+        .get_mut("controllers")
+        .and_then(toml::Value::as_array_mut)
+        .and_then(|controllers| {
+            controllers.iter_mut().find(|controller| {
+                controller.get("name").and_then(toml::Value::as_str) == Some(controller_name)
+            })
+        })
+        .and_then(|controller| controller.get_mut("zones"))
+        .and_then(toml::Value::as_array_mut)
+        .and_then(|zones| zones.get_mut(zone_index))
+        .and_then(toml::Value::as_table_mut)
+        .ok_or("only controllers in the top-level controllers list can be saved")?;
+    zone.insert("effect".to_string(), toml::Value::try_from(effect)?);
+
+    fs::write(&path, toml::to_string(&table)?)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editor() -> ColorStopEditor {
+        ColorStopEditor::new(
+            vec![
+                ColorStop {
+                    position: 0.0,
+                    color: Color::new(10, 20, 30),
+                },
+                ColorStop {
+                    position: 1.0,
+                    color: Color::new(255, 255, 255),
+                },
+            ],
+            false,
+        )
+    }
+
+    fn feed(parser: &mut CommandParser, keys: &str) -> Vec<EditorCommand> {
+        keys.chars()
+            .filter_map(|key| parser.feed(KeyCode::Char(key)))
+            .collect()
+    }
+
+    #[test]
+    fn parses_channel_commands() {
+        let mut parser = CommandParser::default();
+
+        assert_eq!(
+            feed(&mut parser, "b+"),
+            [EditorCommand::AdjustChannel(Channel::Blue, 1)]
+        );
+        assert_eq!(
+            feed(&mut parser, "r-g9"),
+            [
+                EditorCommand::AdjustChannel(Channel::Red, -1),
+                EditorCommand::SetChannel(Channel::Green, 9)
+            ]
+        );
+        // Without a channel, + and - add and remove stops.
+        assert_eq!(
+            feed(&mut parser, "+-"),
+            [EditorCommand::AddStop, EditorCommand::RemoveStop]
+        );
+        // A key that isn't for the channel starts over.
+        assert_eq!(feed(&mut parser, "rq"), [EditorCommand::Quit]);
+        assert_eq!(parser.feed(KeyCode::Enter), Some(EditorCommand::Apply));
+    }
+
+    #[test]
+    fn b_plus_increments_blue() {
+        let mut parser = CommandParser::default();
+        let mut editor = editor();
+
+        for command in feed(&mut parser, "b+b+") {
+            editor.apply(command).unwrap();
+        }
+
+        assert_eq!(editor.stops()[0].color, Color::new(10, 20, 32));
+        assert_eq!(editor.stops()[1].color, Color::new(255, 255, 255));
+    }
+
+    #[test]
+    fn adjusting_a_channel_saturates() {
+        let mut editor = editor();
+        editor.apply(EditorCommand::SelectNext).unwrap();
+
+        editor
+            .apply(EditorCommand::AdjustChannel(Channel::Red, 1))
+            .unwrap();
+        editor
+            .apply(EditorCommand::SetChannel(Channel::Green, 0))
+            .unwrap();
+
+        assert_eq!(editor.stops()[1].color, Color::new(255, 0, 255));
+    }
+}
@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HealthMonitorConfig {
+    /// How long the render loop may go without a heartbeat before it is restarted.
+    pub timeout_secs: u64,
+    /// Consecutive restarts without a heartbeat in between before giving up.
+    pub max_restarts: u32,
+    /// How long the render loop may go without progress before the process exits, for hangs
+    /// restarting the loop can't recover from. 0 disables it.
+    pub watchdog_timeout_secs: u64,
+}
+
+impl Default for HealthMonitorConfig {
+    fn default() -> Self {
+        Self {
+            timeout_secs: 30,
+            max_restarts: 3,
+            watchdog_timeout_secs: 300,
+        }
+    }
+}
+
+/// Watches the heartbeats of the render loop and cancels it when they stop.
+pub struct HealthMonitor {
+    timeout: Duration,
+}
+
+impl HealthMonitor {
+    pub fn new(config: &HealthMonitorConfig) -> Self {
+        Self {
+            timeout: Duration::from_secs(config.timeout_secs),
+        }
+    }
+
+    /// Cancels `stall` if a heartbeat, the first one included, takes longer than the timeout.
+    /// Returns whether any heartbeat arrived.
+    pub async fn watch(self, mut heartbeats: mpsc::Receiver<()>, stall: CancellationToken) -> bool {
+        let mut any_heartbeat = false;
+        loop {
+            match tokio::time::timeout(self.timeout, heartbeats.recv()).await {
+                Ok(Some(())) => any_heartbeat = true,
+                Ok(None) => return any_heartbeat,
+                Err(_) => {
+                    stall.cancel();
+                    return any_heartbeat;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_millis(100);
+
+    #[tokio::test]
+    async fn cancels_when_the_heartbeats_stop() {
+        let (sender, receiver) = mpsc::channel(1);
+        let stall = CancellationToken::new();
+        let monitor = HealthMonitor { timeout: TIMEOUT };
+        let watch = tokio::spawn(monitor.watch(receiver, stall.clone()));
+
+        // Ticks 5 times, then hangs with the sender still alive.
+        for _ in 0..5 {
+            sender.send(()).await.unwrap();
+            tokio::time::sleep(TIMEOUT / 4).await;
+            assert!(!stall.is_cancelled());
+        }
+
+        tokio::time::timeout(TIMEOUT * 5, stall.cancelled())
+            .await
+            .unwrap();
+        assert!(watch.await.unwrap());
+        drop(sender);
+    }
+
+    #[tokio::test]
+    async fn cancels_without_a_first_heartbeat() {
+        let (sender, receiver) = mpsc::channel::<()>(1);
+        let stall = CancellationToken::new();
+        let monitor = HealthMonitor { timeout: TIMEOUT };
+
+        assert!(!monitor.watch(receiver, stall.clone()).await);
+        assert!(stall.is_cancelled());
+        drop(sender);
+    }
+}
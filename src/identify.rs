@@ -0,0 +1,35 @@
+use crate::pool::Stream;
+use openrgb::{data::Color, OpenRGB};
+use std::{error::Error, time::Duration};
+use tracing::info;
+
+const IDENTIFY_COLOR: Color = Color::new(255, 255, 255);
+const BLINKS: usize = 5;
+const BLINK_DURATION: Duration = Duration::from_millis(300);
+
+/// Flashes a controller a few times so it can be told apart from similar devices, then puts its
+/// colors back.
+pub async fn identify(client: &OpenRGB<Stream>, controller_id: u32) -> Result<(), Box<dyn Error>> {
+    let controller = client.get_controller(controller_id).await?;
+    info!(
+        "Identifying controller {controller_id}: {}",
+        controller.name
+    );
+
+    let led_count = controller.leds.len();
+    for _ in 0..BLINKS {
+        client
+            .update_leds(controller_id, vec![IDENTIFY_COLOR; led_count])
+            .await?;
+        tokio::time::sleep(BLINK_DURATION).await;
+
+        client
+            .update_leds(controller_id, vec![Color::new(0, 0, 0); led_count])
+            .await?;
+        tokio::time::sleep(BLINK_DURATION).await;
+    }
+
+    client.update_leds(controller_id, controller.colors).await?;
+
+    Ok(())
+}
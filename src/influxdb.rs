@@ -0,0 +1,224 @@
+//! Sends the metric values to InfluxDB as line protocol over UDP, for graphing them next to the
+//! lighting.
+
+use crate::metrics::{Metric, TemperatureUnit};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    time::{Duration, SystemTime},
+};
+use tokio::{net::UdpSocket, sync::watch};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+const MEASUREMENT: &str = "openrgb_metrics";
+/// The sensor readings of temperature metrics, tagged with their unit.
+const TEMPERATURE_MEASUREMENT: &str = "openrgb_temperatures";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InfluxDbConfig {
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    /// The database of the server's UDP listener. UDP packets can't name one, the server writes
+    /// them to the database configured for the port.
+    pub database: String,
+    /// Added to every point, e.g. `host = "mypc"`.
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+    #[serde(default = "default_flush_interval_secs")]
+    pub flush_interval_secs: u64,
+}
+
+fn default_port() -> u16 {
+    8089
+}
+
+fn default_flush_interval_secs() -> u64 {
+    10
+}
+
+/// Sends the latest values of `metrics`, e.g. from `MetricBus::subscribe_all`, and the readings of
+/// `temperatures` in `unit`, from `MetricBus::subscribe_temperatures`, every `flush_interval_secs`
+/// until `shutdown` is cancelled. Only the metrics sampled since the last flush are sent. Failed
+/// sends are logged and retried on the next flush.
+pub async fn export(
+    config: InfluxDbConfig,
+    mut metrics: Vec<(Metric, watch::Receiver<f32>)>,
+    mut temperatures: Vec<(Metric, watch::Receiver<f32>)>,
+    unit: TemperatureUnit,
+    shutdown: CancellationToken,
+) -> Result<(), Box<dyn Error>> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    let address = format!("{}:{}", config.host, config.port);
+    info!(
+        "Exporting metrics to InfluxDB at {address}, database {}",
+        config.database
+    );
+
+    let mut temperature_tags = config.tags.clone();
+    temperature_tags.insert("unit".to_string(), unit.name().to_string());
+
+    let mut interval =
+        tokio::time::interval(Duration::from_secs(config.flush_interval_secs.max(1)));
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => return Ok(()),
+            _ = interval.tick() => {}
+        }
+
+        let time = SystemTime::now();
+        let values = changed(&mut metrics);
+        let readings: Vec<(Metric, f32)> = changed(&mut temperatures)
+            .into_iter()
+            .map(|(metric, celsius)| (metric, unit.convert(celsius)))
+            .collect();
+        let lines = [
+            line_protocol(MEASUREMENT, &config.tags, &values, time),
+            line_protocol(TEMPERATURE_MEASUREMENT, &temperature_tags, &readings, time),
+        ];
+        let line: String = lines.into_iter().flatten().collect();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Err(e) = socket.send_to(line.as_bytes(), &address).await {
+            warn!("Failed to send metrics to InfluxDB at {address}: {e}");
+        }
+    }
+}
+
+/// The values received since the last call.
+fn changed(metrics: &mut [(Metric, watch::Receiver<f32>)]) -> Vec<(Metric, f32)> {
+    metrics
+        .iter_mut()
+        .filter(|(_, receiver)| receiver.has_changed().unwrap_or(false))
+        .map(|(metric, receiver)| (metric.clone(), *receiver.borrow_and_update()))
+        .collect()
+}
+
+/// One point of `measurement` with a field per metric, `None` without any values.
+pub fn line_protocol(
+    measurement: &str,
+    tags: &BTreeMap<String, String>,
+    values: &[(Metric, f32)],
+    time: SystemTime,
+) -> Option<String> {
+    if values.is_empty() {
+        return None;
+    }
+
+    let mut line = measurement.to_string();
+    for (key, value) in tags {
+        line.push_str(&format!(",{}={}", escape(key), escape(value)));
+    }
+
+    let fields: Vec<String> = values
+        .iter()
+        .map(|(metric, value)| format!("{}={value}", escape(&field_key(metric))))
+        .collect();
+    line.push(' ');
+    line.push_str(&fields.join(","));
+
+    let timestamp = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    line.push_str(&format!(" {timestamp}\n"));
+
+    Some(line)
+}
+
+/// `cpu_usage` for the metrics without parameters, their JSON otherwise.
+fn field_key(metric: &Metric) -> String {
+    match serde_json::to_value(metric) {
+        Ok(serde_json::Value::String(name)) => name,
+        Ok(value) => value.to_string(),
+        Err(_) => String::new(),
+    }
+}
+
+/// Tag keys, tag values and field keys can't have unescaped commas, equal signs or spaces.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, ',' | '=' | ' ' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::{MetricBus, MetricValues};
+
+    #[tokio::test]
+    async fn exports_the_metrics_over_udp() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let config = InfluxDbConfig {
+            host: "127.0.0.1".to_string(),
+            port: receiver.local_addr().unwrap().port(),
+            database: "metrics".to_string(),
+            tags: BTreeMap::from([("host".to_string(), "mypc".to_string())]),
+            flush_interval_secs: 1,
+        };
+        let temperature = Metric::Temperature {
+            sensor: "Tctl".to_string(),
+            min_celsius: 40.0,
+            max_celsius: 90.0,
+        };
+        let bus = MetricBus::default();
+        let metrics = bus.subscribe_all([&Metric::CpuUsage, &Metric::MemoryUsage]);
+        let temperatures = bus.subscribe_temperatures([&Metric::CpuUsage, &temperature]);
+        assert_eq!(temperatures.len(), 1);
+        bus.publish(&MetricValues {
+            cpu_usage: 0.43,
+            memory_usage: 0.5,
+            temperatures: vec![(temperature, 100.0)],
+            ..Default::default()
+        });
+        let shutdown = CancellationToken::new();
+        let exporter = tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move {
+                export(
+                    config,
+                    metrics,
+                    temperatures,
+                    TemperatureUnit::Fahrenheit,
+                    shutdown,
+                )
+                .await
+                .map_err(|e| e.to_string())
+            }
+        });
+
+        let mut buffer = [0; 1024];
+        let length = tokio::time::timeout(Duration::from_secs(5), receiver.recv(&mut buffer))
+            .await
+            .expect("nothing exported")
+            .unwrap();
+        let text = std::str::from_utf8(&buffer[..length]).unwrap();
+        assert!(text.ends_with('\n'));
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 2, "{text}");
+        assert!(
+            lines[0].starts_with("openrgb_metrics,host=mypc cpu_usage=0.43,memory_usage=0.5 "),
+            "{text}"
+        );
+        // The reading, in the unit, rather than the value mapped to 0.0..1.0.
+        assert!(
+            lines[1].starts_with("openrgb_temperatures,host=mypc,unit=fahrenheit "),
+            "{text}"
+        );
+        assert!(lines[1].contains("=212 "), "{text}");
+
+        shutdown.cancel();
+        exporter.await.unwrap().unwrap();
+    }
+}
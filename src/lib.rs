@@ -0,0 +1,47 @@
+#[cfg(unix)]
+pub mod background;
+pub mod batch;
+pub mod benchmark;
+pub mod cli;
+pub mod color;
+pub mod color_history;
+pub mod colormap;
+pub mod completions;
+pub mod config;
+#[cfg(unix)]
+pub mod control;
+pub mod daemon;
+pub mod debounce;
+pub mod effect;
+pub mod frame_rate;
+pub mod gamma;
+pub mod gradient_editor;
+pub mod health;
+pub mod identify;
+pub mod influxdb;
+pub mod logging;
+pub mod metrics;
+pub mod mirror;
+#[cfg(test)]
+mod mock_server;
+pub mod modes;
+pub mod palette;
+pub mod pool;
+pub mod preview;
+pub mod priority;
+pub mod reconnect;
+pub mod render;
+pub mod schedule;
+pub mod sensors;
+pub mod simulator;
+pub mod sink;
+pub mod status;
+pub mod stress;
+pub mod sync;
+pub mod systemd;
+pub mod test_pattern;
+pub mod transition;
+pub mod watchdog;
+#[cfg(windows)]
+pub mod windows_service;
+pub mod zones;
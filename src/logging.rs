@@ -0,0 +1,101 @@
+use clap::ValueEnum;
+use std::{
+    error::Error,
+    io::{self, IsTerminal},
+};
+use tracing::Subscriber;
+use tracing_subscriber::{
+    filter::LevelFilter,
+    fmt::{self, time::ChronoLocal},
+    layer::SubscriberExt,
+    registry::LookupSpan,
+    util::SubscriberInitExt,
+    EnvFilter, Layer,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// `<timestamp> <LEVEL> <spans> <target>: <message>`, colored on a terminal.
+    #[default]
+    Text,
+    /// One JSON object per line, for log collectors.
+    Json,
+}
+
+pub struct LoggerConfig {
+    /// Overrides `RUST_LOG`. Everything is logged when neither is set.
+    pub level: Option<LevelFilter>,
+    pub format: LogFormat,
+    pub timestamps: bool,
+    /// The module the message comes from.
+    pub target: bool,
+    /// OTLP collector the spans are exported to, e.g. `http://localhost:4317`.
+    #[cfg(feature = "otel")]
+    pub otlp_endpoint: Option<String>,
+}
+
+/// Logs to stderr, which ends up in the journal under systemd and in the log file with `--daemon`.
+/// `RUST_LOG` takes directives like `info,main_pc_2_openrgb_client::render=debug`.
+pub fn init(config: LoggerConfig) -> Result<(), Box<dyn Error>> {
+    let filter = match config.level {
+        Some(level) => EnvFilter::default().add_directive(level.into()),
+        None => EnvFilter::try_from_default_env()
+            .unwrap_or_else(|_| EnvFilter::default().add_directive(LevelFilter::TRACE.into())),
+    };
+
+    let subscriber = tracing_subscriber::registry().with(fmt_layer(&config));
+    #[cfg(feature = "otel")]
+    let subscriber = subscriber.with(config.otlp_endpoint.map(otel_layer).transpose()?);
+    subscriber.with(filter).try_init()?;
+
+    Ok(())
+}
+
+fn fmt_layer<S>(config: &LoggerConfig) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let layer = fmt::layer()
+        .with_writer(io::stderr)
+        .with_target(config.target)
+        .with_ansi(config.format == LogFormat::Text && io::stderr().is_terminal());
+
+    match (config.format, config.timestamps) {
+        (LogFormat::Text, true) => layer.with_timer(ChronoLocal::rfc_3339()).boxed(),
+        (LogFormat::Text, false) => layer.without_time().boxed(),
+        (LogFormat::Json, true) => layer.json().with_timer(ChronoLocal::rfc_3339()).boxed(),
+        (LogFormat::Json, false) => layer.json().without_time().boxed(),
+    }
+}
+
+#[cfg(feature = "otel")]
+fn otel_layer<S>(
+    endpoint: String,
+) -> Result<
+    tracing_opentelemetry::OpenTelemetryLayer<S, opentelemetry_sdk::trace::Tracer>,
+    Box<dyn Error>,
+>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_otlp::WithExportConfig;
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(endpoint)
+        .build()?;
+    let provider = opentelemetry_sdk::trace::TracerProvider::builder()
+        .with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio)
+        .build();
+    let tracer = provider.tracer(env!("CARGO_PKG_NAME"));
+    opentelemetry::global::set_tracer_provider(provider);
+
+    Ok(tracing_opentelemetry::layer().with_tracer(tracer))
+}
+
+/// Exports the spans still waiting in the batch, before the process exits.
+pub fn shutdown() {
+    #[cfg(feature = "otel")]
+    opentelemetry::global::shutdown_tracer_provider();
+}
@@ -1,161 +1,198 @@
-use cpu_monitor::CpuInstant;
-use log::{info, warn};
-use openrgb::{data::Color, OpenRGB};
-use ringbuffer::{AllocRingBuffer, RingBuffer};
-use simple_logger::SimpleLogger;
-use std::{error::Error, time::Duration};
-use sysinfo::{MemoryRefreshKind, RefreshKind};
-use tokio::net::TcpStream;
-use tokio_retry::Retry;
-
-const SAMPLE_TIME: f32 = 5.0; // seconds.
-const SAMPLE_RATE: u64 = 500;
-const SAMPLE_BUFFER_SIZE: usize = (SAMPLE_TIME * (1.0 + 1.0 / SAMPLE_RATE as f32)) as usize;
-
-const WHITE_COLOR: Color = Color::new(127, 127, 127);
-const RED_COLOR: Color = Color::new(127, 0, 0);
+use clap::Parser;
+#[cfg(unix)]
+use main_pc_2_openrgb_client::background;
+use main_pc_2_openrgb_client::{
+    benchmark,
+    cli::{Cli, Command},
+    colormap::Colormap,
+    completions,
+    config::Config,
+    daemon, gradient_editor, identify, logging, palette, preview,
+    sensors::SensorRegistry,
+    stress, systemd, test_pattern,
+};
+#[cfg(windows)]
+use main_pc_2_openrgb_client::{cli::ServiceAction, windows_service};
+use std::{error::Error, io, path::PathBuf, time::Duration};
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+
+    #[cfg(windows)]
+    if let Some(Command::Service { action }) = &cli.command {
+        return match action {
+            ServiceAction::Install => windows_service::install(),
+            ServiceAction::Uninstall => windows_service::uninstall(),
+            ServiceAction::Run => windows_service::run(),
+        };
+    }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
-    SimpleLogger::new().env().init().unwrap();
-
-    let client = connect_to_open_rgb_server().await?;
-    info!(
-        "Connected to OpenRGB server! Protocol version: {}",
-        client.get_protocol_version()
-    );
-
-    let mut cpu_samples = AllocRingBuffer::new(SAMPLE_BUFFER_SIZE);
-
-    let mut sys = sysinfo::System::new_with_specifics(
-        RefreshKind::nothing().with_memory(MemoryRefreshKind::nothing().with_ram()),
-    );
-
-    loop {
-        // CPU utilization.
-        let start = CpuInstant::now()?;
-        tokio::time::sleep(Duration::from_millis(SAMPLE_RATE)).await;
-        let end = CpuInstant::now()?;
-        let duration = end - start;
-        let cpu_usage = duration.non_idle() as f32;
-        cpu_samples.push(cpu_usage);
-
-        let cpu_usage = cpu_samples
-            .iter()
-            .copied()
-            .reduce(|accum, sample| accum + sample)
-            .unwrap_or_default();
-        let cpu_usage = cpu_usage / cpu_samples.len() as f32;
-
-        // Memory utilization.
-        sys.refresh_memory();
-        let memory_usage = sys.used_memory() as f32 / sys.total_memory() as f32;
-
-        // Set the color.
-        let controller_count = client.get_controller_count().await?;
-        for controller_id in 0..controller_count {
-            let controller = client.get_controller(controller_id).await?;
-            let led_count = controller.leds.len();
-            if led_count == 0 {
-                warn!("Controller {} has no LEDs", controller.name);
-                continue;
-            }
-
-            let colors: Vec<Color> = match controller.name.as_str() {
-                "Corsair Dominator Platinum" => {
-                    generate_gradient_led_colors(memory_usage, &WHITE_COLOR, &RED_COLOR, led_count)
-                }
-                "Corsair Commander Core" => {
-                    let mut colors = Vec::with_capacity(led_count);
-
-                    // Ring colors.
-                    colors.extend(generate_gradient_led_colors(
-                        cpu_usage,
-                        &WHITE_COLOR,
-                        &RED_COLOR,
-                        24,
-                    ));
-
-                    // Ports (fans) colors.
-                    for _ in 0..6 {
-                        colors.extend(generate_block_led_colors(
-                            cpu_usage,
-                            &WHITE_COLOR,
-                            &RED_COLOR,
-                            5,
-                        ));
-                    }
-
-                    colors
-                }
-                "G502 HERO Gaming Mouse" => {
-                    generate_block_led_colors(cpu_usage, &WHITE_COLOR, &RED_COLOR, led_count)
-                }
-                "MSI X670E GAMING PLUS WIFI (MS-7E16)" => vec![], // Do nothing.
-
-                _ => {
-                    warn!("Unknown controller: {}", controller.name);
-                    vec![]
-                }
-            };
-
-            client.update_leds(controller_id, colors).await?;
+    #[cfg(unix)]
+    {
+        let pid_file = cli
+            .pid_file
+            .clone()
+            .unwrap_or_else(background::default_pid_file);
+
+        if cli.stop {
+            return background::stop(&pid_file);
         }
 
-        tokio::task::yield_now().await;
-    }
-}
+        if cli.daemon {
+            let log_file = cli
+                .log_file
+                .clone()
+                .unwrap_or_else(background::default_log_file);
 
-async fn connect_to_open_rgb_server() -> Result<OpenRGB<TcpStream>, Box<dyn Error>> {
-    let retry_strategy = tokio_retry::strategy::FixedInterval::from_millis(5000);
+            // Must fork before the runtime spawns its threads.
+            background::detach(&pid_file, &log_file)?;
+            let result = run(cli);
+            background::remove_pid_file(&pid_file);
 
-    Retry::spawn(retry_strategy, || async {
-        info!("Connecting to OpenRGB server...");
+            return result;
+        }
+    }
 
-        OpenRGB::connect()
-            .await
-            .map_err(|e| Box::new(e) as Box<dyn Error>)
-    })
-    .await
+    run(cli)
 }
 
-fn lerp(value: f32, start: f32, end: f32) -> f32 {
-    let value = value.clamp(0.0, 1.0);
-    start + value * (end - start)
-}
+#[tokio::main]
+async fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    logging::init(cli.logger_config())?;
+    let result = run_command(cli).await;
+    tokio::task::block_in_place(logging::shutdown);
 
-fn lerp_color(value: f32, start_color: &Color, end_color: &Color) -> Color {
-    Color::new(
-        lerp(value, start_color.r as f32, end_color.r as f32).round() as u8,
-        lerp(value, start_color.g as f32, end_color.g as f32).round() as u8,
-        lerp(value, start_color.b as f32, end_color.b as f32).round() as u8,
-    )
+    result
 }
 
-fn generate_gradient_led_colors(
-    value: f32,
-    start_color: &Color,
-    end_color: &Color,
-    size: usize,
-) -> Vec<Color> {
-    let scaled_value = value * size as f32;
-
-    (0..size)
-        .map(|index| {
-            lerp_color(
-                (scaled_value - index as f32).clamp(0.0, 1.0),
-                start_color,
-                end_color,
-            )
+async fn run_command(cli: Cli) -> Result<(), Box<dyn Error>> {
+    if let Some(shell) = cli.generate_completions {
+        let controller_names = completions::controller_names().await;
+        completions::generate(shell, &controller_names, &mut io::stdout());
+
+        return Ok(());
+    }
+
+    if let Some(Command::PreviewGradient {
+        stops,
+        colormap,
+        width,
+        color_space,
+    }) = &cli.command
+    {
+        let stops = match (stops, colormap) {
+            (Some(stops), _) => preview::parse_stops(stops)?,
+            (None, Some(colormap)) => Colormap::from_matplotlib_name(colormap)
+                .ok_or_else(|| format!("unknown colormap {colormap:?}"))?,
+            (None, None) => unreachable!("clap requires one of them"),
+        };
+        println!("{}", preview::gradient_bar(&stops, *width, *color_space));
+
+        return Ok(());
+    }
+
+    if cli.list_sensors {
+        SensorRegistry::discover().print();
+        return Ok(());
+    }
+
+    let mut config = Config::load()?;
+    if let Some(temp_unit) = cli.temp_unit {
+        config.temperature_unit = temp_unit;
+    }
+    if cli.strict {
+        config.strict_metrics = true;
+    }
+    if let Some(timeout_secs) = cli.require_devices {
+        config.require_devices_timeout_secs = Some(timeout_secs);
+    }
+
+    if let Some(Command::Identify { index }) = cli.command {
+        let client = daemon::connect(&config).await?;
+        return identify::identify(&client, index).await;
+    }
+
+    if let Some(Command::Test) = cli.command {
+        let client = daemon::connect(&config).await?;
+        return test_pattern::run(&client).await;
+    }
+
+    if let Some(controller_name) = &cli.benchmark_leds {
+        let client = daemon::connect(&config).await?;
+        let result = benchmark::run(
+            &client,
+            controller_name,
+            Duration::from_secs(cli.duration_secs),
+        )
+        .await?;
+        print!("{}", result.table());
+
+        return Ok(());
+    }
+
+    if cli.stress_test {
+        let client = daemon::connect(&config).await?;
+        stress::run(&client, Duration::from_secs(cli.duration_secs)).await?;
+
+        return Ok(());
+    }
+
+    if let Some(controller_name) = &cli.edit_gradient {
+        return gradient_editor::run(&config, controller_name).await;
+    }
+
+    if let Some(args) = cli.extract_palette {
+        let n_colors: usize = args[0]
+            .parse()
+            .map_err(|_| format!("invalid number of colors {:?}", args[0]))?;
+        let output = PathBuf::from(&args[1]);
+        let history = config
+            .color_history_path
+            .clone()
+            .ok_or("--extract-palette needs color_history_path to be set in the config")?;
+
+        tokio::task::spawn_blocking(move || {
+            palette::extract_to_file(&history, n_colors, &output).map_err(|e| e.to_string())
         })
-        .collect()
+        .await??;
+        info!("Palette written to {}", args[1]);
+
+        return Ok(());
+    }
+
+    if cli.once {
+        return daemon::render_once(config, cli.restore).await;
+    }
+
+    let shutdown = CancellationToken::new();
+    tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            shutdown_signal().await;
+            info!("Shutting down...");
+            systemd::notify_stopping();
+            shutdown.cancel();
+        }
+    });
+
+    daemon::run(config, shutdown).await
 }
 
-fn generate_block_led_colors(
-    value: f32,
-    start_color: &Color,
-    end_color: &Color,
-    size: usize,
-) -> Vec<Color> {
-    vec![lerp_color(value, start_color, end_color); size]
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate => {}
+    }
 }
@@ -0,0 +1,941 @@
+mod adaptive;
+#[cfg(feature = "audio")]
+mod audio;
+mod bus;
+mod calibrate;
+mod gpu;
+mod pressure;
+#[cfg(target_os = "linux")]
+mod proc_stat;
+pub mod smoothing;
+mod temperature;
+mod transform;
+mod window;
+
+use crate::config::Config;
+use adaptive::AdaptiveTimer;
+use calibrate::{CalibrationState, MetricNormalizer};
+use chrono::{Local, Timelike};
+use cpu_monitor::CpuInstant;
+use gpu::{GpuUsage, Gpus};
+use pressure::MemoryPressure;
+use serde::{Deserialize, Serialize};
+use smoothing::Smoother;
+use std::{error::Error, time::Duration};
+use sysinfo::{
+    Components, MemoryRefreshKind, Networks, RefreshKind, System, MINIMUM_CPU_UPDATE_INTERVAL,
+};
+use tokio::time::Instant;
+use tracing::{debug, info, warn};
+use transform::Transform;
+use window::{SampleWindow, WindowValues};
+
+pub use adaptive::AdaptiveSampleRateConfig;
+pub use bus::MetricBus;
+pub use calibrate::{AutoCalibrate, MetricMax};
+pub use smoothing::{Smoothing, SmoothingProfile};
+pub use temperature::{celsius_to_fahrenheit, TemperatureUnit};
+pub use window::WindowStatistic;
+
+const SAMPLE_TIME: f32 = 5.0; // seconds.
+const SAMPLE_RATE: u64 = 500;
+const SAMPLE_BUFFER_SIZE: usize = (SAMPLE_TIME * (1.0 + 1.0 / SAMPLE_RATE as f32)) as usize;
+
+/// How often an out of range metric is reported at most.
+const RANGE_WARNING_INTERVAL: Duration = Duration::from_secs(60);
+/// How early a sample still counts as due, the frame timer wakes up a little late or early.
+const SAMPLE_INTERVAL_SLACK: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    #[default]
+    CpuUsage,
+
+    /// A statistic of the raw CPU usage over the window `cpu_usage` is smoothed over, e.g. `max`
+    /// for a peak marker over a meter of the smoothed usage.
+    CpuUsageWindow {
+        #[serde(default)]
+        statistic: WindowStatistic,
+    },
+
+    /// Utilization of the logical CPU at `index`, in the order the OS lists them.
+    CpuCore {
+        index: usize,
+    },
+
+    MemoryUsage,
+
+    /// The weighted mean of the RAM and swap usage and, on Linux with pressure stall information,
+    /// the share of the time tasks stalled on memory. Parts that aren't available are left out.
+    MemoryPressure {
+        #[serde(default = "default_ram_weight")]
+        ram_weight: f32,
+        #[serde(default = "default_swap_weight")]
+        swap_weight: f32,
+        #[serde(default = "default_psi_weight")]
+        psi_weight: f32,
+    },
+
+    /// Always this value, e.g. for a controller that should hold one color.
+    Constant(f32),
+
+    /// A slow daily cycle, `baseline + amplitude * cos(2π * (hour - peak_hour) / 24)` clamped to
+    /// 0.0..1.0, where `hour` is the local time in fractional hours, e.g. 13.5 at 13:30.
+    TimeOfDay {
+        peak_hour: f32,
+        amplitude: f32,
+        baseline: f32,
+    },
+
+    /// The maximum can be calibrated from the traffic seen, see `AutoCalibrate`.
+    NetworkUpload {
+        max_bytes_per_second: MetricMax,
+    },
+    NetworkDownload {
+        max_bytes_per_second: MetricMax,
+    },
+
+    /// Core utilization of the GPU at `index`.
+    GpuUtil {
+        #[serde(default)]
+        index: usize,
+    },
+
+    /// VRAM utilization of the GPU at `index`.
+    GpuMem {
+        #[serde(default)]
+        index: usize,
+    },
+
+    /// The first temperature sensor whose label contains `sensor`, e.g. "Tctl" or "edge", mapped
+    /// from `min_celsius`..`max_celsius` to 0.0..1.0.
+    Temperature {
+        sensor: String,
+        min_celsius: f32,
+        max_celsius: f32,
+    },
+
+    /// Peak playback level, 0.0 to 1.0.
+    #[cfg(feature = "audio")]
+    AudioOutputLevel {
+        /// Defaults to the default output device.
+        device_name: Option<String>,
+        /// How fast the level falls on silence, in full scales per second.
+        #[serde(default = "default_audio_decay_rate")]
+        decay_rate: f32,
+    },
+
+    /// The percentile of `metric` within its last `window` samples.
+    Percentile {
+        metric: Box<Metric>,
+        window: usize,
+    },
+
+    /// 1.0 once `metric` rises above `high`, 0.0 again once it falls below `low`.
+    Hysteresis {
+        metric: Box<Metric>,
+        high: f32,
+        low: f32,
+    },
+
+    /// How much `metric` changed since the last sample, divided by `normalize_by` and clamped to
+    /// -1.0..1.0: positive while it rises, negative while it falls.
+    Delta {
+        metric: Box<Metric>,
+        normalize_by: f32,
+    },
+
+    /// The magnitude of `Delta`, 0.0 to 1.0.
+    AbsDelta {
+        metric: Box<Metric>,
+        normalize_by: f32,
+    },
+
+    /// `metric` passed through `smoothing`.
+    Smoothed {
+        metric: Box<Metric>,
+        smoothing: Smoothing,
+    },
+}
+
+impl Metric {
+    /// Pushes the metrics this one is derived from, then itself.
+    pub fn flatten_into<'a>(&'a self, metrics: &mut Vec<&'a Metric>) {
+        if let Some(inner) = self.inner() {
+            inner.flatten_into(metrics);
+        }
+
+        metrics.push(self);
+    }
+
+    fn inner(&self) -> Option<&Metric> {
+        match self {
+            Metric::Percentile { metric, .. }
+            | Metric::Hysteresis { metric, .. }
+            | Metric::Smoothed { metric, .. }
+            | Metric::Delta { metric, .. }
+            | Metric::AbsDelta { metric, .. } => Some(metric.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+fn default_ram_weight() -> f32 {
+    0.5
+}
+
+fn default_swap_weight() -> f32 {
+    0.25
+}
+
+fn default_psi_weight() -> f32 {
+    0.25
+}
+
+#[cfg(feature = "audio")]
+fn default_audio_decay_rate() -> f32 {
+    1.0
+}
+
+/// The values of every metric for one sample tick.
+#[derive(Debug, Default, Clone)]
+pub struct MetricValues {
+    /// When the tick was sampled, in seconds since the sampler started. Time-based effects are
+    /// animated with it.
+    pub time_secs: f64,
+    /// Counts the samples, for effects that advance once per sample whatever `time_secs` says,
+    /// e.g. after a controller's `phase_offset_secs`.
+    pub tick: u64,
+    /// The local time of day in fractional hours, for `time_of_day` metrics.
+    pub local_hour: f32,
+    pub cpu_usage: f32,
+    /// The raw CPU usage over the smoothing window.
+    pub cpu_usage_window: WindowValues,
+    /// Per logical CPU, only sampled when a `cpu_core` metric or a `cpu_cores` effect is used.
+    pub cpu_cores: Vec<f32>,
+    pub memory_usage: f32,
+    /// Only has swap and PSI when a `memory_pressure` metric is used.
+    pub memory_pressure: MemoryPressure,
+    pub network_upload: f32,   // bytes per second.
+    pub network_download: f32, // bytes per second.
+    pub gpus: Vec<GpuUsage>,
+    /// In Celsius, per `Temperature` metric whose sensor was found.
+    pub temperatures: Vec<(Metric, f32)>,
+    #[cfg(feature = "audio")]
+    pub audio_output_levels: Vec<(Metric, f32)>,
+    pub derived: Vec<(Metric, f32)>,
+    /// The maximums of the metrics with `MetricMax::AutoCalibrate`.
+    pub calibrated_maxes: Vec<(Metric, f32)>,
+}
+
+impl MetricValues {
+    /// `None` when the metric isn't available on this machine, e.g. a GPU that isn't there.
+    /// Clamped to 0.0..1.0, or -1.0..1.0 for the signed `delta`.
+    pub fn get(&self, metric: &Metric) -> Option<f32> {
+        let value = self.get_unclamped(metric)?;
+
+        Some(match metric {
+            Metric::Delta { .. } => value.clamp(-1.0, 1.0),
+            _ => value.clamp(0.0, 1.0),
+        })
+    }
+
+    /// The sensor reading of a `Temperature` metric in Celsius, before mapping it to 0.0..1.0.
+    pub fn temperature(&self, metric: &Metric) -> Option<f32> {
+        self.temperatures
+            .iter()
+            .find(|(temperature_metric, _)| temperature_metric == metric)
+            .map(|(_, celsius)| *celsius)
+    }
+
+    /// `get` before clamping, out of range when a source misbehaves or its scale is off, e.g. a
+    /// `max_bytes_per_second` below the actual throughput.
+    fn get_unclamped(&self, metric: &Metric) -> Option<f32> {
+        match metric {
+            Metric::CpuUsage => Some(self.cpu_usage),
+            Metric::CpuUsageWindow { statistic } => Some(self.cpu_usage_window.get(*statistic)),
+            Metric::CpuCore { index } => self.cpu_cores.get(*index).copied(),
+            Metric::MemoryUsage => Some(self.memory_usage),
+            Metric::MemoryPressure {
+                ram_weight,
+                swap_weight,
+                psi_weight,
+            } => Some(
+                self.memory_pressure
+                    .weighted(*ram_weight, *swap_weight, *psi_weight),
+            ),
+            Metric::Constant(value) => Some(*value),
+            Metric::TimeOfDay {
+                peak_hour,
+                amplitude,
+                baseline,
+            } => {
+                let angle = std::f32::consts::TAU * (self.local_hour - peak_hour) / 24.0;
+                Some((baseline + amplitude * angle.cos()).clamp(0.0, 1.0))
+            }
+            Metric::NetworkUpload {
+                max_bytes_per_second,
+            } => self.normalize(metric, self.network_upload, max_bytes_per_second),
+            Metric::NetworkDownload {
+                max_bytes_per_second,
+            } => self.normalize(metric, self.network_download, max_bytes_per_second),
+            Metric::GpuUtil { index } => self.gpus.get(*index).map(|gpu| gpu.utilization),
+            Metric::GpuMem { index } => self.gpus.get(*index).map(|gpu| gpu.memory),
+            Metric::Temperature {
+                min_celsius,
+                max_celsius,
+                ..
+            } => self
+                .temperature(metric)
+                .map(|celsius| (celsius - min_celsius) / (max_celsius - min_celsius)),
+            #[cfg(feature = "audio")]
+            Metric::AudioOutputLevel { .. } => self
+                .audio_output_levels
+                .iter()
+                .find(|(audio_metric, _)| audio_metric == metric)
+                .map(|(_, level)| *level),
+            Metric::Percentile { .. }
+            | Metric::Hysteresis { .. }
+            | Metric::Smoothed { .. }
+            | Metric::Delta { .. }
+            | Metric::AbsDelta { .. } => self
+                .derived
+                .iter()
+                .find(|(derived_metric, _)| derived_metric == metric)
+                .map(|(_, value)| *value),
+        }
+    }
+}
+
+impl MetricValues {
+    /// The value of a `metric` with a maximum, `None` before a calibrated one has a sample.
+    fn normalize(&self, metric: &Metric, value: f32, max: &MetricMax) -> Option<f32> {
+        let max = match max {
+            MetricMax::Fixed(max) => *max,
+            MetricMax::AutoCalibrate(_) => self
+                .calibrated_maxes
+                .iter()
+                .find(|(calibrated_metric, _)| calibrated_metric == metric)
+                .map(|(_, max)| *max)?,
+        };
+
+        Some(if max > 0.0 { value / max } else { 0.0 })
+    }
+
+    /// The value of a metric with a maximum before normalizing it.
+    fn raw(&self, metric: &Metric) -> Option<f32> {
+        match metric {
+            Metric::NetworkUpload { .. } => Some(self.network_upload),
+            Metric::NetworkDownload { .. } => Some(self.network_download),
+            _ => None,
+        }
+    }
+}
+
+/// The CPU time from whichever source works on this machine.
+enum CpuSnapshot {
+    CpuMonitor(CpuInstant),
+    #[cfg(target_os = "linux")]
+    ProcStat(proc_stat::ProcStatCpuInstant),
+}
+
+pub struct MetricSampler {
+    cpu_smoother: Smoother,
+    cpu_window: SampleWindow,
+    /// How long the last sample is reused, and so how long the CPU utilization is measured over.
+    sample_interval: Duration,
+    /// Set once `cpu_monitor` failed, e.g. without the permissions it needs.
+    #[cfg(target_os = "linux")]
+    use_proc_stat: bool,
+    adaptive_timer: Option<AdaptiveTimer>,
+    /// Whether anything reads the CPU usage, see `measures_cpu`.
+    cpu: bool,
+    /// The CPU time at the last sample, the next one measures the utilization since then.
+    cpu_start: Option<CpuSnapshot>,
+    sys: System,
+    per_core: bool,
+    /// Whether to sample the RAM usage.
+    memory: bool,
+    /// Whether to sample the swap and PSI too.
+    memory_pressure: bool,
+    last_cpu_refresh: Instant,
+    /// Only when a network metric is used.
+    networks: Option<Networks>,
+    gpus: Option<Gpus>,
+    components: Option<Components>,
+    temperature_metrics: Vec<Metric>,
+    temperature_unit: TemperatureUnit,
+    #[cfg(feature = "audio")]
+    audio_monitors: Vec<(Metric, audio::AudioLevelMonitor)>,
+    transforms: Vec<(Metric, Transform)>,
+    normalizers: Vec<(Metric, MetricNormalizer)>,
+    calibration_state: CalibrationState,
+    /// Every referenced metric, to report the unavailable ones after the first sample.
+    metrics: Vec<Metric>,
+    /// When each of `metrics` was last reported out of range.
+    range_warnings: Vec<Option<Instant>>,
+    availability_reported: bool,
+    started: Instant,
+    last_sample: Instant,
+    ticks: u64,
+    /// Handed out again until the sample interval passed.
+    last_values: Option<MetricValues>,
+}
+
+impl MetricSampler {
+    pub fn new(config: &Config) -> Result<Self, Box<dyn Error>> {
+        let metrics = config.metrics();
+        debug!("Sampling metrics: {metrics:?}");
+
+        let gpus = metrics
+            .iter()
+            .any(|metric| matches!(metric, Metric::GpuUtil { .. } | Metric::GpuMem { .. }))
+            .then(Gpus::detect);
+
+        let mut temperature_metrics: Vec<Metric> = Vec::new();
+        for &metric in &metrics {
+            if matches!(metric, Metric::Temperature { .. }) && !temperature_metrics.contains(metric)
+            {
+                temperature_metrics.push(metric.clone());
+            }
+        }
+        let components =
+            (!temperature_metrics.is_empty()).then(Components::new_with_refreshed_list);
+
+        let per_core = metrics
+            .iter()
+            .any(|metric| matches!(metric, Metric::CpuCore { .. }));
+
+        // The adaptive sample rate follows the CPU usage.
+        let cpu = config.adaptive_sample_rate
+            || metrics
+                .iter()
+                .any(|metric| matches!(metric, Metric::CpuUsage | Metric::CpuUsageWindow { .. }));
+
+        let memory_pressure = metrics
+            .iter()
+            .any(|metric| matches!(metric, Metric::MemoryPressure { .. }));
+        let memory = memory_pressure
+            || metrics
+                .iter()
+                .any(|metric| matches!(metric, Metric::MemoryUsage));
+
+        let networks = metrics
+            .iter()
+            .any(|metric| {
+                matches!(
+                    metric,
+                    Metric::NetworkUpload { .. } | Metric::NetworkDownload { .. }
+                )
+            })
+            .then(Networks::new_with_refreshed_list);
+
+        let mut refresh_kind = RefreshKind::nothing();
+        if memory {
+            refresh_kind = refresh_kind.with_memory(memory_refresh_kind(memory_pressure));
+        }
+        let mut sys = System::new_with_specifics(refresh_kind);
+        if per_core {
+            // Usage is computed between two refreshes, this one is the baseline.
+            sys.refresh_cpu_usage();
+        }
+
+        #[cfg(feature = "audio")]
+        let audio_monitors = open_audio_monitors(&metrics)?;
+
+        // Inner metrics come first, so derived metrics can be computed in order.
+        let mut transforms: Vec<(Metric, Transform)> = Vec::new();
+        for &metric in &metrics {
+            if transforms.iter().any(|(known, _)| known == metric) {
+                continue;
+            }
+
+            if let Some(transform) = Transform::for_metric(metric) {
+                transforms.push((metric.clone(), transform));
+            }
+        }
+
+        let unique_metrics = metrics.iter().fold(Vec::new(), |mut unique, &metric| {
+            if !unique.contains(metric) {
+                unique.push(metric.clone());
+            }
+            unique
+        });
+
+        let calibration_state = CalibrationState::load();
+        let mut normalizers: Vec<(Metric, MetricNormalizer)> = Vec::new();
+        for &metric in &metrics {
+            let (Metric::NetworkUpload {
+                max_bytes_per_second: MetricMax::AutoCalibrate(calibration),
+            }
+            | Metric::NetworkDownload {
+                max_bytes_per_second: MetricMax::AutoCalibrate(calibration),
+            }) = metric
+            else {
+                continue;
+            };
+            if normalizers.iter().any(|(known, _)| known == metric) {
+                continue;
+            }
+
+            let normalizer =
+                MetricNormalizer::new(calibration.clone(), calibration_state.get(metric));
+            normalizers.push((metric.clone(), normalizer));
+        }
+
+        Ok(Self {
+            cpu_smoother: config.smoothing_profile.smoother(SAMPLE_BUFFER_SIZE),
+            cpu_window: SampleWindow::new(SAMPLE_BUFFER_SIZE),
+            sample_interval: Duration::from_millis(SAMPLE_RATE),
+            #[cfg(target_os = "linux")]
+            use_proc_stat: false,
+            adaptive_timer: config.adaptive_sample_rate.then(|| {
+                AdaptiveTimer::new(
+                    &config.adaptive_sampling,
+                    Duration::from_millis(SAMPLE_RATE),
+                )
+            }),
+            cpu,
+            cpu_start: None,
+            sys,
+            per_core,
+            memory,
+            memory_pressure,
+            last_cpu_refresh: Instant::now(),
+            networks,
+            gpus,
+            components,
+            temperature_metrics,
+            temperature_unit: config.temperature_unit,
+            #[cfg(feature = "audio")]
+            audio_monitors,
+            transforms,
+            normalizers,
+            calibration_state,
+            range_warnings: vec![None; unique_metrics.len()],
+            metrics: unique_metrics,
+            availability_reported: false,
+            started: Instant::now(),
+            last_sample: Instant::now(),
+            ticks: 0,
+            last_values: None,
+        })
+    }
+
+    /// Samples the metrics the config references, once per `SAMPLE_RATE` milliseconds or whatever
+    /// the adaptive timer settled on. Until then it returns right away, with the values of the
+    /// last sample at the current time. The CPU usage is measured since the last sample, so only
+    /// the first one waits a whole sample interval for it.
+    pub async fn sample(&mut self) -> Result<MetricValues, Box<dyn Error>> {
+        if self.last_sample.elapsed() + SAMPLE_INTERVAL_SLACK < self.sample_interval {
+            if let Some(last_values) = &self.last_values {
+                return Ok(MetricValues {
+                    time_secs: self.started.elapsed().as_secs_f64(),
+                    local_hour: local_hour(),
+                    ..last_values.clone()
+                });
+            }
+        }
+
+        let cpu_usage = if self.cpu {
+            self.measure_cpu_usage().await?
+        } else {
+            0.0
+        };
+
+        if let Some(adaptive_timer) = &mut self.adaptive_timer {
+            self.sample_interval = adaptive_timer.tick(cpu_usage);
+        }
+
+        self.cpu_window.push(cpu_usage);
+        let cpu_usage = self.cpu_smoother.push(cpu_usage);
+
+        // Per-core utilization, `cpu_monitor` only has the aggregate. Refreshing more often than
+        // sysinfo's minimum interval would read zeros, so the last values are kept until then.
+        if self.per_core && self.last_cpu_refresh.elapsed() >= MINIMUM_CPU_UPDATE_INTERVAL {
+            self.sys.refresh_cpu_usage();
+            self.last_cpu_refresh = Instant::now();
+        }
+        let cpu_cores = if self.per_core {
+            self.sys
+                .cpus()
+                .iter()
+                .map(|cpu| cpu.cpu_usage() / 100.0)
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        // Memory utilization.
+        let memory_usage = if self.memory {
+            self.sys
+                .refresh_memory_specifics(memory_refresh_kind(self.memory_pressure));
+            self.sys.used_memory() as f32 / self.sys.total_memory() as f32
+        } else {
+            0.0
+        };
+        let memory_pressure = if self.memory_pressure {
+            MemoryPressure {
+                ram: memory_usage,
+                swap: (self.sys.total_swap() > 0)
+                    .then(|| self.sys.used_swap() as f32 / self.sys.total_swap() as f32),
+                psi: pressure::read_psi(),
+            }
+        } else {
+            MemoryPressure {
+                ram: memory_usage,
+                ..MemoryPressure::default()
+            }
+        };
+
+        let elapsed = self.last_sample.elapsed().as_secs_f32();
+        self.last_sample = Instant::now();
+        self.ticks += 1;
+
+        // Network throughput since the last sample.
+        let (transmitted, received) = match &mut self.networks {
+            Some(networks) => {
+                networks.refresh(true);
+                networks
+                    .list()
+                    .values()
+                    .fold((0, 0), |(transmitted, received), network| {
+                        (
+                            transmitted + network.transmitted(),
+                            received + network.received(),
+                        )
+                    })
+            }
+            None => (0, 0),
+        };
+
+        let mut values = MetricValues {
+            time_secs: self.started.elapsed().as_secs_f64(),
+            tick: self.ticks,
+            local_hour: local_hour(),
+            cpu_usage,
+            cpu_usage_window: self.cpu_window.values(),
+            cpu_cores,
+            memory_usage,
+            memory_pressure,
+            network_upload: transmitted as f32 / elapsed,
+            network_download: received as f32 / elapsed,
+            gpus: self.gpus.as_ref().map(Gpus::sample).unwrap_or_default(),
+            temperatures: self.sample_temperatures(),
+            #[cfg(feature = "audio")]
+            audio_output_levels: self
+                .audio_monitors
+                .iter_mut()
+                .map(|(metric, monitor)| (metric.clone(), monitor.sample(elapsed)))
+                .collect(),
+            derived: Vec::new(),
+            calibrated_maxes: Vec::new(),
+        };
+
+        for (metric, normalizer) in &mut self.normalizers {
+            let Some(value) = values.raw(metric) else {
+                continue;
+            };
+            let calibrated = normalizer.observed_max().is_some();
+            let max = normalizer.observe(value);
+            if !calibrated && normalizer.observed_max().is_some() {
+                self.calibration_state.save(metric, max);
+            }
+            values.calibrated_maxes.push((metric.clone(), max));
+        }
+
+        // Derived metrics of unavailable ones are unavailable too.
+        for (metric, transform) in &mut self.transforms {
+            let Some(value) = metric.inner().and_then(|inner| values.get(inner)) else {
+                continue;
+            };
+            let value = transform.apply(value);
+            values.derived.push((metric.clone(), value));
+        }
+
+        self.warn_out_of_range(&values);
+
+        if !self.availability_reported {
+            for metric in &self.metrics {
+                if values.get(metric).is_none() {
+                    warn!("Metric {metric:?} is not available on this machine");
+                }
+            }
+            self.availability_reported = true;
+        }
+
+        self.last_values = Some(values.clone());
+
+        Ok(values)
+    }
+
+    /// Whether sampling measures the CPU usage.
+    pub fn measures_cpu(&self) -> bool {
+        self.cpu
+    }
+
+    pub fn sample_interval(&self) -> Duration {
+        self.sample_interval
+    }
+
+    /// Reports the metrics that came out of range, at most every `RANGE_WARNING_INTERVAL` each,
+    /// `MetricValues::get` clamps them.
+    fn warn_out_of_range(&mut self, values: &MetricValues) {
+        for (metric, last_warning) in self.metrics.iter().zip(&mut self.range_warnings) {
+            let Some(value) = values.get_unclamped(metric) else {
+                continue;
+            };
+            let range = match metric {
+                Metric::Delta { .. } => -1.0..=1.0,
+                _ => 0.0..=1.0,
+            };
+            if range.contains(&value)
+                || last_warning.is_some_and(|last| last.elapsed() < RANGE_WARNING_INTERVAL)
+            {
+                continue;
+            }
+
+            warn!(
+                "Metric {metric:?} is {value}, outside {}..{}, clamping it. Is its scale right?",
+                range.start(),
+                range.end()
+            );
+            *last_warning = Some(Instant::now());
+        }
+    }
+
+    /// The metrics sampled, each once.
+    pub fn metrics(&self) -> &[Metric] {
+        &self.metrics
+    }
+
+    /// The CPU utilization since the last sample, or over a whole sample interval for the first.
+    async fn measure_cpu_usage(&mut self) -> Result<f32, Box<dyn Error>> {
+        let start = match self.cpu_start.take() {
+            Some(start) => start,
+            None => {
+                let start = self.cpu_snapshot()?;
+                tokio::time::sleep(self.sample_interval).await;
+                start
+            }
+        };
+        let end = self.cpu_snapshot()?;
+
+        let usage = match (&start, &end) {
+            (CpuSnapshot::CpuMonitor(start), CpuSnapshot::CpuMonitor(end)) => {
+                (*end - *start).non_idle()
+            }
+            #[cfg(target_os = "linux")]
+            (CpuSnapshot::ProcStat(start), CpuSnapshot::ProcStat(end)) => end.non_idle_since(start),
+            // cpu_monitor stopped working since the last sample, start over from /proc/stat.
+            #[cfg(target_os = "linux")]
+            _ => return Box::pin(self.measure_cpu_usage()).await,
+        };
+        self.cpu_start = Some(end);
+
+        Ok(usage as f32)
+    }
+
+    fn cpu_snapshot(&mut self) -> Result<CpuSnapshot, Box<dyn Error>> {
+        #[cfg(target_os = "linux")]
+        if self.use_proc_stat {
+            return Ok(CpuSnapshot::ProcStat(proc_stat::ProcStatCpuInstant::now()?));
+        }
+
+        match CpuInstant::now() {
+            Ok(instant) => Ok(CpuSnapshot::CpuMonitor(instant)),
+            #[cfg(target_os = "linux")]
+            Err(e) => {
+                info!("Can't read the CPU time through cpu_monitor ({e}), using /proc/stat");
+                self.use_proc_stat = true;
+
+                self.cpu_snapshot()
+            }
+            #[cfg(not(target_os = "linux"))]
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn sample_temperatures(&mut self) -> Vec<(Metric, f32)> {
+        let Some(components) = &mut self.components else {
+            return Vec::new();
+        };
+        components.refresh(false);
+
+        let mut temperatures = Vec::new();
+        for metric in &self.temperature_metrics {
+            let Metric::Temperature { sensor, .. } = metric else {
+                continue;
+            };
+
+            let Some((label, celsius)) = components
+                .iter()
+                .find(|component| component.label().contains(sensor.as_str()))
+                .and_then(|component| Some((component.label(), component.temperature()?)))
+            else {
+                continue;
+            };
+
+            debug!("{label}: {}", self.temperature_unit.format(celsius));
+            temperatures.push((metric.clone(), celsius));
+        }
+
+        temperatures
+    }
+}
+
+/// Samples every referenced metric once and logs a table of whether each is available and its
+/// value, to catch missing drivers, sensors or permissions at startup. Fails on an unavailable
+/// metric when `strict`.
+pub async fn self_test(sampler: &mut MetricSampler, strict: bool) -> Result<(), Box<dyn Error>> {
+    // The table reports them instead.
+    sampler.availability_reported = true;
+    let values = sampler.sample().await?;
+
+    let rows: Vec<(String, Option<f32>)> = sampler
+        .metrics()
+        .iter()
+        .map(|metric| (format!("{metric:?}"), values.get(metric)))
+        .collect();
+    let width = rows
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or_default()
+        .max("Metric".len());
+
+    info!("{:<width$}  Available  Value", "Metric");
+    for (name, value) in &rows {
+        match value {
+            Some(value) => info!("{name:<width$}  yes        {value:.3}"),
+            None => warn!("{name:<width$}  no"),
+        }
+    }
+
+    let unavailable: Vec<&str> = rows
+        .iter()
+        .filter(|(_, value)| value.is_none())
+        .map(|(name, _)| name.as_str())
+        .collect();
+    if strict && !unavailable.is_empty() {
+        return Err(format!("unavailable metrics: {}", unavailable.join(", ")).into());
+    }
+
+    Ok(())
+}
+
+fn memory_refresh_kind(swap: bool) -> MemoryRefreshKind {
+    if swap {
+        MemoryRefreshKind::nothing().with_ram().with_swap()
+    } else {
+        MemoryRefreshKind::nothing().with_ram()
+    }
+}
+
+fn local_hour() -> f32 {
+    let now = Local::now();
+
+    now.hour() as f32 + now.minute() as f32 / 60.0 + now.second() as f32 / 3600.0
+}
+
+#[cfg(feature = "audio")]
+fn open_audio_monitors(
+    metrics: &[&Metric],
+) -> Result<Vec<(Metric, audio::AudioLevelMonitor)>, Box<dyn Error>> {
+    let mut audio_monitors: Vec<(Metric, audio::AudioLevelMonitor)> = Vec::new();
+    for &metric in metrics {
+        if let Metric::AudioOutputLevel {
+            device_name,
+            decay_rate,
+        } = metric
+        {
+            if !audio_monitors.iter().any(|(known, _)| known == metric) {
+                let monitor = audio::AudioLevelMonitor::new(device_name.as_deref(), *decay_rate)?;
+                audio_monitors.push((metric.clone(), monitor));
+            }
+        }
+    }
+
+    Ok(audio_monitors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ControllerConfig;
+
+    #[tokio::test]
+    async fn reuses_the_last_sample_when_the_cpu_is_unused() {
+        let config = Config {
+            controllers: vec![ControllerConfig {
+                name: "Strip".to_string(),
+                metric: Metric::Constant(0.5),
+                ..Default::default()
+            }],
+            ..Config::default()
+        };
+        let mut sampler = MetricSampler::new(&config).unwrap();
+        assert!(!sampler.measures_cpu());
+
+        let start = Instant::now();
+        let first = sampler.sample().await.unwrap();
+        let second = sampler.sample().await.unwrap();
+        assert!(start.elapsed() < sampler.sample_interval());
+        assert_eq!(second.tick, first.tick);
+        assert!(second.time_secs >= first.time_secs);
+
+        tokio::time::sleep(sampler.sample_interval()).await;
+        assert_eq!(sampler.sample().await.unwrap().tick, first.tick + 1);
+    }
+
+    #[tokio::test]
+    async fn measures_the_cpu_since_the_last_sample() {
+        let config = Config {
+            controllers: vec![ControllerConfig {
+                name: "Strip".to_string(),
+                metric: Metric::CpuUsage,
+                ..Default::default()
+            }],
+            ..Config::default()
+        };
+        let mut sampler = MetricSampler::new(&config).unwrap();
+        assert!(sampler.measures_cpu());
+        let interval = sampler.sample_interval();
+
+        // Only the first sample waits to measure over a whole interval.
+        let start = Instant::now();
+        let first = sampler.sample().await.unwrap();
+        assert!(start.elapsed() >= interval);
+        assert!((0.0..=1.0).contains(&first.cpu_usage));
+
+        let start = Instant::now();
+        assert_eq!(sampler.sample().await.unwrap().tick, first.tick);
+        tokio::time::sleep(interval).await;
+        let second = sampler.sample().await.unwrap();
+        assert_eq!(second.tick, first.tick + 1);
+        assert!((0.0..=1.0).contains(&second.cpu_usage));
+        assert!(start.elapsed() < interval * 3 / 2, "{:?}", start.elapsed());
+    }
+
+    #[test]
+    fn time_of_day_peaks_at_the_peak_hour() {
+        let metric = Metric::TimeOfDay {
+            peak_hour: 12.0,
+            amplitude: 0.5,
+            baseline: 0.5,
+        };
+        let at = |local_hour: f32| {
+            MetricValues {
+                local_hour,
+                ..MetricValues::default()
+            }
+            .get(&metric)
+            .unwrap()
+        };
+
+        assert!((at(12.0) - 1.0).abs() < 1e-6);
+        assert!(at(0.0).abs() < 1e-6);
+        assert!((at(6.0) - 0.5).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,112 @@
+use ringbuffer::{AllocRingBuffer, RingBuffer};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Samples the variance is computed over.
+const VARIANCE_WINDOW: usize = 5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AdaptiveSampleRateConfig {
+    /// Below this CPU usage variance the samples count as steady.
+    pub low_variance_threshold: f32,
+    /// Above this CPU usage variance the interval is halved.
+    pub high_variance_threshold: f32,
+    /// Consecutive steady samples before the interval is doubled.
+    pub steady_samples: usize,
+    pub min_sleep_ms: u64,
+    pub max_sleep_ms: u64,
+}
+
+impl Default for AdaptiveSampleRateConfig {
+    fn default() -> Self {
+        Self {
+            low_variance_threshold: 0.0001,  // 1 % standard deviation.
+            high_variance_threshold: 0.0025, // 5 % standard deviation.
+            steady_samples: 10,
+            min_sleep_ms: 250,
+            max_sleep_ms: 4000,
+        }
+    }
+}
+
+/// Stretches the sample interval while the metric is steady and shortens it again as soon as it
+/// moves, so an idle machine isn't sampled every 500 ms for nothing.
+pub struct AdaptiveTimer {
+    config: AdaptiveSampleRateConfig,
+    samples: AllocRingBuffer<f32>,
+    steady_samples: usize,
+    interval: Duration,
+}
+
+impl AdaptiveTimer {
+    pub fn new(config: &AdaptiveSampleRateConfig, initial: Duration) -> Self {
+        Self {
+            config: config.clone(),
+            samples: AllocRingBuffer::new(VARIANCE_WINDOW),
+            steady_samples: 0,
+            interval: initial.clamp(
+                Duration::from_millis(config.min_sleep_ms),
+                Duration::from_millis(config.max_sleep_ms),
+            ),
+        }
+    }
+
+    /// Records a sample and returns how long to wait before the next one.
+    pub fn tick(&mut self, value: f32) -> Duration {
+        self.samples.push(value);
+        let variance = variance(&self.samples);
+
+        if variance > self.config.high_variance_threshold {
+            self.steady_samples = 0;
+            self.interval =
+                (self.interval / 2).max(Duration::from_millis(self.config.min_sleep_ms));
+        } else if variance < self.config.low_variance_threshold {
+            self.steady_samples += 1;
+            if self.steady_samples >= self.config.steady_samples {
+                self.steady_samples = 0;
+                self.interval =
+                    (self.interval * 2).min(Duration::from_millis(self.config.max_sleep_ms));
+            }
+        } else {
+            self.steady_samples = 0;
+        }
+
+        self.interval
+    }
+}
+
+fn variance(samples: &AllocRingBuffer<f32>) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+
+    let count = samples.len() as f32;
+    let mean = samples.iter().sum::<f32>() / count;
+
+    samples
+        .iter()
+        .map(|sample| (sample - mean).powi(2))
+        .sum::<f32>()
+        / count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stretches_while_steady_and_shortens_on_a_spike() {
+        let config = AdaptiveSampleRateConfig::default();
+        let mut timer = AdaptiveTimer::new(&config, Duration::from_millis(500));
+
+        for _ in 0..9 {
+            assert_eq!(timer.tick(0.2), Duration::from_millis(500));
+        }
+        assert_eq!(timer.tick(0.2), Duration::from_millis(1000));
+
+        assert_eq!(timer.tick(0.9), Duration::from_millis(500));
+        // Still moving while the spike is in the window.
+        assert_eq!(timer.tick(0.2), Duration::from_millis(250));
+    }
+}
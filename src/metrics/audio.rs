@@ -0,0 +1,149 @@
+use libpulse_binding::{
+    error::PAErr,
+    sample::{Format, Spec},
+    stream::Direction,
+};
+use libpulse_simple_binding::Simple;
+use std::{
+    error::Error,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+use tracing::error;
+
+const CAPTURE_RATE: u32 = 44100;
+const CAPTURE_CHANNELS: u8 = 2;
+const FRAME_SAMPLES: usize = 1024;
+
+/// Tracks the peak level of a playback device through its PulseAudio/PipeWire monitor source.
+pub struct AudioLevelMonitor {
+    peak: Arc<Mutex<f32>>,
+    level: f32,
+    decay_rate: f32, // level per second.
+}
+
+impl AudioLevelMonitor {
+    pub fn new(device_name: Option<&str>, decay_rate: f32) -> Result<Self, Box<dyn Error>> {
+        let device = device_name
+            .map(|name| format!("{name}.monitor"))
+            .unwrap_or_else(|| "@DEFAULT_MONITOR@".to_string());
+        let peak = Arc::new(Mutex::new(0.0));
+
+        // The stream is owned by the capture thread, so it reports back once it is open.
+        let (ready_sender, ready_receiver) = mpsc::channel();
+        thread::spawn({
+            let peak = peak.clone();
+            move || match open_monitor_stream(&device) {
+                Ok(stream) => {
+                    let _ = ready_sender.send(Ok(()));
+                    read_peaks(&stream, &peak);
+                }
+                Err(e) => {
+                    let _ = ready_sender.send(Err(format!("{device}: {e}")));
+                }
+            }
+        });
+        ready_receiver.recv()??;
+
+        Ok(Self::with_peaks(peak, decay_rate))
+    }
+
+    /// Tracks the peaks some source reports into `peak`, see `report_peak`.
+    fn with_peaks(peak: Arc<Mutex<f32>>, decay_rate: f32) -> Self {
+        Self {
+            peak,
+            level: 0.0,
+            decay_rate,
+        }
+    }
+
+    /// Returns the level for this tick, decaying towards 0 when the peak drops.
+    pub fn sample(&mut self, elapsed_secs: f32) -> f32 {
+        let peak = std::mem::take(&mut *self.peak.lock().unwrap());
+        self.level = update_level(self.level, peak, self.decay_rate, elapsed_secs);
+
+        self.level
+    }
+}
+
+fn update_level(level: f32, peak: f32, decay_rate: f32, elapsed_secs: f32) -> f32 {
+    peak.max(level - decay_rate * elapsed_secs).clamp(0.0, 1.0)
+}
+
+fn open_monitor_stream(device: &str) -> Result<Simple, PAErr> {
+    let spec = Spec {
+        format: Format::F32le,
+        channels: CAPTURE_CHANNELS,
+        rate: CAPTURE_RATE,
+    };
+
+    Simple::new(
+        None,
+        env!("CARGO_PKG_NAME"),
+        Direction::Record,
+        Some(device),
+        "Audio output level",
+        &spec,
+        None,
+        None,
+    )
+}
+
+fn read_peaks(stream: &Simple, peak: &Mutex<f32>) {
+    let mut buffer = vec![0; FRAME_SAMPLES * size_of::<f32>()];
+
+    loop {
+        if let Err(e) = stream.read(&mut buffer) {
+            error!("Failed to read audio samples: {e}");
+            return;
+        }
+
+        let frame_peak = buffer
+            .chunks_exact(size_of::<f32>())
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()).abs())
+            .fold(0.0, f32::max);
+
+        report_peak(peak, frame_peak);
+    }
+}
+
+/// Keeps the highest peak until the monitor takes it on its next sample.
+fn report_peak(peak: &Mutex<f32>, frame_peak: f32) {
+    let mut peak = peak.lock().unwrap();
+    *peak = peak.max(frame_peak);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reports a known peak per tick, like the capture thread does per frame.
+    struct MockAudioSource {
+        peak: Arc<Mutex<f32>>,
+    }
+
+    impl MockAudioSource {
+        fn emit(&self, frame_peaks: &[f32]) {
+            for frame_peak in frame_peaks {
+                report_peak(&self.peak, *frame_peak);
+            }
+        }
+    }
+
+    #[test]
+    fn follows_the_peaks_and_decays_on_silence() {
+        let peak = Arc::new(Mutex::new(0.0));
+        let source = MockAudioSource { peak: peak.clone() };
+        let mut monitor = AudioLevelMonitor::with_peaks(peak, 1.0);
+
+        // Frames per tick, the level takes the highest of them.
+        let ticks: [&[f32]; 6] = [&[0.2, 0.5], &[], &[], &[0.1, 0.8, 0.3], &[0.0], &[1.5]];
+        let expected = [0.5, 0.4, 0.3, 0.8, 0.7, 1.0];
+
+        for (frame_peaks, expected) in ticks.iter().zip(expected) {
+            source.emit(frame_peaks);
+            let level = monitor.sample(0.1);
+            assert!((level - expected).abs() < 1e-5, "{level} != {expected}");
+        }
+    }
+}
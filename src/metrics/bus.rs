@@ -0,0 +1,140 @@
+use super::{Metric, MetricValues};
+use std::sync::Mutex;
+use tokio::sync::watch;
+
+/// Hands the value of a metric to whoever subscribed to it, e.g. a task outside the render loop,
+/// once per sample tick. Every subscriber of a metric sees the value of the same tick.
+#[derive(Default)]
+pub struct MetricBus {
+    senders: Mutex<Vec<(Metric, watch::Sender<f32>)>>,
+    /// The sensor readings of `Temperature` metrics, in Celsius.
+    temperature_senders: Mutex<Vec<(Metric, watch::Sender<f32>)>>,
+}
+
+impl MetricBus {
+    /// Only the metrics the config references are sampled, the receiver of any other keeps 0.0.
+    /// An unavailable metric leaves the receiver at its last value.
+    pub fn subscribe(&self, metric: Metric) -> watch::Receiver<f32> {
+        subscribe_to(&self.senders, metric)
+    }
+
+    /// The sensor readings of the `Temperature` metrics among `metrics`, in Celsius, like
+    /// `subscribe_all` for their values.
+    pub fn subscribe_temperatures<'a>(
+        &self,
+        metrics: impl IntoIterator<Item = &'a Metric>,
+    ) -> Vec<(Metric, watch::Receiver<f32>)> {
+        let mut receivers: Vec<(Metric, watch::Receiver<f32>)> = Vec::new();
+        for metric in metrics {
+            let is_temperature = matches!(metric, Metric::Temperature { .. });
+            if is_temperature && !receivers.iter().any(|(known, _)| known == metric) {
+                receivers.push((
+                    metric.clone(),
+                    subscribe_to(&self.temperature_senders, metric.clone()),
+                ));
+            }
+        }
+
+        receivers
+    }
+
+    /// Subscribes to each of `metrics` once, however often it's listed.
+    pub fn subscribe_all<'a>(
+        &self,
+        metrics: impl IntoIterator<Item = &'a Metric>,
+    ) -> Vec<(Metric, watch::Receiver<f32>)> {
+        let mut receivers: Vec<(Metric, watch::Receiver<f32>)> = Vec::new();
+        for metric in metrics {
+            if !receivers.iter().any(|(known, _)| known == metric) {
+                receivers.push((metric.clone(), self.subscribe(metric.clone())));
+            }
+        }
+
+        receivers
+    }
+
+    /// Sends every subscribed metric its value of this tick, dropping the ones nobody listens to
+    /// anymore.
+    pub fn publish(&self, values: &MetricValues) {
+        publish_to(&self.senders, |metric| values.get(metric));
+        publish_to(&self.temperature_senders, |metric| {
+            values.temperature(metric)
+        });
+    }
+}
+
+fn subscribe_to(
+    senders: &Mutex<Vec<(Metric, watch::Sender<f32>)>>,
+    metric: Metric,
+) -> watch::Receiver<f32> {
+    let mut senders = senders.lock().unwrap();
+    if let Some((_, sender)) = senders.iter().find(|(known, _)| *known == metric) {
+        return sender.subscribe();
+    }
+
+    let (sender, receiver) = watch::channel(0.0);
+    senders.push((metric, sender));
+
+    receiver
+}
+
+fn publish_to(
+    senders: &Mutex<Vec<(Metric, watch::Sender<f32>)>>,
+    value: impl Fn(&Metric) -> Option<f32>,
+) {
+    let mut senders = senders.lock().unwrap();
+    senders.retain(|(_, sender)| !sender.is_closed());
+
+    for (metric, sender) in senders.iter() {
+        if let Some(value) = value(metric) {
+            sender.send_replace(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subscribers_see_the_same_tick() {
+        let bus = MetricBus::default();
+        let mut first = bus.subscribe(Metric::CpuUsage);
+        let mut second = bus.subscribe(Metric::CpuUsage);
+        let mut other = bus.subscribe(Metric::CpuCore { index: 0 });
+
+        bus.publish(&MetricValues {
+            cpu_usage: 0.25,
+            cpu_cores: vec![0.5],
+            ..Default::default()
+        });
+
+        assert!(first.has_changed().unwrap());
+        assert_eq!(*first.borrow_and_update(), 0.25);
+        assert_eq!(*second.borrow_and_update(), 0.25);
+        assert_eq!(*other.borrow_and_update(), 0.5);
+
+        bus.publish(&MetricValues {
+            cpu_usage: 0.75,
+            ..Default::default()
+        });
+
+        assert_eq!(*first.borrow_and_update(), 0.75);
+        assert_eq!(*second.borrow_and_update(), 0.75);
+        // Unavailable, so it keeps its last value.
+        assert!(!other.has_changed().unwrap());
+        assert_eq!(*other.borrow(), 0.5);
+    }
+
+    #[test]
+    fn drops_metrics_nobody_listens_to() {
+        let bus = MetricBus::default();
+        let receivers = bus.subscribe_all([&Metric::CpuUsage, &Metric::CpuUsage]);
+        assert_eq!(receivers.len(), 1);
+
+        drop(receivers);
+        bus.publish(&MetricValues::default());
+
+        assert!(bus.senders.lock().unwrap().is_empty());
+    }
+}
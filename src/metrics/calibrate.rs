@@ -0,0 +1,169 @@
+use super::Metric;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, env, fs, path::PathBuf};
+use tracing::{info, warn};
+
+/// The maximum a metric is normalized against: fixed, or calibrated from the values observed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MetricMax {
+    Fixed(f32),
+    AutoCalibrate(AutoCalibrate),
+}
+
+/// Takes the maximum from the first `warmup_samples` values, as their `percentile_max`
+/// percentile, so a rare spike doesn't set it. Until then the largest value so far is used.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AutoCalibrate {
+    pub warmup_samples: usize,
+    /// From 0.0 to 1.0.
+    #[serde(default = "default_percentile_max")]
+    pub percentile_max: f32,
+}
+
+fn default_percentile_max() -> f32 {
+    0.99
+}
+
+/// Calibrates the maximum of one metric, see `AutoCalibrate`.
+pub struct MetricNormalizer {
+    calibration: AutoCalibrate,
+    samples: Vec<f32>,
+    observed_max: Option<f32>,
+}
+
+impl MetricNormalizer {
+    /// Starts calibrated with the `observed_max` of an earlier run.
+    pub fn new(calibration: AutoCalibrate, observed_max: Option<f32>) -> Self {
+        Self {
+            calibration,
+            samples: Vec::new(),
+            observed_max,
+        }
+    }
+
+    /// Records a raw value and returns the maximum to normalize it against.
+    pub fn observe(&mut self, value: f32) -> f32 {
+        if let Some(observed_max) = self.observed_max {
+            return observed_max;
+        }
+
+        self.samples.push(value);
+        if self.samples.len() < self.calibration.warmup_samples.max(1) {
+            return self.samples.iter().copied().fold(0.0, f32::max);
+        }
+
+        self.samples.sort_by(f32::total_cmp);
+        let percentile = self.calibration.percentile_max.clamp(0.0, 1.0);
+        let index = ((self.samples.len() - 1) as f32 * percentile).round() as usize;
+        let observed_max = self.samples[index];
+        self.samples = Vec::new();
+        self.observed_max = Some(observed_max);
+
+        observed_max
+    }
+
+    /// `None` during the warmup.
+    pub fn observed_max(&self) -> Option<f32> {
+        self.observed_max
+    }
+}
+
+/// The calibrated maximums of earlier runs, by metric.
+pub struct CalibrationState {
+    path: Option<PathBuf>,
+    maxes: BTreeMap<String, f32>,
+}
+
+impl CalibrationState {
+    /// Reads `$XDG_STATE_HOME/main_pc_2_openrgb_client/calibration.toml`, starting over when it's
+    /// missing or broken.
+    pub fn load() -> Self {
+        let path = state_path();
+        let maxes = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|text| match toml::from_str(&text) {
+                Ok(maxes) => Some(maxes),
+                Err(e) => {
+                    warn!("Ignoring the broken calibration state: {e}");
+                    None
+                }
+            })
+            .unwrap_or_default();
+
+        Self { path, maxes }
+    }
+
+    pub fn get(&self, metric: &Metric) -> Option<f32> {
+        self.maxes.get(&key(metric)).copied()
+    }
+
+    /// Remembers the calibrated maximum of `metric` and writes the state file.
+    pub fn save(&mut self, metric: &Metric, observed_max: f32) {
+        info!("Calibrated the maximum of {metric:?} to {observed_max}");
+        self.maxes.insert(key(metric), observed_max);
+
+        let Some(path) = &self.path else {
+            return;
+        };
+        let result = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| {
+                let text = toml::to_string(&self.maxes).map_err(std::io::Error::other)?;
+                fs::write(path, text)
+            });
+        if let Err(e) = result {
+            warn!(
+                "Failed to write the calibration state to {}: {e}",
+                path.display()
+            );
+        }
+    }
+}
+
+/// The metric as JSON, which changes with its calibration settings so they calibrate anew.
+fn key(metric: &Metric) -> String {
+    serde_json::to_string(metric).unwrap_or_default()
+}
+
+fn state_path() -> Option<PathBuf> {
+    let state_dir = env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))?;
+
+    Some(
+        state_dir
+            .join("main_pc_2_openrgb_client")
+            .join("calibration.toml"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calibrates_to_the_percentile_after_the_warmup() {
+        let mut normalizer = MetricNormalizer::new(
+            AutoCalibrate {
+                warmup_samples: 100,
+                percentile_max: 0.9,
+            },
+            None,
+        );
+
+        // 1 to 100, out of order.
+        let values: Vec<f32> = (0..100).map(|i| ((i * 37) % 100 + 1) as f32).collect();
+        for &value in &values[..99] {
+            normalizer.observe(value);
+            assert_eq!(normalizer.observed_max(), None);
+        }
+        assert_eq!(normalizer.observe(values[99]), 90.0);
+        assert_eq!(normalizer.observed_max(), Some(90.0));
+
+        // A spike afterwards doesn't move it.
+        assert_eq!(normalizer.observe(1000.0), 90.0);
+    }
+}
@@ -0,0 +1,128 @@
+#[cfg(feature = "nvidia")]
+use nvml_wrapper::Nvml;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::info;
+#[cfg(feature = "nvidia")]
+use tracing::warn;
+
+const DRM_PATH: &str = "/sys/class/drm";
+
+/// Utilization of one GPU, both normalized from 0.0 to 1.0.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct GpuUsage {
+    pub utilization: f32,
+    pub memory: f32,
+}
+
+/// The GPUs found at startup. NVIDIA GPUs (through NVML) are indexed first, then AMD GPUs (through
+/// sysfs).
+pub struct Gpus {
+    #[cfg(feature = "nvidia")]
+    nvml: Option<Nvml>,
+    amd_devices: Vec<PathBuf>,
+}
+
+impl Gpus {
+    pub fn detect() -> Self {
+        #[cfg(feature = "nvidia")]
+        let nvml = match Nvml::init() {
+            Ok(nvml) => Some(nvml),
+            Err(e) => {
+                warn!("NVML is not available: {e}");
+                None
+            }
+        };
+
+        let amd_devices = detect_amd_devices();
+        info!("Found {} AMD GPUs", amd_devices.len());
+
+        Self {
+            #[cfg(feature = "nvidia")]
+            nvml,
+            amd_devices,
+        }
+    }
+
+    pub fn sample(&self) -> Vec<GpuUsage> {
+        let mut usages = Vec::new();
+
+        #[cfg(feature = "nvidia")]
+        if let Some(nvml) = &self.nvml {
+            usages.extend(sample_nvidia_devices(nvml));
+        }
+
+        usages.extend(
+            self.amd_devices
+                .iter()
+                .map(|device| sample_amd_device(device).unwrap_or_default()),
+        );
+
+        usages
+    }
+}
+
+#[cfg(feature = "nvidia")]
+fn sample_nvidia_devices(nvml: &Nvml) -> Vec<GpuUsage> {
+    let device_count = nvml.device_count().unwrap_or_default();
+
+    (0..device_count)
+        .map(|index| {
+            let Ok(device) = nvml.device_by_index(index) else {
+                return GpuUsage::default();
+            };
+
+            let utilization = device
+                .utilization_rates()
+                .map(|rates| rates.gpu as f32 / 100.0)
+                .unwrap_or_default();
+            let memory = device
+                .memory_info()
+                .map(|memory| memory.used as f32 / memory.total as f32)
+                .unwrap_or_default();
+
+            GpuUsage {
+                utilization,
+                memory,
+            }
+        })
+        .collect()
+}
+
+fn detect_amd_devices() -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(DRM_PATH) else {
+        return vec![];
+    };
+
+    let mut devices: Vec<PathBuf> = entries
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            name.strip_prefix("card")
+                .is_some_and(|index| index.chars().all(|c| c.is_ascii_digit()))
+        })
+        .map(|entry| entry.path().join("device"))
+        .filter(|device| device.join("gpu_busy_percent").exists())
+        .collect();
+    devices.sort();
+
+    devices
+}
+
+fn sample_amd_device(device: &Path) -> Option<GpuUsage> {
+    let read_value = |name: &str| -> Option<f32> {
+        fs::read_to_string(device.join(name))
+            .ok()?
+            .trim()
+            .parse()
+            .ok()
+    };
+
+    Some(GpuUsage {
+        utilization: read_value("gpu_busy_percent")? / 100.0,
+        memory: read_value("mem_info_vram_used")? / read_value("mem_info_vram_total")?,
+    })
+}
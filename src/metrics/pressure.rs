@@ -0,0 +1,86 @@
+/// The parts of `Metric::MemoryPressure`, each from 0.0 to 1.0.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MemoryPressure {
+    pub ram: f32,
+    /// `None` without swap.
+    pub swap: Option<f32>,
+    /// The share of the last 10 seconds tasks stalled on memory, from Linux's pressure stall
+    /// information. `None` on other systems and kernels without it.
+    pub psi: Option<f32>,
+}
+
+impl MemoryPressure {
+    /// The weighted mean of the parts, leaving out the unavailable ones.
+    pub fn weighted(&self, ram_weight: f32, swap_weight: f32, psi_weight: f32) -> f32 {
+        let parts = [
+            Some((self.ram, ram_weight)),
+            self.swap.map(|swap| (swap, swap_weight)),
+            self.psi.map(|psi| (psi, psi_weight)),
+        ];
+
+        let (sum, total_weight) = parts
+            .into_iter()
+            .flatten()
+            .fold((0.0, 0.0), |(sum, total_weight), (part, weight)| {
+                (sum + part * weight, total_weight + weight)
+            });
+        if total_weight <= 0.0 {
+            return 0.0;
+        }
+
+        (sum / total_weight).clamp(0.0, 1.0)
+    }
+}
+
+/// The mean of the `some` and `full` 10 second averages in `/proc/pressure/memory`.
+#[cfg(target_os = "linux")]
+pub fn read_psi() -> Option<f32> {
+    parse_psi(&std::fs::read_to_string("/proc/pressure/memory").ok()?)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn read_psi() -> Option<f32> {
+    None
+}
+
+/// Parses lines like `some avg10=1.23 avg60=0.50 avg300=0.10 total=12345`.
+#[cfg(target_os = "linux")]
+fn parse_psi(pressure: &str) -> Option<f32> {
+    let avg10 = |kind: &str| -> Option<f32> {
+        pressure
+            .lines()
+            .find(|line| line.starts_with(kind))?
+            .split_whitespace()
+            .find_map(|field| field.strip_prefix("avg10="))?
+            .parse()
+            .ok()
+    };
+
+    let some = avg10("some ")?;
+    let full = avg10("full ")?;
+
+    Some(((some + full) / 2.0 / 100.0).clamp(0.0, 1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weighs_ram_and_swap() {
+        let pressure = MemoryPressure {
+            ram: 0.8,
+            swap: Some(0.2),
+            psi: None,
+        };
+
+        // The missing PSI doesn't count, whatever its weight.
+        assert!((pressure.weighted(3.0, 1.0, 5.0) - 0.65).abs() < 1e-6);
+
+        let without_swap = MemoryPressure {
+            swap: None,
+            ..pressure
+        };
+        assert!((without_swap.weighted(3.0, 1.0, 5.0) - 0.8).abs() < 1e-6);
+    }
+}
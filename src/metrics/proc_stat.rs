@@ -0,0 +1,90 @@
+use std::{fs, io};
+
+/// A snapshot of the aggregate CPU time in `/proc/stat`, readable without the permissions
+/// `cpu_monitor` may need.
+pub struct ProcStatCpuInstant {
+    idle: u64,
+    total: u64,
+}
+
+impl ProcStatCpuInstant {
+    pub fn now() -> io::Result<Self> {
+        let stat = fs::read_to_string("/proc/stat")?;
+
+        Self::parse(&stat)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no cpu line in /proc/stat"))
+    }
+
+    pub fn parse(stat: &str) -> Option<Self> {
+        let line = stat.lines().find(|line| line.starts_with("cpu "))?;
+        let times: Vec<u64> = line
+            .split_whitespace()
+            .skip(1)
+            .map(|time| time.parse().ok())
+            .collect::<Option<_>>()?;
+
+        // user nice system idle iowait irq softirq steal, then guest and guest_nice which are
+        // already part of user and nice.
+        let idle = times.get(3)? + times.get(4).copied().unwrap_or_default();
+        let total = times.iter().take(8).sum();
+
+        Some(Self { idle, total })
+    }
+
+    /// The fraction of the time since `start` the CPUs weren't idle, like
+    /// `cpu_monitor::CpuDuration::non_idle`.
+    pub fn non_idle_since(&self, start: &Self) -> f64 {
+        let total = self.total.saturating_sub(start.total);
+        if total == 0 {
+            return 0.0;
+        }
+
+        let idle = self.idle.saturating_sub(start.idle);
+        1.0 - idle as f64 / total as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: &str = "\
+cpu  4705 356 584 3699 23 23 0 0 0 0
+cpu0 1393 280 283 1052 8 5 0 0 0 0
+cpu1 3312 76 301 2647 15 18 0 0 0 0
+intr 114930548 113199788 3 0 5 263 0 4 [... lots more numbers ...]
+ctxt 1990473
+btime 1062191376
+";
+
+    const END: &str = "\
+cpu  4905 356 684 3799 123 23 10 0 0 0
+cpu0 1493 280 333 1102 58 5 5 0 0 0
+cpu1 3412 76 351 2697 65 18 5 0 0 0
+ctxt 1990999
+";
+
+    #[test]
+    fn parses_the_aggregate_line_with_iowait_as_idle() {
+        let start = ProcStatCpuInstant::parse(START).unwrap();
+
+        assert_eq!(start.idle, 3699 + 23);
+        assert_eq!(start.total, 4705 + 356 + 584 + 3699 + 23 + 23);
+    }
+
+    #[test]
+    fn non_idle_is_the_busy_share_of_the_time_between_snapshots() {
+        let start = ProcStatCpuInstant::parse(START).unwrap();
+        let end = ProcStatCpuInstant::parse(END).unwrap();
+
+        // 510 ticks passed, 200 of them idle or waiting for I/O.
+        assert!((end.non_idle_since(&start) - 310.0 / 510.0).abs() < 1e-9);
+        assert_eq!(start.non_idle_since(&start), 0.0);
+    }
+
+    #[test]
+    fn rejects_a_stat_without_an_aggregate_line() {
+        assert!(ProcStatCpuInstant::parse("cpu0 1 2 3 4\nctxt 5\n").is_none());
+        assert!(ProcStatCpuInstant::parse("cpu  1 2 x 4\n").is_none());
+    }
+}
@@ -0,0 +1,40 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// How temperatures are reported. The metrics are always configured in Celsius.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum TemperatureUnit {
+    #[default]
+    Celsius,
+    Fahrenheit,
+}
+
+impl TemperatureUnit {
+    pub fn format(self, celsius: f32) -> String {
+        match self {
+            TemperatureUnit::Celsius => format!("{celsius:.1} °C"),
+            TemperatureUnit::Fahrenheit => format!("{:.1} °F", celsius_to_fahrenheit(celsius)),
+        }
+    }
+
+    /// A reading in Celsius in this unit.
+    pub fn convert(self, celsius: f32) -> f32 {
+        match self {
+            TemperatureUnit::Celsius => celsius,
+            TemperatureUnit::Fahrenheit => celsius_to_fahrenheit(celsius),
+        }
+    }
+
+    /// As in the config, e.g. for tagging exported readings.
+    pub fn name(self) -> &'static str {
+        match self {
+            TemperatureUnit::Celsius => "celsius",
+            TemperatureUnit::Fahrenheit => "fahrenheit",
+        }
+    }
+}
+
+pub fn celsius_to_fahrenheit(celsius: f32) -> f32 {
+    celsius * 9.0 / 5.0 + 32.0
+}
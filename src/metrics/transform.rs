@@ -0,0 +1,157 @@
+use super::{smoothing::Smoother, Metric};
+use ringbuffer::{AllocRingBuffer, RingBuffer};
+
+/// The state of a metric derived from another one, updated once per sample tick.
+pub enum Transform {
+    Percentile(PercentileNormalizer),
+    Hysteresis(HysteresisThreshold),
+    Smoothed(Smoother),
+    Delta(RateOfChange),
+}
+
+impl Transform {
+    pub fn for_metric(metric: &Metric) -> Option<Self> {
+        match metric {
+            Metric::Percentile { window, .. } => {
+                Some(Transform::Percentile(PercentileNormalizer::new(*window)))
+            }
+            Metric::Hysteresis { high, low, .. } => {
+                Some(Transform::Hysteresis(HysteresisThreshold::new(*high, *low)))
+            }
+            Metric::Smoothed { smoothing, .. } => Some(Transform::Smoothed(smoothing.smoother())),
+            Metric::Delta { normalize_by, .. } => {
+                Some(Transform::Delta(RateOfChange::new(*normalize_by, false)))
+            }
+            Metric::AbsDelta { normalize_by, .. } => {
+                Some(Transform::Delta(RateOfChange::new(*normalize_by, true)))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn apply(&mut self, value: f32) -> f32 {
+        match self {
+            Transform::Percentile(normalizer) => normalizer.normalize(value),
+            Transform::Hysteresis(threshold) => {
+                if threshold.update(value) {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Transform::Smoothed(smoother) => smoother.push(value),
+            Transform::Delta(rate_of_change) => rate_of_change.update(value),
+        }
+    }
+}
+
+/// Maps a value to its percentile among the recent samples, so the full color range is used
+/// whatever the machine's typical load is.
+pub struct PercentileNormalizer {
+    samples: AllocRingBuffer<f32>,
+}
+
+impl PercentileNormalizer {
+    pub fn new(window: usize) -> Self {
+        Self {
+            samples: AllocRingBuffer::new(window.max(1)),
+        }
+    }
+
+    pub fn normalize(&mut self, value: f32) -> f32 {
+        self.samples.push(value);
+        if self.samples.len() < 2 {
+            return 0.5;
+        }
+
+        let below = self
+            .samples
+            .iter()
+            .filter(|&&sample| sample < value)
+            .count();
+        let equal = self
+            .samples
+            .iter()
+            .filter(|&&sample| sample == value)
+            .count();
+
+        // Mid-rank, so a window of identical samples sits in the middle of the range.
+        (below as f32 + equal.saturating_sub(1) as f32 / 2.0) / (self.samples.len() - 1) as f32
+    }
+}
+
+/// A threshold that doesn't flicker when the value hovers around it: it is entered above `high`
+/// and only left below `low`.
+pub struct HysteresisThreshold {
+    high: f32,
+    low: f32,
+    active: bool,
+}
+
+impl HysteresisThreshold {
+    pub fn new(high: f32, low: f32) -> Self {
+        Self {
+            high,
+            low,
+            active: false,
+        }
+    }
+
+    pub fn update(&mut self, value: f32) -> bool {
+        if value > self.high {
+            self.active = true;
+        } else if value < self.low {
+            self.active = false;
+        }
+
+        self.active
+    }
+}
+
+/// The change between two consecutive samples, scaled and clamped.
+pub struct RateOfChange {
+    normalize_by: f32,
+    absolute: bool,
+    previous: Option<f32>,
+}
+
+impl RateOfChange {
+    pub fn new(normalize_by: f32, absolute: bool) -> Self {
+        Self {
+            normalize_by,
+            absolute,
+            previous: None,
+        }
+    }
+
+    pub fn update(&mut self, value: f32) -> f32 {
+        // No change on the first sample rather than a jump from nothing.
+        let previous = self.previous.replace(value).unwrap_or(value);
+        let delta = ((value - previous) / self.normalize_by).clamp(-1.0, 1.0);
+
+        if self.absolute {
+            delta.abs()
+        } else {
+            delta
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_of_change_is_the_scaled_difference_to_the_previous_sample() {
+        let mut delta = RateOfChange::new(0.5, false);
+        assert_eq!(delta.update(0.25), 0.0);
+        assert_eq!(delta.update(0.5), 0.5);
+        assert_eq!(delta.update(0.375), -0.25);
+        // Clamped.
+        assert_eq!(delta.update(1.0), 1.0);
+
+        let mut abs_delta = RateOfChange::new(0.5, true);
+        abs_delta.update(0.5);
+        assert_eq!(abs_delta.update(0.375), 0.25);
+    }
+}
@@ -0,0 +1,80 @@
+use ringbuffer::{AllocRingBuffer, RingBuffer};
+use serde::{Deserialize, Serialize};
+
+/// Which statistic of the recent samples a `cpu_usage_window` metric reads.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowStatistic {
+    #[default]
+    Mean,
+    Max,
+    Min,
+    Latest,
+}
+
+/// The statistics of a `SampleWindow` at one sample tick.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct WindowValues {
+    pub mean: f32,
+    pub max: f32,
+    pub min: f32,
+    pub latest: f32,
+}
+
+impl WindowValues {
+    pub fn get(&self, statistic: WindowStatistic) -> f32 {
+        match statistic {
+            WindowStatistic::Mean => self.mean,
+            WindowStatistic::Max => self.max,
+            WindowStatistic::Min => self.min,
+            WindowStatistic::Latest => self.latest,
+        }
+    }
+}
+
+/// The last raw samples of a metric, so a smoothed value and the recent peak can be shown from
+/// the same window.
+pub struct SampleWindow {
+    samples: AllocRingBuffer<f32>,
+}
+
+impl SampleWindow {
+    pub fn new(window: usize) -> Self {
+        Self {
+            samples: AllocRingBuffer::new(window.max(1)),
+        }
+    }
+
+    pub fn push(&mut self, sample: f32) {
+        self.samples.push(sample);
+    }
+
+    pub fn mean(&self) -> f32 {
+        self.samples.iter().sum::<f32>() / self.samples.len().max(1) as f32
+    }
+
+    pub fn max(&self) -> f32 {
+        self.samples.iter().copied().fold(0.0, f32::max)
+    }
+
+    pub fn min(&self) -> f32 {
+        self.samples
+            .iter()
+            .copied()
+            .reduce(f32::min)
+            .unwrap_or_default()
+    }
+
+    pub fn latest(&self) -> f32 {
+        self.samples.back().copied().unwrap_or_default()
+    }
+
+    pub fn values(&self) -> WindowValues {
+        WindowValues {
+            mean: self.mean(),
+            max: self.max(),
+            min: self.min(),
+            latest: self.latest(),
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use openrgb::data::Color;
+use std::collections::{HashMap, HashSet};
+use tracing::warn;
+
+struct RenderedColors {
+    zone_led_counts: Vec<usize>,
+    colors: Vec<Color>,
+}
+
+/// The colors each controller was last rendered with, for `mirror_of` effects to copy.
+#[derive(Default)]
+pub struct Mirrors {
+    rendered: HashMap<String, RenderedColors>,
+    /// Mirrors already warned about having a different LED count than their source.
+    mismatched: HashSet<String>,
+}
+
+impl Mirrors {
+    pub fn record(&mut self, controller: &str, zone_led_counts: &[usize], colors: &[Color]) {
+        self.rendered.insert(
+            controller.to_string(),
+            RenderedColors {
+                zone_led_counts: zone_led_counts.to_vec(),
+                colors: colors.to_vec(),
+            },
+        );
+    }
+
+    /// The colors of `source` for `mirror`, or `None` until the source has been rendered or
+    /// while their LED counts differ.
+    pub fn colors(
+        &mut self,
+        mirror: &str,
+        source: &str,
+        led_count: usize,
+        horizontal_flip: bool,
+        vertical_flip: bool,
+    ) -> Option<Vec<Color>> {
+        let rendered = self.rendered.get(source)?;
+        if rendered.colors.len() != led_count {
+            if self.mismatched.insert(mirror.to_string()) {
+                warn!(
+                    "{mirror} has {led_count} LEDs but its mirror source {source} has {}, leaving it alone",
+                    rendered.colors.len()
+                );
+            }
+            return None;
+        }
+        self.mismatched.remove(mirror);
+
+        // The zones are the rows of the device, when they add up to its LEDs.
+        let mut rows: Vec<Vec<Color>> =
+            if rendered.zone_led_counts.iter().sum::<usize>() == led_count {
+                let mut start = 0;
+                rendered
+                    .zone_led_counts
+                    .iter()
+                    .map(|&zone_led_count| {
+                        start += zone_led_count;
+                        rendered.colors[start - zone_led_count..start].to_vec()
+                    })
+                    .collect()
+            } else {
+                vec![rendered.colors.clone()]
+            };
+
+        if vertical_flip {
+            rows.reverse();
+        }
+        if horizontal_flip {
+            rows.iter_mut().for_each(|row| row.reverse());
+        }
+
+        Some(rows.concat())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn horizontal_flip_reverses_the_source() {
+        let source: Vec<Color> = (1..=4).map(|red| Color::new(red, 0, 0)).collect();
+        let mut mirrors = Mirrors::default();
+        mirrors.record("Front", &[4], &source);
+
+        let mut reversed = source.clone();
+        reversed.reverse();
+        assert_eq!(
+            mirrors.colors("Back", "Front", 4, true, false),
+            Some(reversed)
+        );
+        assert_eq!(
+            mirrors.colors("Back", "Front", 4, false, false),
+            Some(source)
+        );
+        // Not until the counts match.
+        assert_eq!(mirrors.colors("Back", "Front", 5, true, false), None);
+    }
+}
@@ -0,0 +1,241 @@
+//! A fake OpenRGB server for tests of the code that talks the protocol itself. It answers the
+//! protocol version, controller count and controller data requests and records every packet it
+//! receives.
+
+use std::{
+    io,
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    io::{self as tokio_io, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, DuplexStream},
+    net::TcpListener,
+    task::JoinHandle,
+};
+
+pub const REQUEST_CONTROLLER_COUNT: u32 = 0;
+pub const REQUEST_CONTROLLER_DATA: u32 = 1;
+pub const REQUEST_PROTOCOL_VERSION: u32 = 40;
+pub const SET_CLIENT_NAME: u32 = 50;
+pub const RESIZE_ZONE: u32 = 1000;
+pub const UPDATE_LEDS: u32 = 1050;
+
+/// Every controller has a single zone of this many LEDs, which can be resized to
+/// `ZONE_LEDS_MIN..=ZONE_LEDS_MAX`.
+pub const ZONE_LED_COUNT: u32 = 10;
+pub const ZONE_LEDS_MIN: u32 = 1;
+pub const ZONE_LEDS_MAX: u32 = 60;
+
+/// A packet the server received.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Packet {
+    /// Which of the accepted connections it came over, counting from 0.
+    pub connection: usize,
+    pub packet_id: u32,
+    pub data: Vec<u8>,
+}
+
+pub struct MockServer {
+    address: String,
+    protocol_version: u32,
+    controller_count: u32,
+    packets: Arc<Mutex<Vec<Packet>>>,
+    connections: Arc<Mutex<Vec<JoinHandle<()>>>>,
+    accept: JoinHandle<()>,
+}
+
+impl MockServer {
+    /// Listens on a free local port, negotiating up to `protocol_version` and reporting
+    /// `controller_count` controllers, named "Controller 0" and so on.
+    pub async fn start(protocol_version: u32, controller_count: u32) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let packets = Arc::new(Mutex::new(Vec::new()));
+        let connections = Arc::new(Mutex::new(Vec::new()));
+
+        let accept = tokio::spawn({
+            let packets = packets.clone();
+            let connections = connections.clone();
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    spawn_serving(
+                        stream,
+                        protocol_version,
+                        controller_count,
+                        &packets,
+                        &connections,
+                    );
+                }
+            }
+        });
+
+        Self {
+            address,
+            protocol_version,
+            controller_count,
+            packets,
+            connections,
+            accept,
+        }
+    }
+
+    /// `host:port`.
+    pub fn address(&self) -> String {
+        self.address.clone()
+    }
+
+    /// The packets received so far, in order.
+    pub fn packets(&self) -> Vec<Packet> {
+        self.packets.lock().unwrap().clone()
+    }
+
+    /// A connection that doesn't go over the network, the other end of a pipe the server serves.
+    pub fn connect_in_memory(&self) -> DuplexStream {
+        let (client, server) = tokio_io::duplex(64 * 1024);
+        spawn_serving(
+            server,
+            self.protocol_version,
+            self.controller_count,
+            &self.packets,
+            &self.connections,
+        );
+
+        client
+    }
+
+    /// How many connections were accepted.
+    pub fn connection_count(&self) -> usize {
+        self.connections.lock().unwrap().len()
+    }
+
+    /// Closes every connection and stops accepting new ones, like a server that went away.
+    pub fn stop(&self) {
+        self.accept.abort();
+        for connection in self.connections.lock().unwrap().iter() {
+            connection.abort();
+        }
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Serves `stream` as the next connection.
+fn spawn_serving(
+    stream: impl AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    protocol_version: u32,
+    controller_count: u32,
+    packets: &Arc<Mutex<Vec<Packet>>>,
+    connections: &Mutex<Vec<JoinHandle<()>>>,
+) {
+    let mut connections = connections.lock().unwrap();
+    let served = serve(
+        stream,
+        connections.len(),
+        protocol_version,
+        controller_count,
+        packets.clone(),
+    );
+    connections.push(tokio::spawn(async move {
+        let _ = served.await;
+    }));
+}
+
+async fn serve(
+    mut stream: impl AsyncRead + AsyncWrite + Unpin,
+    connection: usize,
+    protocol_version: u32,
+    controller_count: u32,
+    packets: Arc<Mutex<Vec<Packet>>>,
+) -> io::Result<()> {
+    let mut negotiated = 0;
+    loop {
+        let mut header = [0; 16];
+        stream.read_exact(&mut header).await?;
+        assert_eq!(&header[..4], b"ORGB");
+        let field =
+            |index: usize| u32::from_le_bytes(header[4 * index..4 * index + 4].try_into().unwrap());
+        let (device_id, packet_id, length) = (field(1), field(2), field(3));
+        let mut data = vec![0; length as usize];
+        stream.read_exact(&mut data).await?;
+
+        let reply = match packet_id {
+            REQUEST_PROTOCOL_VERSION => {
+                let requested = u32::from_le_bytes(data[..4].try_into().unwrap());
+                negotiated = requested.min(protocol_version);
+                Some(negotiated.to_le_bytes().to_vec())
+            }
+            REQUEST_CONTROLLER_COUNT => Some(controller_count.to_le_bytes().to_vec()),
+            REQUEST_CONTROLLER_DATA => Some(controller_data(device_id, negotiated)),
+            _ => None,
+        };
+        packets.lock().unwrap().push(Packet {
+            connection,
+            packet_id,
+            data,
+        });
+
+        if let Some(reply) = reply {
+            let mut response = Vec::with_capacity(16 + reply.len());
+            response.extend_from_slice(b"ORGB");
+            response.extend_from_slice(&device_id.to_le_bytes());
+            response.extend_from_slice(&packet_id.to_le_bytes());
+            response.extend_from_slice(&(reply.len() as u32).to_le_bytes());
+            response.extend_from_slice(&reply);
+            stream.write_all(&response).await?;
+        }
+    }
+}
+
+/// The description of controller `id` as the server sends it in `protocol_version`: no modes and
+/// one linear zone of `ZONE_LED_COUNT` black LEDs.
+fn controller_data(id: u32, protocol_version: u32) -> Vec<u8> {
+    fn string(data: &mut Vec<u8>, text: &str) {
+        // Counting the terminating NUL.
+        data.extend_from_slice(&(text.len() as u16 + 1).to_le_bytes());
+        data.extend_from_slice(text.as_bytes());
+        data.push(0);
+    }
+
+    let mut data = Vec::new();
+    // Device type, 0 is a motherboard.
+    data.extend_from_slice(&0i32.to_le_bytes());
+    string(&mut data, &format!("Controller {id}"));
+    if protocol_version >= 1 {
+        string(&mut data, "Vendor");
+    }
+    for text in ["Description", "Version", "Serial", "Location"] {
+        string(&mut data, text);
+    }
+
+    // No modes, and the active one.
+    data.extend_from_slice(&0u16.to_le_bytes());
+    data.extend_from_slice(&0i32.to_le_bytes());
+
+    data.extend_from_slice(&1u16.to_le_bytes());
+    string(&mut data, "Strip");
+    // Zone type, 1 is linear.
+    data.extend_from_slice(&1i32.to_le_bytes());
+    for value in [ZONE_LEDS_MIN, ZONE_LEDS_MAX, ZONE_LED_COUNT] {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    // No matrix map.
+    data.extend_from_slice(&0u16.to_le_bytes());
+
+    data.extend_from_slice(&(ZONE_LED_COUNT as u16).to_le_bytes());
+    for led in 0..ZONE_LED_COUNT {
+        string(&mut data, &format!("LED {led}"));
+        data.extend_from_slice(&led.to_le_bytes());
+    }
+    data.extend_from_slice(&(ZONE_LED_COUNT as u16).to_le_bytes());
+    for _ in 0..ZONE_LED_COUNT {
+        data.extend_from_slice(&[0; 4]);
+    }
+
+    // Prefixed with its size, counting the size itself.
+    let mut sized = (data.len() as u32 + 4).to_le_bytes().to_vec();
+    sized.extend_from_slice(&data);
+    sized
+}
@@ -0,0 +1,300 @@
+use crate::{
+    config::{Config, Dimming},
+    pool::Stream,
+};
+use openrgb::{data::Mode, OpenRGB, OpenRGBError};
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+};
+use tracing::{info, warn};
+
+/// The modes of a controller, as far as switching between them is concerned.
+#[derive(Debug, Clone)]
+pub struct ControllerModes<M> {
+    pub name: String,
+    pub active_mode: i32,
+    /// Each mode with its name.
+    pub modes: Vec<(String, M)>,
+    pub led_count: usize,
+}
+
+/// Where modes are switched: the OpenRGB server, or a stand-in for it.
+pub trait ModeSwitch {
+    type Mode: Clone + Send;
+
+    fn get_modes(
+        &self,
+        controller_id: u32,
+    ) -> impl Future<Output = Result<ControllerModes<Self::Mode>, OpenRGBError>> + Send;
+
+    fn update_mode(
+        &self,
+        controller_id: u32,
+        mode_index: i32,
+        mode: Self::Mode,
+    ) -> impl Future<Output = Result<(), OpenRGBError>> + Send;
+}
+
+impl ModeSwitch for OpenRGB<Stream> {
+    type Mode = Mode;
+
+    async fn get_modes(&self, controller_id: u32) -> Result<ControllerModes<Mode>, OpenRGBError> {
+        let controller = self.get_controller(controller_id).await?;
+
+        Ok(ControllerModes {
+            led_count: controller.leds.len(),
+            modes: controller
+                .modes
+                .into_iter()
+                .map(|mode| (mode.name.clone(), mode))
+                .collect(),
+            active_mode: controller.active_mode,
+            name: controller.name,
+        })
+    }
+
+    async fn update_mode(
+        &self,
+        controller_id: u32,
+        mode_index: i32,
+        mode: Mode,
+    ) -> Result<(), OpenRGBError> {
+        OpenRGB::update_mode(self, controller_id, mode_index, mode).await
+    }
+}
+
+/// The mode each controller was in before the client touched it, so the devices can go back to
+/// their hardware lighting on exit.
+pub struct OriginalModes {
+    modes: HashMap<u32, i32>,
+}
+
+impl OriginalModes {
+    pub async fn capture(client: &OpenRGB<Stream>) -> Result<Self, OpenRGBError> {
+        let mut modes = HashMap::new();
+
+        let controller_count = client.get_controller_count().await?;
+        for controller_id in 0..controller_count {
+            let controller = client.get_controller(controller_id).await?;
+            if !controller.modes.is_empty() {
+                modes.insert(controller_id, controller.active_mode);
+            }
+        }
+
+        Ok(Self { modes })
+    }
+
+    /// Switches back every controller whose mode changed. Failures are logged and skipped, so
+    /// one device doesn't keep the others from being restored.
+    pub async fn restore(&self, client: &OpenRGB<Stream>) {
+        for (&controller_id, &mode_index) in &self.modes {
+            if let Err(e) = restore_mode(client, controller_id, mode_index).await {
+                warn!("Failed to restore the mode of controller {controller_id}: {e}");
+            }
+        }
+    }
+}
+
+/// Switches a controller to its "Direct" mode, the one arbitrary colors can be sent in, unless it
+/// is in it already. Returns its LED count in that mode, some devices report none before.
+pub async fn ensure_direct_mode(
+    client: &impl ModeSwitch,
+    controller_id: u32,
+) -> Result<usize, OpenRGBError> {
+    let controller = client.get_modes(controller_id).await?;
+
+    let Some(mode_index) = controller
+        .modes
+        .iter()
+        .position(|(name, _)| name.eq_ignore_ascii_case("Direct"))
+    else {
+        warn!("Controller {} has no Direct mode", controller.name);
+        return Ok(controller.led_count);
+    };
+    if controller.active_mode == mode_index as i32 {
+        return Ok(controller.led_count);
+    }
+
+    info!("Switching {} to Direct mode", controller.name);
+    client
+        .update_mode(
+            controller_id,
+            mode_index as i32,
+            controller.modes[mode_index].1.clone(),
+        )
+        .await?;
+
+    let led_count = client.get_modes(controller_id).await?.led_count;
+    if controller.led_count == 0 && led_count > 0 {
+        info!("{} has {led_count} LEDs in Direct mode", controller.name);
+    }
+
+    Ok(led_count)
+}
+
+/// `ensure_direct_mode` for every controller. Failures are logged and skipped. Returns the
+/// controllers that still have no LEDs.
+pub async fn ensure_direct_modes(client: &OpenRGB<Stream>) -> Result<Vec<u32>, OpenRGBError> {
+    let mut empty_controllers = Vec::new();
+
+    let controller_count = client.get_controller_count().await?;
+    for controller_id in 0..controller_count {
+        match ensure_direct_mode(client, controller_id).await {
+            Ok(0) => empty_controllers.push(controller_id),
+            Ok(_) => {}
+            Err(e) => warn!("Failed to switch controller {controller_id} to Direct mode: {e}"),
+        }
+    }
+
+    Ok(empty_controllers)
+}
+
+/// Sets the brightness of the active mode of every controller configured with a `brightness` and
+/// `dimming = "mode_brightness"`, where the mode has one. Returns those controllers, the others
+/// have to be dimmed by scaling their colors.
+pub async fn apply_mode_brightness(
+    client: &OpenRGB<Stream>,
+    config: &Config,
+) -> Result<HashSet<u32>, OpenRGBError> {
+    let mut dimmed = HashSet::new();
+
+    let controller_count = client.get_controller_count().await?;
+    for controller_id in 0..controller_count {
+        let controller = client.get_controller(controller_id).await?;
+        let Some(brightness) = config
+            .controllers
+            .iter()
+            .chain(
+                config
+                    .profiles
+                    .values()
+                    .flat_map(|profile| &profile.controllers),
+            )
+            .filter(|mapping| mapping.name == controller.name)
+            .find(|mapping| mapping.dimming == Dimming::ModeBrightness)
+            .and_then(|mapping| mapping.brightness)
+        else {
+            continue;
+        };
+
+        let Some(mut mode) = usize::try_from(controller.active_mode)
+            .ok()
+            .and_then(|index| controller.modes.get(index))
+            .cloned()
+        else {
+            continue;
+        };
+        let (Some(min), Some(max), Some(_)) =
+            (mode.brightness_min, mode.brightness_max, mode.brightness)
+        else {
+            info!(
+                "{} has no mode brightness, scaling its colors instead",
+                controller.name
+            );
+            continue;
+        };
+
+        let level = min as f32 + (max as f32 - min as f32) * brightness.clamp(0.0, 1.0);
+        mode.brightness = Some(level.round() as u32);
+        client
+            .update_mode(controller_id, controller.active_mode, mode)
+            .await?;
+        dimmed.insert(controller_id);
+    }
+
+    Ok(dimmed)
+}
+
+async fn restore_mode(
+    client: &OpenRGB<Stream>,
+    controller_id: u32,
+    mode_index: i32,
+) -> Result<(), OpenRGBError> {
+    let controller = client.get_controller(controller_id).await?;
+    if controller.active_mode == mode_index {
+        return Ok(());
+    }
+
+    let Some(mode) = usize::try_from(mode_index)
+        .ok()
+        .and_then(|index| controller.modes.get(index))
+    else {
+        warn!("Controller {} has no mode {mode_index}", controller.name);
+        return Ok(());
+    };
+
+    info!("Restoring mode {} of {}", mode.name, controller.name);
+    client
+        .update_mode(controller_id, mode_index, mode.clone())
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// One controller with a "Static" and a "Direct" mode, which remembers the modes set.
+    struct TwoModes {
+        active_mode: Mutex<i32>,
+        updates: Mutex<Vec<i32>>,
+    }
+
+    impl TwoModes {
+        fn new(active_mode: i32) -> Self {
+            Self {
+                active_mode: Mutex::new(active_mode),
+                updates: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    impl ModeSwitch for TwoModes {
+        type Mode = ();
+
+        async fn get_modes(
+            &self,
+            _controller_id: u32,
+        ) -> Result<ControllerModes<()>, OpenRGBError> {
+            Ok(ControllerModes {
+                name: "Strip".to_string(),
+                active_mode: *self.active_mode.lock().unwrap(),
+                modes: vec![("Static".to_string(), ()), ("Direct".to_string(), ())],
+                led_count: 8,
+            })
+        }
+
+        async fn update_mode(
+            &self,
+            _controller_id: u32,
+            mode_index: i32,
+            _mode: (),
+        ) -> Result<(), OpenRGBError> {
+            *self.active_mode.lock().unwrap() = mode_index;
+            self.updates.lock().unwrap().push(mode_index);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn switches_to_direct_mode_only_when_not_in_it() {
+        let controller = TwoModes::new(0);
+
+        assert_eq!(ensure_direct_mode(&controller, 0).await.unwrap(), 8);
+        assert_eq!(*controller.updates.lock().unwrap(), [1]);
+
+        // In Direct mode now, so it's left alone.
+        assert_eq!(ensure_direct_mode(&controller, 0).await.unwrap(), 8);
+        assert_eq!(*controller.updates.lock().unwrap(), [1]);
+    }
+
+    #[tokio::test]
+    async fn leaves_a_controller_in_direct_mode_alone() {
+        let controller = TwoModes::new(1);
+
+        ensure_direct_mode(&controller, 0).await.unwrap();
+
+        assert!(controller.updates.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,209 @@
+//! Extracts the colors that appeared most often in the color history, to use as a theme.
+
+use crate::color::ColorStop;
+use crate::color_history::read_record;
+use openrgb::data::Color;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::{self, File},
+    io::{self, BufReader, Read},
+    path::Path,
+};
+
+const MAX_ITERATIONS: usize = 50;
+
+pub struct ColorPaletteExtractor;
+
+impl ColorPaletteExtractor {
+    /// The `n_colors` most characteristic colors of a color history log, most frequent first.
+    pub fn from_history(history: &mut impl Read, n_colors: usize) -> io::Result<Vec<Color>> {
+        // Clustering distinct colors weighted by their count keeps long histories cheap.
+        let mut counts: HashMap<[u8; 3], u64> = HashMap::new();
+        while let Some(record) = read_record(history)? {
+            for color in record.colors {
+                *counts.entry([color.r, color.g, color.b]).or_default() += 1;
+            }
+        }
+
+        let samples: Vec<([f32; 3], f32)> = counts
+            .into_iter()
+            .map(|(rgb, count)| (rgb.map(f32::from), count as f32))
+            .collect();
+
+        Ok(Self::from_samples(&samples, n_colors))
+    }
+
+    /// Weighted k-means over `(rgb, weight)` samples.
+    fn from_samples(samples: &[([f32; 3], f32)], n_colors: usize) -> Vec<Color> {
+        if samples.is_empty() || n_colors == 0 {
+            return Vec::new();
+        }
+
+        let mut centers = initial_centers(samples, n_colors.min(samples.len()));
+        let mut weights = vec![0.0; centers.len()];
+
+        for _ in 0..MAX_ITERATIONS {
+            let mut sums = vec![[0.0; 3]; centers.len()];
+            weights.iter_mut().for_each(|weight| *weight = 0.0);
+
+            for (rgb, weight) in samples {
+                let nearest = nearest_center(&centers, rgb);
+                for channel in 0..3 {
+                    sums[nearest][channel] += rgb[channel] * weight;
+                }
+                weights[nearest] += weight;
+            }
+
+            let mut moved = false;
+            for (center, (sum, weight)) in centers.iter_mut().zip(sums.iter().zip(&weights)) {
+                if *weight == 0.0 {
+                    continue;
+                }
+                let mean = sum.map(|channel| channel / weight);
+                moved |= distance(center, &mean) > 0.25;
+                *center = mean;
+            }
+
+            if !moved {
+                break;
+            }
+        }
+
+        let mut clusters: Vec<_> = centers.into_iter().zip(weights).collect();
+        clusters.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        clusters
+            .into_iter()
+            .map(|(rgb, _)| {
+                let [r, g, b] = rgb.map(|channel| channel.round().clamp(0.0, 255.0) as u8);
+                Color::new(r, g, b)
+            })
+            .collect()
+    }
+}
+
+/// Starts from the most frequent color, then repeatedly adds the one farthest from every center
+/// so far, which keeps the result deterministic.
+fn initial_centers(samples: &[([f32; 3], f32)], n_colors: usize) -> Vec<[f32; 3]> {
+    let first = samples
+        .iter()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(rgb, _)| *rgb)
+        .unwrap();
+
+    let mut centers = vec![first];
+    while centers.len() < n_colors {
+        let farthest = samples
+            .iter()
+            .map(|(rgb, _)| {
+                let nearest = centers
+                    .iter()
+                    .map(|center| distance(center, rgb))
+                    .fold(f32::INFINITY, f32::min);
+                (rgb, nearest)
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(rgb, _)| *rgb)
+            .unwrap();
+        centers.push(farthest);
+    }
+
+    centers
+}
+
+fn nearest_center(centers: &[[f32; 3]], rgb: &[f32; 3]) -> usize {
+    centers
+        .iter()
+        .enumerate()
+        .min_by(|a, b| distance(a.1, rgb).total_cmp(&distance(b.1, rgb)))
+        .map(|(index, _)| index)
+        .unwrap_or_default()
+}
+
+fn distance(a: &[f32; 3], b: &[f32; 3]) -> f32 {
+    (0..3)
+        .map(|channel| (a[channel] - b[channel]).powi(2))
+        .sum()
+}
+
+#[derive(Serialize)]
+struct PaletteFile {
+    palette: Palette,
+}
+
+#[derive(Serialize)]
+struct Palette {
+    stops: Vec<ColorStop>,
+}
+
+/// Extracts a palette from the color history at `history_path` and writes it to `output_path` as a
+/// `[palette]` section whose `stops` can be used wherever the config takes a palette.
+pub fn extract_to_file(
+    history_path: &Path,
+    n_colors: usize,
+    output_path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let mut reader = BufReader::new(File::open(history_path)?);
+    let colors = ColorPaletteExtractor::from_history(&mut reader, n_colors)?;
+    if colors.is_empty() {
+        return Err(format!("no colors recorded in {}", history_path.display()).into());
+    }
+
+    let last = (colors.len() - 1).max(1) as f32;
+    let stops = colors
+        .into_iter()
+        .enumerate()
+        .map(|(index, color)| ColorStop {
+            position: index as f32 / last,
+            color,
+        })
+        .collect();
+
+    let file = PaletteFile {
+        palette: Palette { stops },
+    };
+    fs::write(output_path, toml::to_string(&file)?)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_three_obvious_clusters() {
+        let clusters = [
+            ([220.0, 20.0, 20.0], 50),
+            ([20.0, 200.0, 40.0], 30),
+            ([30.0, 30.0, 180.0], 20),
+        ];
+        // Scattered a few steps around each center.
+        let samples: Vec<([f32; 3], f32)> = clusters
+            .iter()
+            .flat_map(|(center, count)| {
+                (0..*count).map(move |index| {
+                    let offset = (index % 7) as f32 - 3.0;
+                    (
+                        [center[0] + offset, center[1] - offset, center[2] + offset],
+                        1.0,
+                    )
+                })
+            })
+            .collect();
+        assert_eq!(samples.len(), 100);
+
+        let colors = ColorPaletteExtractor::from_samples(&samples, 3);
+
+        // Most frequent first.
+        assert_eq!(colors.len(), 3);
+        for (color, (center, _)) in colors.iter().zip(&clusters) {
+            let channels = [color.r, color.g, color.b];
+            for (channel, expected) in channels.iter().zip(center) {
+                assert!((*channel as f32 - expected).abs() <= 2.0, "{color:?}");
+            }
+        }
+    }
+}
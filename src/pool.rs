@@ -0,0 +1,241 @@
+use openrgb::{OpenRGB, OpenRGBError};
+use std::{error::Error, future::Future, io, ops::Deref, pin::Pin, sync::Mutex};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpStream,
+};
+
+/// A connected stream the OpenRGB protocol can run over, e.g. TCP, TLS or a tunnel.
+pub trait Transport: AsyncRead + AsyncWrite + Unpin + Send + Sync {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send + Sync> Transport for T {}
+
+/// The stream of a pooled connection, whatever the `Connector` opened.
+pub type Stream = Box<dyn Transport>;
+
+/// Opens the stream to a server that the OpenRGB protocol then runs over. A custom one can reach
+/// the server some other way, e.g. through a proxy or a tunnel it sets up itself.
+pub trait Connector: Send + Sync {
+    fn open<'a>(
+        &'a self,
+        address: &'a str,
+    ) -> Pin<Box<dyn Future<Output = io::Result<Stream>> + Send + 'a>>;
+}
+
+/// Connects straight to `host:port`, which covers SSH local forwards too.
+pub struct TcpConnector;
+
+impl Connector for TcpConnector {
+    fn open<'a>(
+        &'a self,
+        address: &'a str,
+    ) -> Pin<Box<dyn Future<Output = io::Result<Stream>> + Send + 'a>> {
+        Box::pin(async move {
+            let stream = TcpStream::connect(address).await?;
+            Ok(Box::new(stream) as Stream)
+        })
+    }
+}
+
+/// Runs the OpenRGB handshake over an open `stream`, then names the client `client_name`.
+pub async fn handshake<S: Transport>(
+    stream: S,
+    client_name: &str,
+) -> Result<OpenRGB<S>, OpenRGBError> {
+    let connection = OpenRGB::new(stream).await?;
+    // Before anything else, so the server shows the name from the start.
+    connection.set_name(client_name).await?;
+
+    Ok(connection)
+}
+
+/// Connections to one or more OpenRGB servers, kept across reconnects so only a connection that
+/// failed is replaced and the others keep their state.
+pub struct ConnectionPool {
+    servers: Vec<Server>,
+    client_name: String,
+    connector: Box<dyn Connector>,
+}
+
+struct Server {
+    address: String,
+    connection: Mutex<Option<OpenRGB<Stream>>>,
+}
+
+impl ConnectionPool {
+    /// A pool for the servers at `addresses` (`host:port`), connected on first use. The servers
+    /// list the connections under `client_name`.
+    pub fn new(addresses: impl IntoIterator<Item = String>, client_name: &str) -> Self {
+        Self {
+            servers: addresses
+                .into_iter()
+                .map(|address| Server {
+                    address,
+                    connection: Mutex::new(None),
+                })
+                .collect(),
+            client_name: client_name.to_string(),
+            connector: Box::new(TcpConnector),
+        }
+    }
+
+    /// Opens the streams to the servers with `connector` instead of over TCP.
+    pub fn with_connector(mut self, connector: impl Connector + 'static) -> Self {
+        self.connector = Box::new(connector);
+        self
+    }
+
+    /// Connects to every server that has no connection yet.
+    pub async fn connect_all(&self) -> Result<(), Box<dyn Error>> {
+        for index in 0..self.servers.len() {
+            self.acquire(index).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Hands out the connection to the server at `index`, connecting first if there is none.
+    pub async fn acquire(&self, index: usize) -> Result<PooledConnection<'_>, Box<dyn Error>> {
+        let server = &self.servers[index];
+
+        let pooled = server.connection.lock().unwrap().take();
+        let connection = match pooled {
+            Some(connection) => connection,
+            None => self.open(index).await?,
+        };
+
+        Ok(PooledConnection {
+            server,
+            connection: Some(connection),
+        })
+    }
+
+    /// Opens a new connection to the server at `index` that isn't pooled.
+    pub async fn open(&self, index: usize) -> Result<OpenRGB<Stream>, Box<dyn Error>> {
+        let stream = self.connector.open(&self.servers[index].address).await?;
+
+        Ok(handshake(stream, &self.client_name).await?)
+    }
+
+    pub fn address(&self, index: usize) -> &str {
+        &self.servers[index].address
+    }
+
+    /// Drops the pooled connection to the server at `index`, e.g. one that stopped responding.
+    pub fn disconnect(&self, index: usize) {
+        self.servers[index].connection.lock().unwrap().take();
+    }
+}
+
+/// A connection out of the pool, which goes back to it when dropped.
+pub struct PooledConnection<'a> {
+    server: &'a Server,
+    // Only `None` once given back.
+    connection: Option<OpenRGB<Stream>>,
+}
+
+impl PooledConnection<'_> {
+    /// Drops a connection that failed instead of giving it back, the next `acquire` of its
+    /// server reconnects.
+    pub fn invalidate(mut self) {
+        self.connection = None;
+    }
+}
+
+impl Deref for PooledConnection<'_> {
+    type Target = OpenRGB<Stream>;
+
+    fn deref(&self) -> &Self::Target {
+        self.connection.as_ref().unwrap()
+    }
+}
+
+impl Drop for PooledConnection<'_> {
+    fn drop(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            *self.server.connection.lock().unwrap() = Some(connection);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_server::{
+        MockServer, REQUEST_CONTROLLER_COUNT, REQUEST_PROTOCOL_VERSION, SET_CLIENT_NAME,
+    };
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn names_the_client_before_querying_controllers() {
+        let server = MockServer::start(3, 1).await;
+        let pool = ConnectionPool::new([server.address()], "Desk PC");
+
+        let connection = pool.acquire(0).await.unwrap();
+        connection.get_controller_count().await.unwrap();
+
+        let packets = server.packets();
+        let ids: Vec<u32> = packets.iter().map(|packet| packet.packet_id).collect();
+        assert_eq!(
+            ids,
+            [
+                REQUEST_PROTOCOL_VERSION,
+                SET_CLIENT_NAME,
+                REQUEST_CONTROLLER_COUNT
+            ]
+        );
+        assert!(packets[1]
+            .data
+            .windows(b"Desk PC".len())
+            .any(|window| window == b"Desk PC"));
+    }
+
+    #[tokio::test]
+    async fn a_server_going_away_leaves_the_others_connection_alone() {
+        let (first, second) = (MockServer::start(3, 1).await, MockServer::start(3, 2).await);
+        let pool = ConnectionPool::new([first.address(), second.address()], "test");
+        pool.connect_all().await.unwrap();
+
+        first.stop();
+        let failed = pool.acquire(0).await.unwrap();
+        assert!(failed.get_controller_count().await.is_err());
+        failed.invalidate();
+        assert!(pool.acquire(0).await.is_err());
+
+        // Still the connection from before, which still works.
+        let connection = pool.acquire(1).await.unwrap();
+        assert_eq!(connection.get_controller_count().await.unwrap(), 2);
+        assert_eq!(second.connection_count(), 1);
+        assert!(second.packets().iter().all(|packet| packet.connection == 0));
+        let handshakes = second
+            .packets()
+            .iter()
+            .filter(|packet| packet.packet_id == REQUEST_PROTOCOL_VERSION)
+            .count();
+        assert_eq!(handshakes, 1);
+    }
+
+    /// Connects to the server over a pipe instead of the network.
+    struct InMemory(Arc<MockServer>);
+
+    impl Connector for InMemory {
+        fn open<'a>(
+            &'a self,
+            _address: &'a str,
+        ) -> Pin<Box<dyn Future<Output = io::Result<Stream>> + Send + 'a>> {
+            Box::pin(async move { Ok(Box::new(self.0.connect_in_memory()) as Stream) })
+        }
+    }
+
+    #[tokio::test]
+    async fn runs_over_the_stream_a_connector_opens() {
+        let server = Arc::new(MockServer::start(3, 2).await);
+        let pool = ConnectionPool::new(["in-memory".to_string()], "test")
+            .with_connector(InMemory(server.clone()));
+
+        let connection = pool.acquire(0).await.unwrap();
+        assert_eq!(connection.get_controller_count().await.unwrap(), 2);
+        assert_eq!(server.connection_count(), 1);
+        assert_eq!(server.packets()[0].packet_id, REQUEST_PROTOCOL_VERSION);
+    }
+}
@@ -0,0 +1,56 @@
+use crate::color::{lerp_color_stops, parse_hex_color, ColorSpace, ColorStop};
+use std::error::Error;
+
+/// Parses stops written as `#RRGGBB:position`, separated by commas, e.g.
+/// `#000000:0.0,#FF0000:0.5,#FFFF00:1.0`.
+pub fn parse_stops(text: &str) -> Result<Vec<ColorStop>, Box<dyn Error>> {
+    let mut stops = Vec::new();
+    for stop in text.split(',') {
+        let (color, position) = stop
+            .trim()
+            .rsplit_once(':')
+            .ok_or_else(|| format!("invalid stop {stop:?}, expected #RRGGBB:position"))?;
+        let color =
+            parse_hex_color(color).ok_or_else(|| format!("invalid color {color:?} in {stop:?}"))?;
+        let position = position
+            .parse()
+            .map_err(|_| format!("invalid position {position:?} in {stop:?}"))?;
+
+        stops.push(ColorStop { position, color });
+    }
+    stops.sort_by(|a, b| a.position.total_cmp(&b.position));
+
+    Ok(stops)
+}
+
+/// The gradient as a bar of `width` true-color ANSI cells.
+pub fn gradient_bar(stops: &[ColorStop], width: usize, color_space: ColorSpace) -> String {
+    let last = (width.saturating_sub(1)).max(1) as f32;
+
+    let mut bar = String::new();
+    for column in 0..width {
+        let color = lerp_color_stops(stops, column as f32 / last, color_space);
+        bar.push_str(&format!("\x1b[48;2;{};{};{}m ", color.r, color.g, color.b));
+    }
+    bar.push_str("\x1b[0m");
+
+    bar
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bar_runs_from_the_first_to_the_last_stop() {
+        let stops = parse_stops("#000000:0.0,#FF0000:0.5,#FFFF00:1.0").unwrap();
+        let bar = gradient_bar(&stops, 10, ColorSpace::Rgb);
+
+        // Reset at the end, so the terminal colors after it aren't affected.
+        let cells = bar.strip_suffix("\x1b[0m").unwrap();
+        let cells: Vec<&str> = cells.split_terminator(' ').collect();
+        assert_eq!(cells.len(), 10);
+        assert_eq!(cells[0], "\x1b[48;2;0;0;0m");
+        assert_eq!(cells[9], "\x1b[48;2;255;255;0m");
+    }
+}
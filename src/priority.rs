@@ -0,0 +1,88 @@
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+/// The scheduling priority of the render loop.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    #[default]
+    Normal,
+    /// A nice value of 10, so the lighting gives way to everything else.
+    BelowNormal,
+    /// `SCHED_FIFO` at priority 1 on Linux, so the lighting doesn't flicker on a loaded machine.
+    /// Needs the `rt-priority` feature and `CAP_SYS_NICE`.
+    RealTime,
+}
+
+/// Applies `priority` to the calling thread, which runs the render loop. Failures are logged, the
+/// client runs at normal priority then.
+pub fn apply(priority: Priority) {
+    match priority {
+        Priority::Normal => {}
+        Priority::BelowNormal => below_normal(),
+        Priority::RealTime => real_time(),
+    }
+}
+
+#[cfg(unix)]
+fn below_normal() {
+    // Can't return -1 on success, the nice value only goes up from 0 here.
+    if unsafe { libc::nice(10) } == -1 {
+        warn!(
+            "Failed to lower the priority: {}",
+            std::io::Error::last_os_error()
+        );
+    } else {
+        info!("Running at a nice value of 10");
+    }
+}
+
+#[cfg(not(unix))]
+fn below_normal() {
+    warn!("scheduler_priority below_normal is only supported on Unix");
+}
+
+#[cfg(all(target_os = "linux", feature = "rt-priority"))]
+fn real_time() {
+    let param = libc::sched_param { sched_priority: 1 };
+    if unsafe { libc::sched_setscheduler(0, libc::SCHED_FIFO, &param) } == -1 {
+        let error = std::io::Error::last_os_error();
+        if error.raw_os_error() == Some(libc::EPERM) {
+            warn!("Can't switch to real-time priority without CAP_SYS_NICE, running at normal priority");
+        } else {
+            warn!("Failed to switch to real-time priority: {error}");
+        }
+    } else {
+        info!("Running with SCHED_FIFO real-time priority");
+    }
+}
+
+#[cfg(all(target_os = "linux", not(feature = "rt-priority")))]
+fn real_time() {
+    warn!("scheduler_priority real_time needs the rt-priority feature, running at normal priority");
+}
+
+#[cfg(not(target_os = "linux"))]
+fn real_time() {
+    warn!("scheduler_priority real_time is only supported on Linux");
+}
+
+// On Linux the nice value is per thread, so the test leaves the others alone.
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn below_normal_adds_10_to_the_nice_value() {
+        std::thread::spawn(|| {
+            let nice = || unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) };
+            let before = nice();
+
+            apply(Priority::BelowNormal);
+
+            assert_eq!(nice(), (before + 10).min(19));
+        })
+        .join()
+        .unwrap();
+    }
+}
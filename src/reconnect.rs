@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio_retry::strategy::{jitter, ExponentialBackoff, FixedInterval};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetryStrategy {
+    /// `interval_ms` between attempts.
+    #[default]
+    Fixed,
+    /// `factor_ms * base^n` before the nth attempt.
+    Exponential,
+    /// Like `exponential`, but never waits longer than `max_ms`.
+    ExponentialCapped,
+}
+
+/// How the client waits between attempts to connect to the OpenRGB server, at startup and after
+/// losing the connection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReconnectConfig {
+    pub strategy: RetryStrategy,
+    pub interval_ms: u64,
+    pub base: u64,
+    pub factor_ms: u64,
+    pub max_ms: u64,
+    /// Waits a random part of each delay instead, so clients don't retry in lockstep.
+    pub jitter: bool,
+    /// Gives up after this many failed attempts. 0 retries forever.
+    pub max_attempts: usize,
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        Self {
+            strategy: RetryStrategy::Fixed,
+            interval_ms: 5000,
+            base: 2,
+            factor_ms: 1000,
+            max_ms: 60_000,
+            jitter: false,
+            max_attempts: 0,
+        }
+    }
+}
+
+impl ReconnectConfig {
+    /// The delays between attempts, for `tokio_retry`.
+    pub fn delays(&self) -> Box<dyn Iterator<Item = Duration> + Send> {
+        let delays: Box<dyn Iterator<Item = Duration> + Send> = match self.strategy {
+            RetryStrategy::Fixed => Box::new(FixedInterval::from_millis(self.interval_ms)),
+            RetryStrategy::Exponential => {
+                Box::new(ExponentialBackoff::from_millis(self.base.max(1)).factor(self.factor_ms))
+            }
+            RetryStrategy::ExponentialCapped => Box::new(
+                ExponentialBackoff::from_millis(self.base.max(1))
+                    .factor(self.factor_ms)
+                    .max_delay(Duration::from_millis(self.max_ms)),
+            ),
+        };
+        let delays: Box<dyn Iterator<Item = Duration> + Send> = if self.jitter {
+            Box::new(delays.map(jitter))
+        } else {
+            delays
+        };
+
+        // The first attempt isn't a retry.
+        match self.max_attempts {
+            0 => delays,
+            max_attempts => Box::new(delays.take(max_attempts - 1)),
+        }
+    }
+}
@@ -0,0 +1,1442 @@
+use crate::{
+    batch::{PendingUpdate, UpdateBatch},
+    color::{apply_white_balance, effective_kelvin, scale_colors, white_point},
+    color_history::ColorHistory,
+    config::{
+        Config, ControllerConfig, GroupConfig, LedCountMismatch, MetricUnavailable,
+        ZeroLedControllers,
+    },
+    debounce::Debouncer,
+    effect::Effect,
+    frame_rate::AdaptiveFrameRate,
+    gamma::GammaTables,
+    metrics::{self, MetricBus, MetricSampler, MetricValues},
+    mirror::Mirrors,
+    schedule::ActiveProfile,
+    sink::{ControllerInfo, OpenRgbSink},
+    status::{ControllerStatus, MetricStatus, SharedStatus, WrittenColors},
+    sync::ControllerGroupSynchronizer,
+    transition::{EffectStateMachine, EffectTransition, TransitionState},
+};
+use chrono::Local;
+use openrgb::data::Color;
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt,
+    future::Future,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+use tokio::time::{Instant, Interval, MissedTickBehavior};
+use tracing::{info, warn};
+
+/// How often an animated config is rendered when sampling takes no time, about 30 FPS.
+const ANIMATION_FRAME_PERIOD: Duration = Duration::from_millis(33);
+
+/// Turns the metrics into colors and writes them to the controllers. Survives reconnects, so the
+/// metric history and transitions carry over.
+pub struct Renderer {
+    config: Config,
+    active_profile: Arc<RwLock<ActiveProfile>>,
+    sampler: MetricSampler,
+    color_history: Option<ColorHistory>,
+    transitions: HashMap<u32, TransitionState>,
+    mirrors: Mirrors,
+    /// The colors last sent to each controller, to only send the LEDs that changed.
+    sent_colors: HashMap<u32, Vec<Color>>,
+    /// Controllers dimmed through their mode's brightness rather than by scaling their colors.
+    mode_dimmed: HashSet<u32>,
+    /// Controllers already warned about having another LED count than their zones.
+    led_count_mismatches: HashSet<u32>,
+    /// Controllers known to report no LEDs, see `ZeroLedControllers`.
+    empty_controllers: HashSet<u32>,
+    frame_rate: Option<AdaptiveFrameRate>,
+    /// Set when `batch_flush_interval_ms` is.
+    batch: Option<UpdateBatch>,
+    status: SharedStatus,
+    metric_bus: Arc<MetricBus>,
+    /// The effect of every zone by controller name, when `effect_transition` is set.
+    effect_states: HashMap<String, Vec<EffectStateMachine>>,
+    /// The profile of the last frame, to notice switches.
+    rendered_profile: Option<Option<String>>,
+    last_frame: Option<Instant>,
+    synchronizer: Option<ControllerGroupSynchronizer>,
+    debouncer: Debouncer,
+    gamma_tables: GammaTables,
+    /// Paces the frames when sampling doesn't, see `frame_period`.
+    frame_timer: Option<Interval>,
+    /// Since when the server has had no controllers, e.g. before it detected the devices.
+    no_controllers_since: Option<Instant>,
+    /// The controllers whose calls timed out lately.
+    controller_timeouts: HashMap<u32, TimeoutBackoff>,
+}
+
+impl Renderer {
+    pub fn new(
+        config: Config,
+        active_profile: Arc<RwLock<ActiveProfile>>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let sampler = MetricSampler::new(&config)?;
+
+        let color_history = config
+            .color_history_path
+            .as_deref()
+            .map(ColorHistory::open)
+            .transpose()?;
+
+        let frame_rate = config
+            .adaptive_frame_rate
+            .as_ref()
+            .map(AdaptiveFrameRate::new);
+
+        let synchronizer = config
+            .sync
+            .as_ref()
+            .map(|sync| ControllerGroupSynchronizer::new(sync, frame_period(&config, &sampler)))
+            .transpose()?;
+
+        let batch = (config.batch_flush_interval_ms > 0)
+            .then(|| UpdateBatch::new(Duration::from_millis(config.batch_flush_interval_ms)));
+
+        Ok(Self {
+            config,
+            active_profile,
+            sampler,
+            color_history,
+            transitions: HashMap::new(),
+            mirrors: Mirrors::default(),
+            sent_colors: HashMap::new(),
+            mode_dimmed: HashSet::new(),
+            led_count_mismatches: HashSet::new(),
+            empty_controllers: HashSet::new(),
+            frame_rate,
+            batch,
+            status: SharedStatus::default(),
+            metric_bus: Arc::default(),
+            effect_states: HashMap::new(),
+            rendered_profile: None,
+            last_frame: None,
+            synchronizer,
+            debouncer: Debouncer::default(),
+            gamma_tables: GammaTables::new(config.gammas()),
+            frame_timer: None,
+            no_controllers_since: None,
+            controller_timeouts: HashMap::new(),
+        })
+    }
+
+    /// Makes the next frame send every LED, e.g. after reconnecting to a server that may not have
+    /// the colors sent before.
+    pub fn forget_sent_colors(&mut self) {
+        self.sent_colors.clear();
+        self.debouncer.clear();
+        if let Some(batch) = &mut self.batch {
+            batch.clear();
+        }
+    }
+
+    /// The controllers whose `brightness` was set through their mode on a new connection.
+    pub fn set_mode_dimmed(&mut self, mode_dimmed: HashSet<u32>) {
+        self.mode_dimmed = mode_dimmed;
+    }
+
+    /// The controllers that report no LEDs on a new connection, even in Direct mode.
+    pub fn set_empty_controllers(&mut self, empty_controllers: impl IntoIterator<Item = u32>) {
+        self.empty_controllers = empty_controllers.into_iter().collect();
+
+        if !self.empty_controllers.is_empty() {
+            match self.config.zero_led_controllers {
+                ZeroLedControllers::Retry => {
+                    info!(
+                        "Checking controllers {:?} for LEDs every frame",
+                        self.empty_controllers
+                    )
+                }
+                ZeroLedControllers::Skip => {
+                    info!(
+                        "Skipping controllers {:?}, they have no LEDs",
+                        self.empty_controllers
+                    )
+                }
+            }
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Updated at the end of every frame.
+    pub fn status(&self) -> SharedStatus {
+        self.status.clone()
+    }
+
+    /// Published to after sampling, at the start of every frame.
+    pub fn metric_bus(&self) -> Arc<MetricBus> {
+        self.metric_bus.clone()
+    }
+
+    /// The servers the renderer is drawing to, for the status.
+    pub fn set_connected_servers(&mut self, servers: Vec<String>) {
+        let mut status = self.status.write().unwrap();
+        status.connected_servers = servers;
+        if status.connected_servers.is_empty() {
+            status.controllers.clear();
+        }
+    }
+
+    /// Logs which metrics are available, see `metrics::self_test`.
+    pub async fn self_test(&mut self) -> Result<(), Box<dyn Error>> {
+        metrics::self_test(&mut self.sampler, self.config.strict_metrics).await
+    }
+
+    /// Fails when the server has controllers the config doesn't know about.
+    pub async fn check_controllers(&self, client: &impl OpenRgbSink) -> Result<(), Box<dyn Error>> {
+        let mut names = Vec::new();
+        for controller_id in 0..client.get_controller_count().await? {
+            let controller = client.get_controller(controller_id).await?;
+            self.config.check_zone_names(&controller)?;
+            names.push(controller.name);
+        }
+
+        self.config.check_controllers(&names)
+    }
+
+    /// Samples the metrics and updates every controller once.
+    pub async fn render_frame(&mut self, client: &impl OpenRgbSink) -> Result<(), Box<dyn Error>> {
+        // The adaptive frame rate may wait longer after the frame, the timer then ticks right away.
+        let period = self.frame_period();
+        if self.frame_timer.as_ref().map(Interval::period) != Some(period) {
+            let mut frame_timer = tokio::time::interval(period);
+            frame_timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            self.frame_timer = Some(frame_timer);
+        }
+        if let Some(frame_timer) = &mut self.frame_timer {
+            frame_timer.tick().await;
+        }
+
+        let frame_start = Instant::now();
+        let metrics = self.sampler.sample().await?;
+
+        self.render(client, metrics, frame_start).await
+    }
+
+    fn frame_period(&self) -> Duration {
+        frame_period(&self.config, &self.sampler)
+    }
+
+    /// Updates every controller once from `metrics` instead of sampling them, e.g. to replay a
+    /// known series of values.
+    pub async fn render_metrics(
+        &mut self,
+        client: &impl OpenRgbSink,
+        metrics: MetricValues,
+    ) -> Result<(), Box<dyn Error>> {
+        self.render(client, metrics, Instant::now()).await
+    }
+
+    async fn render(
+        &mut self,
+        client: &impl OpenRgbSink,
+        mut metrics: MetricValues,
+        frame_start: Instant,
+    ) -> Result<(), Box<dyn Error>> {
+        let frame_elapsed = self
+            .last_frame
+            .replace(frame_start)
+            .map_or(Duration::ZERO, |last_frame| frame_start - last_frame);
+        let color_temperature = self
+            .config
+            .color_temperature_schedule
+            .as_ref()
+            .map(|schedule| white_point(effective_kelvin(Local::now().time(), schedule)));
+        if let Some(synchronizer) = &mut self.synchronizer {
+            metrics.time_secs = synchronizer.sync(metrics.time_secs);
+        }
+        self.metric_bus.publish(&metrics);
+
+        let active_profile = self.active_profile.read().unwrap().name.clone();
+        let mappings = self.config.controllers(active_profile.as_deref());
+        let profile_switched = self
+            .rendered_profile
+            .replace(active_profile.clone())
+            .is_some_and(|rendered_profile| rendered_profile != active_profile);
+        if profile_switched {
+            for zone in mappings.iter().flat_map(|mapping| &mapping.zones) {
+                zone.effect.restart();
+            }
+        }
+
+        // Set the color.
+        let timeout = Duration::from_millis(self.config.protocol_timeout_ms);
+        let controller_count = with_timeout(timeout, client.get_controller_count(), || {
+            "Getting the controller count".to_string()
+        })
+        .await?;
+        self.track_controller_count(controller_count)?;
+        let mut controller_statuses = Vec::with_capacity(controller_count as usize);
+        for controller_id in 0..controller_count {
+            let skip_empty = self.config.zero_led_controllers == ZeroLedControllers::Skip;
+            if skip_empty && self.empty_controllers.contains(&controller_id) {
+                continue;
+            }
+
+            let backing_off = self
+                .controller_timeouts
+                .get(&controller_id)
+                .is_some_and(TimeoutBackoff::is_waiting);
+            if backing_off {
+                continue;
+            }
+
+            let controller = with_controller_timeout(
+                &self.config,
+                &mut self.controller_timeouts,
+                controller_id,
+                client.get_controller(controller_id),
+                || format!("Getting controller {controller_id}"),
+            )
+            .await?;
+            let led_count = controller.led_count;
+            if led_count == 0 {
+                // Only warn once per controller.
+                if self.empty_controllers.insert(controller_id) {
+                    warn!("Controller {} has no LEDs", controller.name);
+                }
+                continue;
+            }
+            if self.empty_controllers.remove(&controller_id) {
+                info!("Controller {} has {led_count} LEDs now", controller.name);
+            }
+            controller_statuses.push(ControllerStatus {
+                id: controller_id,
+                name: controller.name.clone(),
+                led_count,
+                last_written: None,
+            });
+
+            let mapping = mappings
+                .iter()
+                .find(|mapping| mapping.name == controller.name);
+            if mapping.is_some_and(|mapping| mapping.disabled) {
+                continue;
+            }
+
+            let group = self.config.group(&controller.name);
+            let member_mapping;
+            let mapping = match (mapping, group) {
+                (Some(mapping), _) => mapping,
+                (None, Some(group)) => {
+                    member_mapping = group.member_mapping(&controller.name);
+                    &member_mapping
+                }
+                // Only mapped in other profiles, see `Config::check_controllers`.
+                (None, None) => continue,
+            };
+
+            let effect_states = self.config.effect_transition.as_ref().map(|transition| {
+                let effect_states = self
+                    .effect_states
+                    .entry(controller.name.clone())
+                    .or_default();
+                sync_effect_states(effect_states, mapping, group, transition, profile_switched);
+                (effect_states.as_mut_slice(), frame_elapsed)
+            });
+
+            // Every animation runs off `time_secs`, which only an offset moves apart.
+            let offset_metrics;
+            let controller_metrics = if mapping.phase_offset_secs != 0.0 {
+                offset_metrics = MetricValues {
+                    time_secs: metrics.time_secs + mapping.phase_offset_secs,
+                    ..metrics.clone()
+                };
+                &offset_metrics
+            } else {
+                &metrics
+            };
+
+            let Some(mut colors) = generate_controller_colors(
+                mapping,
+                group,
+                controller_metrics,
+                &mut self.mirrors,
+                effect_states,
+                &controller,
+            ) else {
+                continue;
+            };
+
+            if colors.len() != led_count {
+                // Only warn once per controller.
+                if self.led_count_mismatches.insert(controller_id) {
+                    warn!(
+                        "The zones of {} add up to {} LEDs but it has {led_count}, handling it with {:?}",
+                        controller.name,
+                        colors.len(),
+                        mapping.led_count_mismatch
+                    );
+                }
+                match &mapping.led_count_mismatch {
+                    LedCountMismatch::Truncate => colors.resize(led_count, Color::new(0, 0, 0)),
+                    LedCountMismatch::Pad { color } => colors.resize(led_count, *color),
+                    LedCountMismatch::Skip => continue,
+                }
+            }
+
+            let mut colors = match &mapping.smooth_transition {
+                Some(transition) => self
+                    .transitions
+                    .entry(controller_id)
+                    .or_default()
+                    .update(transition, colors),
+                None => colors,
+            };
+            self.mirrors
+                .record(&controller.name, &controller.zone_led_counts, &colors);
+
+            if let Some(white_balance) = mapping.white_balance {
+                apply_white_balance(&mut colors, white_balance);
+            }
+
+            if let Some(color_temperature) =
+                color_temperature.filter(|_| mapping.use_color_temperature_schedule)
+            {
+                scale_colors(&mut colors, color_temperature);
+            }
+
+            if let Some(brightness) = mapping.brightness {
+                if !self.mode_dimmed.contains(&controller_id) {
+                    scale_colors(&mut colors, [brightness; 3]);
+                }
+            }
+
+            if mapping.rotate != 0 && !colors.is_empty() {
+                let shift = mapping.rotate.rem_euclid(colors.len() as i64);
+                colors.rotate_right(shift as usize);
+            }
+
+            if mapping.invert_leds {
+                colors.reverse();
+            }
+
+            let gamma = mapping.gamma.or(self.config.gamma);
+            if let Some(gamma) = gamma {
+                self.gamma_tables.apply(gamma, &mut colors);
+            }
+
+            if let Some(debounce) = &mapping.debounce {
+                if !self.debouncer.allow(debounce, controller_id, &colors) {
+                    continue;
+                }
+            }
+
+            // Last, so it's written as it is.
+            if let Some(off_color) = mapping.off_color {
+                let black = Color::new(0, 0, 0);
+                for color in colors.iter_mut().filter(|color| **color == black) {
+                    *color = off_color;
+                }
+            }
+
+            if let Some(controller_status) = controller_statuses.last_mut() {
+                controller_status.last_written = Some(WrittenColors::new(&colors));
+            }
+
+            if let Some(color_history) = &mut self.color_history {
+                if let Err(e) = color_history.record(controller_id, &colors) {
+                    warn!("Failed to record colors of {}: {e}", controller.name);
+                }
+            }
+
+            match &mut self.batch {
+                Some(batch) => batch.push(PendingUpdate {
+                    controller_id,
+                    controller_name: controller.name.clone(),
+                    zone_led_counts: controller.zone_led_counts,
+                    colors,
+                }),
+                None => {
+                    let send = send_colors(
+                        client,
+                        &mut self.sent_colors,
+                        &self.config,
+                        &controller.name,
+                        controller_id,
+                        &controller.zone_led_counts,
+                        colors,
+                    );
+                    with_controller_timeout(
+                        &self.config,
+                        &mut self.controller_timeouts,
+                        controller_id,
+                        send,
+                        || format!("Updating {}", controller.name),
+                    )
+                    .await?;
+                }
+            }
+        }
+
+        if let Some(batch) = self.batch.as_mut().filter(|batch| batch.is_due()) {
+            for update in batch.take() {
+                let controller_id = update.controller_id;
+                let send = send_colors(
+                    client,
+                    &mut self.sent_colors,
+                    &self.config,
+                    &update.controller_name,
+                    controller_id,
+                    &update.zone_led_counts,
+                    update.colors,
+                );
+                with_controller_timeout(
+                    &self.config,
+                    &mut self.controller_timeouts,
+                    controller_id,
+                    send,
+                    || format!("Updating {}", update.controller_name),
+                )
+                .await?;
+            }
+        }
+
+        self.update_status(active_profile, controller_statuses, &metrics);
+
+        if let Some(frame_rate) = &mut self.frame_rate {
+            let activity = mappings
+                .iter()
+                .filter(|mapping| !mapping.disabled)
+                .filter_map(|mapping| metrics.get(&mapping.metric))
+                .fold(0.0, f32::max);
+
+            let interval = frame_rate.update(activity);
+            tokio::time::sleep(interval.saturating_sub(frame_start.elapsed())).await;
+        }
+
+        Ok(())
+    }
+
+    /// Tells once when the server has no controllers, which is checked again every frame, and
+    /// fails after `require_devices_timeout_secs` without any.
+    fn track_controller_count(&mut self, controller_count: u32) -> Result<(), Box<dyn Error>> {
+        if controller_count > 0 {
+            if self.no_controllers_since.take().is_some() {
+                info!("The OpenRGB server has {controller_count} controllers now");
+            }
+            return Ok(());
+        }
+
+        let since = *self.no_controllers_since.get_or_insert_with(|| {
+            info!(
+                "The OpenRGB server has no controllers, waiting for devices to show up. Check that OpenRGB detects them"
+            );
+            Instant::now()
+        });
+
+        if let Some(timeout_secs) = self.config.require_devices_timeout_secs {
+            if since.elapsed() >= Duration::from_secs(timeout_secs) {
+                return Err(RequiredDevicesMissing(timeout_secs).into());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn update_status(
+        &self,
+        active_profile: Option<String>,
+        controllers: Vec<ControllerStatus>,
+        metrics: &MetricValues,
+    ) {
+        let mut metric_statuses: Vec<MetricStatus> = Vec::new();
+        for metric in self.config.metrics() {
+            if !metric_statuses
+                .iter()
+                .any(|status| &status.metric == metric)
+            {
+                metric_statuses.push(MetricStatus {
+                    metric: metric.clone(),
+                    value: metrics.get(metric),
+                    temperature: metrics
+                        .temperature(metric)
+                        .map(|celsius| self.config.temperature_unit.convert(celsius)),
+                });
+            }
+        }
+
+        let mut status = self.status.write().unwrap();
+        status.active_profile = active_profile;
+        status.controllers = controllers;
+        status.metrics = metric_statuses;
+        status.temperature_unit = self.config.temperature_unit;
+    }
+}
+
+/// Points the effect state of every zone of `mapping` at the zone's effect, starting transitions
+/// when the profile was switched. Starts over without transitions when the zones changed.
+fn sync_effect_states(
+    effect_states: &mut Vec<EffectStateMachine>,
+    mapping: &ControllerConfig,
+    group: Option<&GroupConfig>,
+    transition: &EffectTransition,
+    profile_switched: bool,
+) {
+    let effects = mapping
+        .zones
+        .iter()
+        .map(|zone| group.map_or(&zone.effect, |group| &group.shared_effect));
+
+    if effect_states.len() != mapping.zones.len() {
+        *effect_states = effects
+            .map(|effect| EffectStateMachine::new(effect.clone(), transition.clone()))
+            .collect();
+    } else if profile_switched {
+        for (effect_state, effect) in effect_states.iter_mut().zip(effects) {
+            effect_state.switch_to(effect.clone());
+        }
+    }
+}
+
+/// The server had no controllers for `require_devices_timeout_secs`, which reconnecting won't fix.
+#[derive(Debug)]
+pub struct RequiredDevicesMissing(pub u64);
+
+impl fmt::Display for RequiredDevicesMissing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "no controllers showed up within {} seconds", self.0)
+    }
+}
+
+impl Error for RequiredDevicesMissing {}
+
+/// The colors of a mapped controller, with the metric and effect of its `group` if it's in one.
+/// With `effect_states`, and the time since the last frame, the zones transition between effects.
+/// `None` leaves the controller alone.
+pub fn generate_controller_colors(
+    mapping: &ControllerConfig,
+    group: Option<&GroupConfig>,
+    metrics: &MetricValues,
+    mirrors: &mut Mirrors,
+    mut effect_states: Option<(&mut [EffectStateMachine], Duration)>,
+    controller: &ControllerInfo,
+) -> Option<Vec<Color>> {
+    let led_count = controller.led_count;
+    let metric = group.map_or(&mapping.metric, |group| &group.metric);
+    let invert = |value: f32| {
+        if mapping.invert_metric {
+            1.0 - value
+        } else {
+            value
+        }
+    };
+    let value = match (metrics.get(metric), &mapping.metric_unavailable) {
+        (Some(value), _) => invert(value),
+        (None, MetricUnavailable::Disable) => return None,
+        (None, MetricUnavailable::Solid { color }) => return Some(vec![*color; led_count]),
+        (None, MetricUnavailable::Substitute { metric }) => invert(metrics.get(metric)?),
+    };
+
+    let mut colors = Vec::with_capacity(led_count);
+    // Where the next zone without a name starts.
+    let mut next_led = 0;
+    for (zone_index, zone) in mapping.zones.iter().enumerate() {
+        // Checked on connecting, see `Config::check_zone_names`.
+        let leds = match &zone.name {
+            Some(name) => controller.zone_range(name)?,
+            None => {
+                let size = zone
+                    .led_count
+                    .unwrap_or_else(|| led_count.saturating_sub(next_led));
+                next_led..next_led + size
+            }
+        };
+        let size = leds.len();
+        next_led = leds.end;
+        // A group's metric drives every zone of its members.
+        let value = match zone.metric.as_ref().filter(|_| group.is_none()) {
+            Some(zone_metric) => match (metrics.get(zone_metric), &mapping.metric_unavailable) {
+                (Some(value), _) => invert(value),
+                (None, MetricUnavailable::Disable) => return None,
+                (None, MetricUnavailable::Solid { color }) => {
+                    place_zone(&mut colors, leds.start, vec![*color; size]);
+                    continue;
+                }
+                (None, MetricUnavailable::Substitute { metric }) => invert(metrics.get(metric)?),
+            },
+            None => value,
+        };
+
+        let effect = group.map_or(&zone.effect, |group| &group.shared_effect);
+        let zone_colors = match effect {
+            Effect::MirrorOf {
+                source_controller,
+                horizontal_flip,
+                vertical_flip,
+            } => mirrors.colors(
+                &mapping.name,
+                source_controller,
+                size,
+                *horizontal_flip,
+                *vertical_flip,
+            )?,
+            effect => match &mut effect_states {
+                Some((effect_states, elapsed)) => effect_states[zone_index].tick(
+                    *elapsed,
+                    value,
+                    metrics,
+                    size,
+                    mapping.color_space,
+                ),
+                None => effect.render(value, metrics, size, mapping.color_space),
+            },
+        };
+        place_zone(&mut colors, leds.start, zone_colors);
+    }
+
+    Some(colors)
+}
+
+/// Writes a zone's colors from `start` on, leaving any LEDs before it black.
+fn place_zone(colors: &mut Vec<Color>, start: usize, zone_colors: Vec<Color>) {
+    let end = start + zone_colors.len();
+    if colors.len() < end {
+        colors.resize(end, Color::new(0, 0, 0));
+    }
+    colors[start..end].copy_from_slice(&zone_colors);
+}
+
+/// How often frames are rendered: often enough for smooth animations and transitions, once per
+/// sample interval when nothing moves in between.
+fn frame_period(config: &Config, sampler: &MetricSampler) -> Duration {
+    if config.is_animated() {
+        ANIMATION_FRAME_PERIOD
+    } else {
+        sampler.sample_interval()
+    }
+}
+
+/// Fails a protocol call that takes longer than `timeout`, e.g. on a hung device, so the frame
+/// fails and the client reconnects. The connection can't be used any further anyway, the call
+/// may have left half a packet on it.
+async fn with_timeout<T, E: Into<Box<dyn Error>>>(
+    timeout: Duration,
+    call: impl Future<Output = Result<T, E>>,
+    what: impl FnOnce() -> String,
+) -> Result<T, Box<dyn Error>> {
+    match tokio::time::timeout(timeout, call).await {
+        Ok(result) => result.map_err(Into::into),
+        Err(_) => Err(format!("{} timed out after {} ms", what(), timeout.as_millis()).into()),
+    }
+}
+
+/// The longest a controller is left alone after its calls timed out.
+const MAX_TIMEOUT_BACKOFF: Duration = Duration::from_secs(60);
+
+/// The timeouts of a controller's calls in a row, and until when it's left alone after the last.
+#[derive(Debug, Default)]
+struct TimeoutBackoff {
+    consecutive: u32,
+    retry_at: Option<Instant>,
+}
+
+impl TimeoutBackoff {
+    fn is_waiting(&self) -> bool {
+        self.retry_at
+            .is_some_and(|retry_at| Instant::now() < retry_at)
+    }
+
+    /// Counts a timeout, doubling how long the controller is left alone with each in a row.
+    fn record(&mut self, timeout: Duration) -> Duration {
+        self.consecutive += 1;
+        let factor = 2u32.saturating_pow(self.consecutive - 1);
+        let delay = timeout.saturating_mul(factor).min(MAX_TIMEOUT_BACKOFF);
+        self.retry_at = Some(Instant::now() + delay);
+
+        delay
+    }
+}
+
+/// Times out a call for a single controller like `with_timeout`, after
+/// `per_controller_timeout_ms` or `protocol_timeout_ms` when unset. The controller is left alone
+/// for a while after a timeout, so the frames after the reconnect don't wait for it again.
+async fn with_controller_timeout<T, E: Into<Box<dyn Error>>>(
+    config: &Config,
+    backoffs: &mut HashMap<u32, TimeoutBackoff>,
+    controller_id: u32,
+    call: impl Future<Output = Result<T, E>>,
+    what: impl FnOnce() -> String,
+) -> Result<T, Box<dyn Error>> {
+    let timeout_ms = config
+        .per_controller_timeout_ms
+        .unwrap_or(config.protocol_timeout_ms);
+    let timeout = Duration::from_millis(timeout_ms);
+    match tokio::time::timeout(timeout, call).await {
+        Ok(result) => {
+            backoffs.remove(&controller_id);
+            result.map_err(Into::into)
+        }
+        Err(_) => {
+            let delay = backoffs.entry(controller_id).or_default().record(timeout);
+            Err(format!(
+                "{} timed out after {timeout_ms} ms, skipping it for {} ms",
+                what(),
+                delay.as_millis()
+            )
+            .into())
+        }
+    }
+}
+
+/// Sends only what changed since the last frame when the config allows it: the LEDs one by one
+/// when there are few of them, or the zones they are in. Everything otherwise.
+#[tracing::instrument(
+    skip(client, sent_colors, config, zone_led_counts, colors),
+    level = "debug"
+)]
+async fn send_colors(
+    client: &impl OpenRgbSink,
+    sent_colors: &mut HashMap<u32, Vec<Color>>,
+    config: &Config,
+    controller_name: &str,
+    controller_id: u32,
+    zone_led_counts: &[usize],
+    colors: Vec<Color>,
+) -> Result<(), Box<dyn Error>> {
+    let threshold = config.single_led_update_threshold;
+    if threshold == 0 && !config.zone_updates {
+        client.update_leds(controller_id, colors).await?;
+        return Ok(());
+    }
+
+    let changed: Option<Vec<usize>> = sent_colors
+        .get(&controller_id)
+        .filter(|sent| sent.len() == colors.len())
+        .map(|sent| {
+            sent.iter()
+                .zip(&colors)
+                .enumerate()
+                .filter(|(_, (sent, color))| sent != color)
+                .map(|(led, _)| led)
+                .collect()
+        });
+
+    // Zone updates need the zones to cover the LEDs exactly.
+    let zones_match = zone_led_counts.iter().sum::<usize>() == colors.len();
+
+    match changed {
+        Some(changed) if changed.len() <= threshold => {
+            for led in changed {
+                client
+                    .update_led(controller_id, led as i32, colors[led])
+                    .await?;
+            }
+        }
+        Some(changed) if config.zone_updates && zones_match => {
+            let mut start = 0;
+            for (zone_id, &zone_led_count) in zone_led_counts.iter().enumerate() {
+                let zone = start..start + zone_led_count;
+                start = zone.end;
+
+                if changed.iter().any(|led| zone.contains(led)) {
+                    client
+                        .update_zone_leds(controller_id, zone_id as u32, colors[zone].to_vec())
+                        .await?;
+                }
+            }
+        }
+        _ => client.update_leds(controller_id, colors.clone()).await?,
+    }
+    sent_colors.insert(controller_id, colors);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        color::ColorSpace,
+        config::Zone,
+        effect::Phase,
+        metrics::Metric,
+        simulator::{ControllerDef, ControllerSimulator, UnknownControllerError},
+        sync::{SyncConfig, SyncMode},
+    };
+    use std::{fmt, sync::Mutex};
+    use tracing::{
+        field::{Field, Visit},
+        span::{Attributes, Id},
+        Subscriber,
+    };
+    use tracing_subscriber::{
+        layer::{Context, SubscriberExt},
+        Layer,
+    };
+
+    /// The name and fields of every span created.
+    #[derive(Clone, Default)]
+    struct SpanRecorder(Arc<Mutex<Vec<(String, Vec<(String, String)>)>>>);
+
+    #[derive(Default)]
+    struct Fields(Vec<(String, String)>);
+
+    impl Visit for Fields {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.push((field.name().to_string(), value.to_string()));
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0
+                .push((field.name().to_string(), format!("{value:?}")));
+        }
+    }
+
+    impl<S: Subscriber> Layer<S> for SpanRecorder {
+        fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+            let mut fields = Fields::default();
+            attrs.record(&mut fields);
+            self.0
+                .lock()
+                .unwrap()
+                .push((attrs.metadata().name().to_string(), fields.0));
+        }
+    }
+
+    #[tokio::test]
+    async fn controller_updates_are_spans_with_the_controller_name() {
+        let recorder = SpanRecorder::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(recorder.clone()));
+        let simulator = ControllerSimulator::new(vec![ControllerDef {
+            name: "Strip".to_string(),
+            led_count: 2,
+        }]);
+
+        send_colors(
+            &simulator,
+            &mut HashMap::new(),
+            &Config::default(),
+            "Strip",
+            0,
+            &[2],
+            vec![Color::new(1, 2, 3); 2],
+        )
+        .await
+        .unwrap();
+
+        let spans = recorder.0.lock().unwrap();
+        let (_, fields) = spans
+            .iter()
+            .find(|(name, _)| name == "send_colors")
+            .expect("no send_colors span");
+        assert!(fields.contains(&("controller_name".to_string(), "Strip".to_string())));
+        assert!(fields.contains(&("controller_id".to_string(), "0".to_string())));
+    }
+
+    #[tokio::test]
+    async fn off_color_is_written_after_gamma() {
+        let off_color = Color::new(1, 0, 0);
+        let config = Config {
+            gamma: Some(2.2),
+            controllers: vec![ControllerConfig {
+                name: "Strip".to_string(),
+                off_color: Some(off_color),
+                zones: vec![Zone {
+                    name: None,
+                    led_count: None,
+                    metric: None,
+                    effect: Effect::Solid {
+                        color: Color::new(0, 0, 0),
+                    },
+                }],
+                ..Default::default()
+            }],
+            ..Config::default()
+        };
+        let mut renderer = Renderer::new(config, Arc::default()).unwrap();
+        let simulator = ControllerSimulator::new(vec![ControllerDef {
+            name: "Strip".to_string(),
+            led_count: 3,
+        }]);
+
+        renderer
+            .render_metrics(&simulator, MetricValues::default())
+            .await
+            .unwrap();
+
+        assert_eq!(simulator.last_colors(0).unwrap(), [off_color; 3]);
+    }
+
+    /// A controller "Strip" with one zone of `effect` driven by `value`.
+    fn strip(value: f32, effect: Effect) -> ControllerConfig {
+        ControllerConfig {
+            name: "Strip".to_string(),
+            metric: Metric::Constant(value),
+            zones: vec![Zone {
+                name: None,
+                led_count: None,
+                metric: None,
+                effect,
+            }],
+            ..Default::default()
+        }
+    }
+
+    /// The colors `mapping` sends to a controller "Strip" with `led_count` LEDs in one frame.
+    async fn render_strip(mapping: ControllerConfig, led_count: usize) -> Vec<Color> {
+        let config = Config {
+            controllers: vec![mapping],
+            ..Config::default()
+        };
+        let mut renderer = Renderer::new(config, Arc::default()).unwrap();
+        let simulator = ControllerSimulator::new(vec![ControllerDef {
+            name: "Strip".to_string(),
+            led_count,
+        }]);
+
+        renderer
+            .render_metrics(&simulator, MetricValues::default())
+            .await
+            .unwrap();
+
+        simulator.last_colors(0).unwrap()
+    }
+
+    fn gradient() -> Effect {
+        Effect::Gradient {
+            start_color: Color::new(0, 255, 0),
+            end_color: Color::new(255, 0, 0),
+        }
+    }
+
+    #[tokio::test]
+    async fn invert_leds_reverses_the_colors() {
+        let mut colors = render_strip(strip(0.6, gradient()), 5).await;
+        let inverted = render_strip(
+            ControllerConfig {
+                invert_leds: true,
+                ..strip(0.6, gradient())
+            },
+            5,
+        )
+        .await;
+
+        assert_ne!(colors.first(), colors.last());
+        colors.reverse();
+        assert_eq!(inverted, colors);
+    }
+
+    #[tokio::test]
+    async fn rotate_wraps_around() {
+        let colors = render_strip(strip(0.6, gradient()), 5).await;
+        let rotated = |rotate| {
+            render_strip(
+                ControllerConfig {
+                    rotate,
+                    ..strip(0.6, gradient())
+                },
+                5,
+            )
+        };
+
+        let mut expected = colors.clone();
+        expected.rotate_right(2);
+        assert_ne!(expected, colors);
+        assert_eq!(rotated(2).await, expected);
+        assert_eq!(rotated(7).await, expected);
+        assert_eq!(rotated(-3).await, expected);
+        assert_eq!(rotated(5).await, colors);
+    }
+
+    #[tokio::test]
+    async fn invert_metric_maps_the_value_to_its_complement() {
+        let block = || Effect::Block {
+            start_color: Color::new(0, 0, 0),
+            end_color: Color::new(200, 100, 0),
+        };
+        let inverted = render_strip(
+            ControllerConfig {
+                invert_metric: true,
+                ..strip(0.3, block())
+            },
+            3,
+        )
+        .await;
+
+        assert_eq!(inverted, render_strip(strip(0.7, block()), 3).await);
+        assert_eq!(inverted, [Color::new(140, 70, 0); 3]);
+    }
+
+    #[tokio::test]
+    async fn group_members_animate_in_phase() {
+        let flashing = Effect::Alert {
+            alert: Metric::Constant(1.0),
+            color: Color::new(255, 0, 0),
+            flash_rate_hz: 3.0,
+            base: Box::new(Effect::Solid {
+                color: Color::new(0, 0, 255),
+            }),
+        };
+        let config = Config {
+            // Only one of them has a mapping of its own.
+            controllers: vec![ControllerConfig {
+                name: "Fans".to_string(),
+                zones: vec![
+                    Zone {
+                        name: None,
+                        led_count: Some(2),
+                        metric: None,
+                        effect: gradient(),
+                    },
+                    Zone {
+                        name: None,
+                        led_count: None,
+                        metric: None,
+                        effect: gradient(),
+                    },
+                ],
+                ..Default::default()
+            }],
+            groups: vec![GroupConfig {
+                name: "Case".to_string(),
+                members: vec!["Fans".to_string(), "Ram".to_string()],
+                metric: Metric::Constant(1.0),
+                shared_effect: flashing,
+                color_space: ColorSpace::default(),
+            }],
+            ..Config::default()
+        };
+        let mut renderer = Renderer::new(config, Arc::default()).unwrap();
+        let simulator = ControllerSimulator::new(vec![
+            ControllerDef {
+                name: "Fans".to_string(),
+                led_count: 4,
+            },
+            ControllerDef {
+                name: "Ram".to_string(),
+                led_count: 4,
+            },
+        ]);
+
+        let mut frames = Vec::new();
+        for tick in 0..12 {
+            let metrics = MetricValues {
+                time_secs: tick as f64 * 0.05,
+                ..MetricValues::default()
+            };
+            renderer.render_metrics(&simulator, metrics).await.unwrap();
+
+            let fans = simulator.last_colors(0).unwrap();
+            assert_eq!(simulator.last_colors(1).unwrap(), fans, "tick {tick}");
+            if !frames.contains(&fans) {
+                frames.push(fans);
+            }
+        }
+        // Both on and off during the flashes.
+        assert_eq!(frames.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn unmapped_group_members_use_the_group_color_space() {
+        let (red, green) = (Color::new(255, 0, 0), Color::new(0, 255, 0));
+        let config = Config {
+            groups: vec![GroupConfig {
+                name: "Case".to_string(),
+                members: vec!["Ram".to_string()],
+                metric: Metric::Constant(0.5),
+                shared_effect: Effect::Block {
+                    start_color: red,
+                    end_color: green,
+                },
+                color_space: ColorSpace::Hsv,
+            }],
+            ..Config::default()
+        };
+        let mut renderer = Renderer::new(config, Arc::default()).unwrap();
+        let simulator = ControllerSimulator::new(vec![ControllerDef {
+            name: "Ram".to_string(),
+            led_count: 2,
+        }]);
+
+        renderer
+            .render_metrics(&simulator, MetricValues::default())
+            .await
+            .unwrap();
+
+        let halfway = ColorSpace::Hsv.lerp(0.5, &red, &green);
+        assert_ne!(halfway, ColorSpace::Rgb.lerp(0.5, &red, &green));
+        assert_eq!(simulator.last_colors(0).unwrap(), [halfway; 2]);
+    }
+
+    #[tokio::test]
+    async fn paces_frames_by_what_the_config_animates() {
+        let config = |effect| Config {
+            controllers: vec![ControllerConfig {
+                name: "Strip".to_string(),
+                metric: Metric::Constant(0.5),
+                zones: vec![Zone {
+                    name: None,
+                    led_count: None,
+                    metric: None,
+                    effect,
+                }],
+                ..Default::default()
+            }],
+            ..Config::default()
+        };
+        let still = Renderer::new(
+            config(Effect::Solid {
+                color: Color::new(0, 0, 0),
+            }),
+            Arc::default(),
+        )
+        .unwrap();
+        assert_eq!(still.frame_period(), still.sampler.sample_interval());
+
+        let flashing = Effect::Alert {
+            alert: Metric::Constant(1.0),
+            color: Color::new(255, 0, 0),
+            flash_rate_hz: 2.0,
+            base: Box::new(Effect::Solid {
+                color: Color::new(0, 0, 0),
+            }),
+        };
+        let mut animated = Renderer::new(config(flashing), Arc::default()).unwrap();
+        assert_eq!(animated.frame_period(), ANIMATION_FRAME_PERIOD);
+        let simulator = ControllerSimulator::new(vec![ControllerDef {
+            name: "Strip".to_string(),
+            led_count: 3,
+        }]);
+
+        // The first frame is right away, then one per period, without waiting for samples.
+        let start = Instant::now();
+        for _ in 0..3 {
+            animated.render_frame(&simulator).await.unwrap();
+        }
+        let elapsed = start.elapsed();
+        assert!(elapsed >= ANIMATION_FRAME_PERIOD * 2, "{elapsed:?}");
+        assert!(elapsed < animated.sampler.sample_interval(), "{elapsed:?}");
+    }
+
+    #[tokio::test]
+    async fn paces_animations_while_measuring_the_cpu() {
+        let config = Config {
+            controllers: vec![ControllerConfig {
+                name: "Strip".to_string(),
+                metric: Metric::CpuUsage,
+                zones: vec![Zone {
+                    name: None,
+                    led_count: None,
+                    metric: None,
+                    effect: Effect::Breathing {
+                        rate_hz: 1.0,
+                        color: Color::new(255, 0, 0),
+                        phase: Phase::default(),
+                    },
+                }],
+                ..Default::default()
+            }],
+            ..Config::default()
+        };
+        let mut renderer = Renderer::new(config, Arc::default()).unwrap();
+        assert!(renderer.sampler.measures_cpu());
+        let simulator = ControllerSimulator::new(vec![ControllerDef {
+            name: "Strip".to_string(),
+            led_count: 3,
+        }]);
+
+        // The first frame waits for the first CPU sample, the next ones don't.
+        renderer.render_frame(&simulator).await.unwrap();
+        let start = Instant::now();
+        for _ in 0..3 {
+            renderer.render_frame(&simulator).await.unwrap();
+        }
+        let elapsed = start.elapsed();
+        assert!(elapsed >= ANIMATION_FRAME_PERIOD * 2, "{elapsed:?}");
+        assert!(elapsed < renderer.sampler.sample_interval(), "{elapsed:?}");
+    }
+
+    /// The simulator, but getting the controller `slow` takes `delay`, like a hung device.
+    struct SlowController {
+        simulator: ControllerSimulator,
+        slow: u32,
+        delay: Duration,
+    }
+
+    impl OpenRgbSink for SlowController {
+        type Error = UnknownControllerError;
+
+        async fn get_controller_count(&self) -> Result<u32, Self::Error> {
+            self.simulator.get_controller_count().await
+        }
+
+        async fn get_controller(&self, controller_id: u32) -> Result<ControllerInfo, Self::Error> {
+            if controller_id == self.slow {
+                tokio::time::sleep(self.delay).await;
+            }
+
+            self.simulator.get_controller(controller_id).await
+        }
+
+        async fn update_leds(
+            &self,
+            controller_id: u32,
+            colors: Vec<Color>,
+        ) -> Result<(), Self::Error> {
+            self.simulator.update_leds(controller_id, colors).await
+        }
+
+        async fn update_led(
+            &self,
+            controller_id: u32,
+            led_id: i32,
+            color: Color,
+        ) -> Result<(), Self::Error> {
+            self.simulator
+                .update_led(controller_id, led_id, color)
+                .await
+        }
+
+        async fn update_zone_leds(
+            &self,
+            controller_id: u32,
+            zone_id: u32,
+            colors: Vec<Color>,
+        ) -> Result<(), Self::Error> {
+            self.simulator
+                .update_zone_leds(controller_id, zone_id, colors)
+                .await
+        }
+    }
+
+    #[tokio::test]
+    async fn skips_a_controller_that_timed_out_after_reconnecting() {
+        let red = Color::new(255, 0, 0);
+        let controller = |name: &str| ControllerConfig {
+            name: name.to_string(),
+            metric: Metric::Constant(1.0),
+            zones: vec![Zone {
+                name: None,
+                led_count: None,
+                metric: None,
+                effect: Effect::Solid { color: red },
+            }],
+            ..Default::default()
+        };
+        let config = Config {
+            controllers: vec![controller("Fast"), controller("Slow")],
+            per_controller_timeout_ms: Some(50),
+            ..Config::default()
+        };
+        let mut renderer = Renderer::new(config, Arc::default()).unwrap();
+        let client = SlowController {
+            simulator: ControllerSimulator::new(vec![
+                ControllerDef {
+                    name: "Fast".to_string(),
+                    led_count: 2,
+                },
+                ControllerDef {
+                    name: "Slow".to_string(),
+                    led_count: 2,
+                },
+            ]),
+            slow: 1,
+            delay: Duration::from_secs(5),
+        };
+        let black = Color::new(0, 0, 0);
+
+        // The call can't be taken back, so the frame fails for the client to reconnect.
+        let result = renderer
+            .render_metrics(&client, MetricValues::default())
+            .await;
+        assert!(result.is_err());
+
+        // Left alone while backing off, so the frame after the reconnect doesn't wait for it.
+        let start = Instant::now();
+        renderer
+            .render_metrics(&client, MetricValues::default())
+            .await
+            .unwrap();
+        assert!(start.elapsed() < Duration::from_millis(50));
+        assert_eq!(client.simulator.last_colors(0).unwrap(), [red; 2]);
+        assert_eq!(client.simulator.last_colors(1).unwrap(), [black; 2]);
+
+        // Then tried again, timing out a second time in a row and left alone for twice as long.
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        let result = renderer
+            .render_metrics(&client, MetricValues::default())
+            .await;
+        assert!(result.is_err());
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        let start = Instant::now();
+        renderer
+            .render_metrics(&client, MetricValues::default())
+            .await
+            .unwrap();
+        assert!(start.elapsed() < Duration::from_millis(50));
+        assert_eq!(client.simulator.last_colors(1).unwrap(), [black; 2]);
+    }
+
+    #[tokio::test]
+    async fn followers_render_the_leaders_colors() {
+        // A free port for the follower to listen on.
+        let address = std::net::UdpSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let synced = |mode| {
+            let mapping = strip(
+                1.0,
+                Effect::Alert {
+                    alert: Metric::Constant(1.0),
+                    color: Color::new(255, 0, 0),
+                    flash_rate_hz: 1.0,
+                    base: Box::new(Effect::Solid {
+                        color: Color::new(0, 0, 255),
+                    }),
+                },
+            );
+            Config {
+                controllers: vec![mapping],
+                sync: Some(SyncConfig {
+                    mode,
+                    address,
+                    group: "Desk".to_string(),
+                }),
+                ..Config::default()
+            }
+        };
+        let mut follower = Renderer::new(synced(SyncMode::Follower), Arc::default()).unwrap();
+        let mut leader = Renderer::new(synced(SyncMode::Leader), Arc::default()).unwrap();
+        let simulators = [(); 2].map(|_| {
+            ControllerSimulator::new(vec![ControllerDef {
+                name: "Strip".to_string(),
+                led_count: 3,
+            }])
+        });
+
+        // Half a flash apart, so the follower's own clock would render the other color.
+        for (leader_time, follower_time) in [(0.25, 0.75), (0.75, 0.25)] {
+            let at = |time_secs| MetricValues {
+                time_secs,
+                ..MetricValues::default()
+            };
+            leader
+                .render_metrics(&simulators[0], at(leader_time))
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            follower
+                .render_metrics(&simulators[1], at(follower_time))
+                .await
+                .unwrap();
+
+            assert_eq!(
+                simulators[1].last_colors(0).unwrap(),
+                simulators[0].last_colors(0).unwrap(),
+                "leader at {leader_time} s"
+            );
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use chrono::{DateTime, Local};
+use cron::Schedule;
+use serde::{Deserialize, Serialize};
+use std::{
+    str::FromStr,
+    sync::{Arc, RwLock},
+};
+use tracing::info;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleEntry {
+    /// With seconds, e.g. `0 0 9 * * Mon-Fri` for 09:00 on weekdays.
+    pub cron_expr: String,
+    pub profile: String,
+}
+
+/// The profile the controllers are rendered with. `None` uses the top-level controllers.
+#[derive(Debug, Default)]
+pub struct ActiveProfile {
+    pub name: Option<String>,
+}
+
+/// Where the scheduler gets the time from, so it can be simulated.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Local>;
+}
+
+/// The local time of the system.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+}
+
+/// Switches the active profile whenever a schedule entry triggers.
+pub struct SceneScheduler {
+    entries: Vec<(Schedule, String)>,
+    active_profile: Arc<RwLock<ActiveProfile>>,
+    clock: Box<dyn Clock>,
+}
+
+impl SceneScheduler {
+    pub fn new(
+        entries: &[ScheduleEntry],
+        active_profile: Arc<RwLock<ActiveProfile>>,
+    ) -> Result<Self, cron::error::Error> {
+        let entries = entries
+            .iter()
+            .map(|entry| Ok((Schedule::from_str(&entry.cron_expr)?, entry.profile.clone())))
+            .collect::<Result<_, cron::error::Error>>()?;
+
+        Ok(Self {
+            entries,
+            active_profile,
+            clock: Box::new(SystemClock),
+        })
+    }
+
+    /// Takes the time from `clock` instead of the system.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    pub async fn run(self) {
+        // Start with the profile that would be active had we been running all along.
+        if let Some(profile) = self.latest_trigger(&self.clock.now()) {
+            self.activate(profile);
+        }
+
+        loop {
+            let now = self.clock.now();
+            let Some((time, profile)) = self.next_trigger(&now) else {
+                return;
+            };
+
+            let delay = (time - now).to_std().unwrap_or_default();
+            tokio::time::sleep(delay).await;
+
+            self.activate(profile);
+        }
+    }
+
+    /// The profile of the entry that triggered last before `now`.
+    fn latest_trigger(&self, now: &DateTime<Local>) -> Option<&str> {
+        self.entries
+            .iter()
+            .filter_map(|(schedule, profile)| Some((schedule.after(now).next_back()?, profile)))
+            .max_by_key(|(time, _)| *time)
+            .map(|(_, profile)| profile.as_str())
+    }
+
+    /// When the next entry after `now` triggers, and its profile.
+    fn next_trigger(&self, now: &DateTime<Local>) -> Option<(DateTime<Local>, &str)> {
+        self.entries
+            .iter()
+            .filter_map(|(schedule, profile)| Some((schedule.after(now).next()?, profile)))
+            .min_by_key(|(time, _)| *time)
+            .map(|(time, profile)| (time, profile.as_str()))
+    }
+
+    fn activate(&self, profile: &str) {
+        let mut active_profile = self.active_profile.write().unwrap();
+        if active_profile.name.as_deref() != Some(profile) {
+            info!("Switching to profile {profile}");
+            active_profile.name = Some(profile.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    struct MockClock(DateTime<Local>);
+
+    impl Clock for MockClock {
+        fn now(&self) -> DateTime<Local> {
+            self.0
+        }
+    }
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2025, 1, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    fn scheduler(now: DateTime<Local>) -> SceneScheduler {
+        let entries = [
+            ScheduleEntry {
+                cron_expr: "0 0 9 * * Mon-Fri".to_string(),
+                profile: "work".to_string(),
+            },
+            ScheduleEntry {
+                cron_expr: "0 0 18 * * *".to_string(),
+                profile: "gaming".to_string(),
+            },
+        ];
+
+        SceneScheduler::new(&entries, Arc::default())
+            .unwrap()
+            .with_clock(MockClock(now))
+    }
+
+    #[test]
+    fn entry_fires_at_its_time() {
+        // Monday, January 6th 2025.
+        let scheduler = scheduler(at(6, 8, 59));
+        let now = scheduler.clock.now();
+
+        assert_eq!(scheduler.latest_trigger(&now), Some("gaming"));
+        assert_eq!(scheduler.next_trigger(&now), Some((at(6, 9, 0), "work")));
+
+        let (time, profile) = scheduler.next_trigger(&at(6, 9, 0)).unwrap();
+        assert_eq!((time, profile), (at(6, 18, 0), "gaming"));
+        scheduler.activate(profile);
+        assert_eq!(
+            scheduler.active_profile.read().unwrap().name.as_deref(),
+            Some("gaming")
+        );
+    }
+
+    #[test]
+    fn weekday_entry_skips_the_weekend() {
+        // Saturday, January 11th 2025.
+        let scheduler = scheduler(at(11, 12, 0));
+        let now = scheduler.clock.now();
+
+        assert_eq!(scheduler.latest_trigger(&now), Some("gaming"));
+        assert_eq!(
+            scheduler.next_trigger(&now),
+            Some((at(11, 18, 0), "gaming"))
+        );
+        assert_eq!(
+            scheduler.next_trigger(&at(12, 18, 0)),
+            Some((at(13, 9, 0), "work"))
+        );
+    }
+}
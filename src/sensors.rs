@@ -0,0 +1,102 @@
+use crate::{config::Config, metrics::Metric};
+use std::fmt;
+use sysinfo::{Components, Disks};
+use tracing::warn;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SensorKind {
+    Temperature,
+    Disk,
+}
+
+impl fmt::Display for SensorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SensorKind::Temperature => "temperature",
+            SensorKind::Disk => "disk",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Sensor {
+    pub name: String,
+    pub kind: SensorKind,
+    /// The current reading, already formatted with its unit.
+    pub value: String,
+}
+
+/// The sensors found on this machine, to find the names to use in the config.
+pub struct SensorRegistry {
+    pub sensors: Vec<Sensor>,
+}
+
+impl SensorRegistry {
+    pub fn discover() -> Self {
+        let mut sensors = Vec::new();
+
+        for component in Components::new_with_refreshed_list().iter() {
+            sensors.push(Sensor {
+                name: component.label().to_string(),
+                kind: SensorKind::Temperature,
+                value: component
+                    .temperature()
+                    .map_or_else(|| "-".to_string(), |celsius| format!("{celsius:.1} °C")),
+            });
+        }
+
+        for disk in Disks::new_with_refreshed_list().list() {
+            let total = disk.total_space();
+            let used = total - disk.available_space();
+            sensors.push(Sensor {
+                name: disk.mount_point().display().to_string(),
+                kind: SensorKind::Disk,
+                value: format!(
+                    "{:.1} / {:.1} GB used",
+                    used as f64 / 1e9,
+                    total as f64 / 1e9
+                ),
+            });
+        }
+
+        Self { sensors }
+    }
+
+    /// Warns about `temperature` metrics whose sensor isn't there. Not an error, the sensor may
+    /// show up once a device is plugged in.
+    pub fn check_config(&self, config: &Config) {
+        for metric in config.metrics() {
+            let Metric::Temperature { sensor, .. } = metric else {
+                continue;
+            };
+
+            let found = self.sensors.iter().any(|known| {
+                known.kind == SensorKind::Temperature && known.name.contains(sensor.as_str())
+            });
+            if !found {
+                warn!("No temperature sensor matches {sensor:?}, see --list-sensors");
+            }
+        }
+    }
+
+    /// Prints the sensors as a table.
+    pub fn print(&self) {
+        let name_width = self
+            .sensors
+            .iter()
+            .map(|sensor| sensor.name.chars().count())
+            .max()
+            .unwrap_or_default()
+            .max("name".len());
+
+        println!("{:<name_width$}  {:<11}  value", "name", "type");
+        for sensor in &self.sensors {
+            println!(
+                "{:<name_width$}  {:<11}  {}",
+                sensor.name,
+                sensor.kind.to_string(),
+                sensor.value
+            );
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use crate::sink::{ControllerInfo, OpenRgbSink};
+use openrgb::data::Color;
+use std::{error::Error, fmt, sync::Mutex};
+
+#[derive(Debug, Clone)]
+pub struct ControllerDef {
+    pub name: String,
+    pub led_count: usize,
+}
+
+/// An in-memory stand-in for the OpenRGB server, which remembers the colors sent to each
+/// controller.
+pub struct ControllerSimulator {
+    controllers: Vec<ControllerDef>,
+    colors: Mutex<Vec<Vec<Color>>>,
+}
+
+#[derive(Debug)]
+pub struct UnknownControllerError(pub u32);
+
+impl fmt::Display for UnknownControllerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown controller {}", self.0)
+    }
+}
+
+impl Error for UnknownControllerError {}
+
+impl ControllerSimulator {
+    pub fn new(controllers: Vec<ControllerDef>) -> Self {
+        let colors = controllers
+            .iter()
+            .map(|controller| vec![Color::new(0, 0, 0); controller.led_count])
+            .collect();
+
+        Self {
+            controllers,
+            colors: Mutex::new(colors),
+        }
+    }
+
+    /// The colors last sent to the controller, black before any update.
+    pub fn last_colors(&self, controller_id: u32) -> Option<Vec<Color>> {
+        self.colors
+            .lock()
+            .unwrap()
+            .get(controller_id as usize)
+            .cloned()
+    }
+
+    fn controller(&self, controller_id: u32) -> Result<&ControllerDef, UnknownControllerError> {
+        self.controllers
+            .get(controller_id as usize)
+            .ok_or(UnknownControllerError(controller_id))
+    }
+}
+
+impl OpenRgbSink for ControllerSimulator {
+    type Error = UnknownControllerError;
+
+    async fn get_controller_count(&self) -> Result<u32, Self::Error> {
+        Ok(self.controllers.len() as u32)
+    }
+
+    async fn get_controller(&self, controller_id: u32) -> Result<ControllerInfo, Self::Error> {
+        let controller = self.controller(controller_id)?;
+
+        Ok(ControllerInfo {
+            name: controller.name.clone(),
+            led_count: controller.led_count,
+            // A single zone with every LED.
+            zone_led_counts: vec![controller.led_count],
+            zone_names: vec!["All".to_string()],
+        })
+    }
+
+    async fn update_leds(&self, controller_id: u32, colors: Vec<Color>) -> Result<(), Self::Error> {
+        self.controller(controller_id)?;
+        self.colors.lock().unwrap()[controller_id as usize] = colors;
+
+        Ok(())
+    }
+
+    async fn update_led(
+        &self,
+        controller_id: u32,
+        led_id: i32,
+        color: Color,
+    ) -> Result<(), Self::Error> {
+        self.controller(controller_id)?;
+        let mut colors = self.colors.lock().unwrap();
+        if let Some(led) = usize::try_from(led_id)
+            .ok()
+            .and_then(|led_id| colors[controller_id as usize].get_mut(led_id))
+        {
+            *led = color;
+        }
+
+        Ok(())
+    }
+
+    async fn update_zone_leds(
+        &self,
+        controller_id: u32,
+        zone_id: u32,
+        colors: Vec<Color>,
+    ) -> Result<(), Self::Error> {
+        self.controller(controller_id)?;
+        if zone_id == 0 {
+            self.colors.lock().unwrap()[controller_id as usize] = colors;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::{Config, ControllerConfig, Zone},
+        effect::Effect,
+        metrics::{Metric, MetricValues},
+        render::Renderer,
+    };
+    use std::{f32::consts::TAU, sync::Arc};
+
+    const LED_COUNT: usize = 10;
+
+    #[tokio::test]
+    async fn renders_a_sinusoidal_metric_into_the_simulator() {
+        let red = Color::new(255, 0, 0);
+        let config = Config {
+            controllers: vec![ControllerConfig {
+                name: "Strip".to_string(),
+                metric: Metric::CpuUsage,
+                zones: vec![Zone {
+                    name: None,
+                    led_count: None,
+                    metric: None,
+                    effect: Effect::Gradient {
+                        start_color: Color::new(0, 0, 0),
+                        end_color: red,
+                    },
+                }],
+                ..Default::default()
+            }],
+            ..Config::default()
+        };
+        let mut renderer = Renderer::new(config, Arc::default()).unwrap();
+        let simulator = ControllerSimulator::new(vec![ControllerDef {
+            name: "Strip".to_string(),
+            led_count: LED_COUNT,
+        }]);
+
+        for tick in 0..16 {
+            let value = 0.5 + 0.5 * (TAU * tick as f32 / 8.0).sin();
+            let metrics = MetricValues {
+                time_secs: tick as f64 * 0.5,
+                cpu_usage: value,
+                ..Default::default()
+            };
+            renderer.render_metrics(&simulator, metrics).await.unwrap();
+
+            let colors = simulator.last_colors(0).unwrap();
+            assert_eq!(colors.len(), LED_COUNT);
+            // Filled from the start, up to a partly lit LED at the value.
+            let filled = value * LED_COUNT as f32;
+            let lit = colors.iter().filter(|color| **color == red).count();
+            assert!(
+                (lit as f32 - filled).abs() < 1.0,
+                "tick {tick}: {lit} LEDs lit"
+            );
+            assert!(colors.windows(2).all(|pair| pair[0].r >= pair[1].r));
+            let red_sum: u32 = colors.iter().map(|color| color.r as u32).sum();
+            assert!(
+                (red_sum as f32 - filled * 255.0).abs() <= 1.0,
+                "tick {tick}: {red_sum} instead of {}",
+                filled * 255.0
+            );
+        }
+    }
+}
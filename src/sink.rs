@@ -0,0 +1,111 @@
+use crate::pool::Stream;
+use openrgb::{
+    data::{Color, Controller},
+    OpenRGB, OpenRGBError,
+};
+use std::{error::Error, future::Future};
+
+/// What the renderer needs to know about a controller.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControllerInfo {
+    pub name: String,
+    pub led_count: usize,
+    /// The LED count of each of the controller's zones, in LED order.
+    pub zone_led_counts: Vec<usize>,
+    /// The names of the zones, in the same order.
+    pub zone_names: Vec<String>,
+}
+
+impl ControllerInfo {
+    /// The LEDs of the zone called `name`, ignoring case.
+    pub fn zone_range(&self, name: &str) -> Option<std::ops::Range<usize>> {
+        let index = self
+            .zone_names
+            .iter()
+            .position(|zone_name| zone_name.eq_ignore_ascii_case(name))?;
+        let start = self.zone_led_counts[..index].iter().sum();
+
+        Some(start..start + self.zone_led_counts[index])
+    }
+}
+
+impl From<Controller> for ControllerInfo {
+    fn from(controller: Controller) -> Self {
+        Self {
+            led_count: controller.leds.len(),
+            zone_led_counts: controller
+                .zones
+                .iter()
+                .map(|zone| zone.leds_count as usize)
+                .collect(),
+            zone_names: controller.zones.into_iter().map(|zone| zone.name).collect(),
+            name: controller.name,
+        }
+    }
+}
+
+/// Where the renderer sends the colors to: the OpenRGB server, or a stand-in for it.
+pub trait OpenRgbSink {
+    type Error: Error + 'static;
+
+    fn get_controller_count(&self) -> impl Future<Output = Result<u32, Self::Error>> + Send;
+
+    fn get_controller(
+        &self,
+        controller_id: u32,
+    ) -> impl Future<Output = Result<ControllerInfo, Self::Error>> + Send;
+
+    fn update_leds(
+        &self,
+        controller_id: u32,
+        colors: Vec<Color>,
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send;
+
+    fn update_led(
+        &self,
+        controller_id: u32,
+        led_id: i32,
+        color: Color,
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send;
+
+    fn update_zone_leds(
+        &self,
+        controller_id: u32,
+        zone_id: u32,
+        colors: Vec<Color>,
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send;
+}
+
+impl OpenRgbSink for OpenRGB<Stream> {
+    type Error = OpenRGBError;
+
+    async fn get_controller_count(&self) -> Result<u32, Self::Error> {
+        OpenRGB::get_controller_count(self).await
+    }
+
+    async fn get_controller(&self, controller_id: u32) -> Result<ControllerInfo, Self::Error> {
+        Ok(OpenRGB::get_controller(self, controller_id).await?.into())
+    }
+
+    async fn update_leds(&self, controller_id: u32, colors: Vec<Color>) -> Result<(), Self::Error> {
+        OpenRGB::update_leds(self, controller_id, colors).await
+    }
+
+    async fn update_led(
+        &self,
+        controller_id: u32,
+        led_id: i32,
+        color: Color,
+    ) -> Result<(), Self::Error> {
+        OpenRGB::update_led(self, controller_id, led_id, color).await
+    }
+
+    async fn update_zone_leds(
+        &self,
+        controller_id: u32,
+        zone_id: u32,
+        colors: Vec<Color>,
+    ) -> Result<(), Self::Error> {
+        OpenRGB::update_zone_leds(self, controller_id, zone_id, colors).await
+    }
+}
@@ -0,0 +1,85 @@
+use crate::metrics::{Metric, TemperatureUnit};
+use openrgb::data::Color;
+use serde::Serialize;
+use std::sync::{Arc, RwLock};
+
+/// Bumped whenever a field changes or goes away, adding fields doesn't.
+pub const STATUS_VERSION: u32 = 1;
+
+/// What the daemon is doing, as reported by the control socket's `status` command.
+#[derive(Debug, Clone, Serialize)]
+pub struct Status {
+    pub version: u32,
+    /// Empty while connecting.
+    pub connected_servers: Vec<String>,
+    pub active_profile: Option<String>,
+    pub controllers: Vec<ControllerStatus>,
+    /// The value of every referenced metric on the last frame, `None` when unavailable.
+    pub metrics: Vec<MetricStatus>,
+    /// The unit of the `temperature` of the metrics.
+    pub temperature_unit: TemperatureUnit,
+}
+
+impl Default for Status {
+    fn default() -> Self {
+        Self {
+            version: STATUS_VERSION,
+            connected_servers: Vec::new(),
+            active_profile: None,
+            controllers: Vec::new(),
+            metrics: Vec::new(),
+            temperature_unit: TemperatureUnit::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ControllerStatus {
+    pub id: u32,
+    pub name: String,
+    pub led_count: usize,
+    /// `None` when the client left the controller alone on the last frame.
+    pub last_written: Option<WrittenColors>,
+}
+
+/// A summary of the colors last written to a controller.
+#[derive(Debug, Clone, Serialize)]
+pub struct WrittenColors {
+    /// `#RRGGBB`.
+    pub average_color: String,
+    /// LEDs that aren't black.
+    pub lit_leds: usize,
+}
+
+impl WrittenColors {
+    pub fn new(colors: &[Color]) -> Self {
+        let count = colors.len().max(1) as u64;
+        let sum = colors.iter().fold([0u64; 3], |sum, color| {
+            [
+                sum[0] + color.r as u64,
+                sum[1] + color.g as u64,
+                sum[2] + color.b as u64,
+            ]
+        });
+        let [r, g, b] = sum.map(|channel| channel / count);
+
+        Self {
+            average_color: format!("#{r:02X}{g:02X}{b:02X}"),
+            lit_leds: colors
+                .iter()
+                .filter(|color| color.r > 0 || color.g > 0 || color.b > 0)
+                .count(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricStatus {
+    pub metric: Metric,
+    pub value: Option<f32>,
+    /// The sensor reading of a `temperature` metric, in the status' `temperature_unit`.
+    pub temperature: Option<f32>,
+}
+
+/// Written by the renderer once per frame, read by the control socket.
+pub type SharedStatus = Arc<RwLock<Status>>;
@@ -0,0 +1,145 @@
+//! `--stress-test`: random colors to every controller as fast as the server takes them, to see
+//! whether the connection holds up under load.
+
+use crate::{benchmark::p99_latency, pool::Stream};
+use openrgb::{data::Color, OpenRGB};
+use std::{error::Error, time::Duration};
+use tokio::time::Instant;
+use tracing::warn;
+
+/// The test fails when more of the updates than this fail.
+const MAX_ERROR_RATE: f64 = 0.01;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct StressResult {
+    pub successes: u64,
+    pub failures: u64,
+}
+
+impl StressResult {
+    pub fn error_rate(&self) -> f64 {
+        self.failures as f64 / (self.successes + self.failures).max(1) as f64
+    }
+}
+
+/// Sends random colors to every controller back to back for `duration`, printing the stats of
+/// every second, then puts their colors back. Fails when over 1 % of the updates failed.
+pub async fn run(
+    client: &OpenRGB<Stream>,
+    duration: Duration,
+) -> Result<StressResult, Box<dyn Error>> {
+    let started = Instant::now();
+    run_until(client, |_| started.elapsed() >= duration).await
+}
+
+/// Like `run`, but sends rounds of updates until `done` with the results so far.
+async fn run_until(
+    client: &OpenRGB<Stream>,
+    done: impl Fn(&StressResult) -> bool,
+) -> Result<StressResult, Box<dyn Error>> {
+    let mut controllers = Vec::new();
+    for controller_id in 0..client.get_controller_count().await? {
+        let controller = client.get_controller(controller_id).await?;
+        if !controller.leds.is_empty() {
+            controllers.push((controller_id, controller));
+        }
+    }
+    if controllers.is_empty() {
+        return Err("the server has no controllers with LEDs".into());
+    }
+
+    let mut result = StressResult::default();
+    let mut second = Second::new();
+    while !done(&result) {
+        for (controller_id, controller) in &controllers {
+            let colors = (0..controller.leds.len())
+                .map(|_| Color::new(fastrand::u8(..), fastrand::u8(..), fastrand::u8(..)))
+                .collect();
+
+            let call_started = Instant::now();
+            match client.update_leds(*controller_id, colors).await {
+                Ok(()) => result.successes += 1,
+                Err(_) => result.failures += 1,
+            }
+            second.latencies.push(call_started.elapsed());
+        }
+
+        if second.started.elapsed() >= Duration::from_secs(1) {
+            second.print(result.failures);
+            second = Second::new();
+        }
+    }
+
+    // Still summed up when the connection broke down.
+    for (controller_id, controller) in controllers {
+        if let Err(e) = client.update_leds(controller_id, controller.colors).await {
+            warn!("Failed to put the colors of {} back: {e}", controller.name);
+        }
+    }
+
+    println!(
+        "{} updates, {} failed ({:.2} %)",
+        result.successes + result.failures,
+        result.failures,
+        result.error_rate() * 100.0
+    );
+    if result.error_rate() > MAX_ERROR_RATE {
+        return Err(format!(
+            "{:.2} % of the updates failed, more than {} %",
+            result.error_rate() * 100.0,
+            MAX_ERROR_RATE * 100.0
+        )
+        .into());
+    }
+
+    Ok(result)
+}
+
+/// The updates of the second the next stats line is about.
+struct Second {
+    started: Instant,
+    latencies: Vec<Duration>,
+}
+
+impl Second {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            latencies: Vec::new(),
+        }
+    }
+
+    fn print(&mut self, failures: u64) {
+        self.latencies.sort();
+        let rate = self.latencies.len() as f64 / self.started.elapsed().as_secs_f64();
+        println!(
+            "rate: {rate:.0}/s  errors: {failures}  p99: {:.1} ms",
+            p99_latency(&self.latencies).as_secs_f64() * 1000.0
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mock_server::MockServer, pool::ConnectionPool};
+
+    #[tokio::test]
+    async fn counts_every_update_as_a_success() {
+        let server = MockServer::start(3, 1).await;
+        let pool = ConnectionPool::new([server.address()], "test");
+        let client = pool.acquire(0).await.unwrap();
+
+        // One controller, so one update per round.
+        let result = run_until(&client, |result| result.successes + result.failures >= 1000)
+            .await
+            .unwrap();
+        assert_eq!(
+            result,
+            StressResult {
+                successes: 1000,
+                failures: 0
+            }
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! Locks the animations of machines sharing a desk together over UDP.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    net::{SocketAddr, UdpSocket as StdUdpSocket},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{net::UdpSocket, time::Instant};
+use tracing::{debug, info};
+
+/// Followers go back to their own clock after missing this many packets in a row.
+const MAX_MISSED_PACKETS: u32 = 3;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncConfig {
+    pub mode: SyncMode,
+    /// Where the leader sends its packets to, e.g. a broadcast address, and followers listen on.
+    pub address: SocketAddr,
+    /// Followers only follow leaders of the same group.
+    pub group: String,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncMode {
+    Leader,
+    Follower,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PhasePacket {
+    group: String,
+    /// The leader's animation clock, in seconds.
+    wall_clock_phase: f64,
+}
+
+struct ReceivedPhase {
+    phase: f64,
+    received: Instant,
+    /// The time between the last two packets, roughly the leader's frame interval.
+    interval: Option<Duration>,
+}
+
+/// Shares the clock time-based effects are animated with, see `MetricValues::time_secs`. The
+/// leader sends its clock every frame, followers animate with it instead of their own.
+pub struct ControllerGroupSynchronizer {
+    role: Role,
+}
+
+enum Role {
+    Leader {
+        socket: UdpSocket,
+        address: SocketAddr,
+        group: String,
+    },
+    Follower {
+        latest: Arc<Mutex<Option<ReceivedPhase>>>,
+        following: bool,
+        /// The leader's frame interval until there are two packets to measure it from.
+        frame_period: Duration,
+    },
+}
+
+impl ControllerGroupSynchronizer {
+    /// Binds the socket, followers start listening right away. Followers assume the leader renders
+    /// every `frame_period` like they do until they measured it. Needs a Tokio runtime.
+    pub fn new(config: &SyncConfig, frame_period: Duration) -> io::Result<Self> {
+        let role = match config.mode {
+            SyncMode::Leader => {
+                let bind_address: SocketAddr = if config.address.is_ipv4() {
+                    ([0, 0, 0, 0], 0).into()
+                } else {
+                    ([0u16; 8], 0).into()
+                };
+                let socket = StdUdpSocket::bind(bind_address)?;
+                socket.set_broadcast(true)?;
+                socket.set_nonblocking(true)?;
+
+                Role::Leader {
+                    socket: UdpSocket::from_std(socket)?,
+                    address: config.address,
+                    group: config.group.clone(),
+                }
+            }
+            SyncMode::Follower => {
+                let socket = StdUdpSocket::bind(config.address)?;
+                socket.set_nonblocking(true)?;
+                let socket = UdpSocket::from_std(socket)?;
+
+                let latest = Arc::new(Mutex::new(None));
+                tokio::spawn(receive_phases(socket, config.group.clone(), latest.clone()));
+
+                Role::Follower {
+                    latest,
+                    following: false,
+                    frame_period,
+                }
+            }
+        };
+
+        Ok(Self { role })
+    }
+
+    /// The clock to animate this frame with, given the local one. Sends it to the followers on the
+    /// leader.
+    pub fn sync(&mut self, local_time_secs: f64) -> f64 {
+        match &mut self.role {
+            Role::Leader {
+                socket,
+                address,
+                group,
+            } => {
+                let packet = PhasePacket {
+                    group: group.clone(),
+                    wall_clock_phase: local_time_secs,
+                };
+                let packet = serde_json::to_vec(&packet).unwrap();
+                // A lost packet is what the followers' fallback is for.
+                if let Err(e) = socket.try_send_to(&packet, *address) {
+                    debug!("Failed to send the phase to {address}: {e}");
+                }
+
+                local_time_secs
+            }
+            Role::Follower {
+                latest,
+                following,
+                frame_period,
+            } => {
+                let phase = latest.lock().unwrap().as_ref().and_then(|latest| {
+                    let since = latest.received.elapsed();
+                    let interval = latest.interval.unwrap_or(*frame_period);
+                    let missed = if interval.is_zero() {
+                        0
+                    } else {
+                        (since.as_secs_f64() / interval.as_secs_f64()) as u32
+                    };
+                    (missed < MAX_MISSED_PACKETS).then(|| latest.phase + since.as_secs_f64())
+                });
+
+                if phase.is_some() != *following {
+                    *following = phase.is_some();
+                    if *following {
+                        info!("Following the leader's phase");
+                    } else {
+                        info!("Lost the leader, animating with the local clock");
+                    }
+                }
+
+                phase.unwrap_or(local_time_secs)
+            }
+        }
+    }
+}
+
+async fn receive_phases(
+    socket: UdpSocket,
+    group: String,
+    latest: Arc<Mutex<Option<ReceivedPhase>>>,
+) {
+    let mut buffer = [0; 512];
+    loop {
+        let length = match socket.recv_from(&mut buffer).await {
+            Ok((length, _)) => length,
+            Err(e) => {
+                debug!("Failed to receive a phase: {e}");
+                continue;
+            }
+        };
+        let Ok(packet) = serde_json::from_slice::<PhasePacket>(&buffer[..length]) else {
+            continue;
+        };
+        if packet.group != group {
+            continue;
+        }
+
+        let received = Instant::now();
+        let mut latest = latest.lock().unwrap();
+        let interval = latest
+            .as_ref()
+            .map(|previous| received.duration_since(previous.received));
+        *latest = Some(ReceivedPhase {
+            phase: packet.wall_clock_phase,
+            received,
+            interval,
+        });
+    }
+}
@@ -0,0 +1,44 @@
+//! Notifications to systemd for `Type=notify` services. They do nothing without the `systemd`
+//! feature, or when not running under systemd.
+
+#[cfg(feature = "systemd")]
+use sd_notify::NotifyState;
+
+/// Connected and the first frame is rendered.
+pub fn notify_ready() {
+    #[cfg(feature = "systemd")]
+    notify(&[NotifyState::Ready]);
+}
+
+/// The render loop is still making progress.
+pub fn notify_watchdog() {
+    #[cfg(feature = "systemd")]
+    notify(&[NotifyState::Watchdog]);
+}
+
+pub fn notify_stopping() {
+    #[cfg(feature = "systemd")]
+    notify(&[NotifyState::Stopping]);
+}
+
+/// Gives up on the service: a last watchdog ping with a `dying` status before exiting.
+pub fn notify_dying() {
+    #[cfg(feature = "systemd")]
+    notify(&[NotifyState::Watchdog, NotifyState::Status("dying")]);
+}
+
+/// Asks systemd to handle the service as if the watchdog timed out.
+pub fn notify_watchdog_failure(status: &str) {
+    #[cfg(feature = "systemd")]
+    notify(&[NotifyState::Status(status), NotifyState::WatchdogTrigger]);
+
+    #[cfg(not(feature = "systemd"))]
+    let _ = status;
+}
+
+#[cfg(feature = "systemd")]
+fn notify(states: &[NotifyState]) {
+    if let Err(e) = sd_notify::notify(false, states) {
+        tracing::warn!("Failed to notify systemd: {e}");
+    }
+}
@@ -0,0 +1,68 @@
+use crate::pool::Stream;
+use openrgb::{data::Color, OpenRGB};
+use std::{error::Error, time::Duration};
+use tracing::info;
+
+const STEP_COLOR: Color = Color::new(255, 255, 255);
+const STEP_DURATION: Duration = Duration::from_millis(50);
+const FLASH_COLORS: [(&str, Color); 3] = [
+    ("red", Color::new(255, 0, 0)),
+    ("green", Color::new(0, 255, 0)),
+    ("blue", Color::new(0, 0, 255)),
+];
+const FLASH_DURATION: Duration = Duration::from_millis(700);
+
+/// Steps a white LED through every LED of every controller in index order, then flashes them all
+/// red, green and blue, to check the LED counts, their order and the channel order. Puts the
+/// colors back afterwards, even when the pattern fails halfway.
+pub async fn run(client: &OpenRGB<Stream>) -> Result<(), Box<dyn Error>> {
+    let controller_count = client.get_controller_count().await?;
+
+    let mut original_colors = Vec::new();
+    for controller_id in 0..controller_count {
+        let controller = client.get_controller(controller_id).await?;
+        original_colors.push((controller_id, controller.name, controller.colors));
+    }
+
+    let result = show_pattern(client, &original_colors).await;
+
+    for (controller_id, _, colors) in original_colors {
+        client.update_leds(controller_id, colors).await?;
+    }
+
+    result
+}
+
+async fn show_pattern(
+    client: &OpenRGB<Stream>,
+    controllers: &[(u32, String, Vec<Color>)],
+) -> Result<(), Box<dyn Error>> {
+    let black = Color::new(0, 0, 0);
+
+    for (controller_id, name, colors) in controllers {
+        let led_count = colors.len();
+        info!("Stepping through the {led_count} LEDs of controller {controller_id}: {name}");
+
+        for led in 0..led_count {
+            let mut colors = vec![black; led_count];
+            colors[led] = STEP_COLOR;
+            client.update_leds(*controller_id, colors).await?;
+            tokio::time::sleep(STEP_DURATION).await;
+        }
+        client
+            .update_leds(*controller_id, vec![black; led_count])
+            .await?;
+    }
+
+    for (color_name, color) in FLASH_COLORS {
+        info!("Flashing every controller {color_name}");
+        for (controller_id, _, colors) in controllers {
+            client
+                .update_leds(*controller_id, vec![color; colors.len()])
+                .await?;
+        }
+        tokio::time::sleep(FLASH_DURATION).await;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,257 @@
+use crate::{
+    color::{lerp_color, ColorSpace},
+    effect::Effect,
+    metrics::MetricValues,
+};
+use openrgb::data::Color;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EasingFunction {
+    Linear,
+    EaseInCubic,
+    EaseOutCubic,
+    #[default]
+    EaseInOutCubic,
+}
+
+impl EasingFunction {
+    /// Maps the linear progress `t`, from 0.0 to 1.0, to the eased progress.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+
+        match self {
+            EasingFunction::Linear => t,
+            EasingFunction::EaseInCubic => t * t * t,
+            EasingFunction::EaseOutCubic => 1.0 - (1.0 - t).powi(3),
+            EasingFunction::EaseInOutCubic => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+        }
+    }
+}
+
+/// Fades a controller's LEDs to new colors instead of cutting to them. The fade advances once per
+/// sample tick, so `duration_ms` should span a few ticks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmoothTransition {
+    pub duration_ms: u64,
+    #[serde(default)]
+    pub easing: EasingFunction,
+}
+
+pub struct TransitionState {
+    from: Vec<Color>,
+    to: Vec<Color>,
+    started: Instant,
+}
+
+impl TransitionState {
+    pub fn new() -> Self {
+        Self {
+            from: vec![],
+            to: vec![],
+            started: Instant::now(),
+        }
+    }
+
+    /// Returns the colors to show now, restarting the fade whenever the target changes.
+    pub fn update(&mut self, transition: &SmoothTransition, target: Vec<Color>) -> Vec<Color> {
+        let now = Instant::now();
+
+        if target != self.to {
+            let current = self.colors_at(transition, now);
+            self.from = if current.len() == target.len() {
+                current
+            } else {
+                target.clone()
+            };
+            self.to = target;
+            self.started = now;
+        }
+
+        self.colors_at(transition, now)
+    }
+
+    fn colors_at(&self, transition: &SmoothTransition, now: Instant) -> Vec<Color> {
+        let duration = Duration::from_millis(transition.duration_ms);
+        if duration.is_zero() || self.from.len() != self.to.len() {
+            return self.to.clone();
+        }
+
+        let t = now.duration_since(self.started).as_secs_f32() / duration.as_secs_f32();
+        let t = transition.easing.apply(t);
+
+        self.from
+            .iter()
+            .zip(&self.to)
+            .map(|(from, to)| lerp_color(t, from, to))
+            .collect()
+    }
+}
+
+impl Default for TransitionState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How a zone goes over to another effect, e.g. when the active profile changes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EffectTransition {
+    /// Blends the outgoing and incoming effect's colors LED by LED.
+    Fade { duration_ms: u64 },
+    /// Sweeps the incoming effect over the LEDs from one end.
+    Wipe {
+        #[serde(default)]
+        direction: WipeDirection,
+        duration_ms: u64,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WipeDirection {
+    /// From the first LED to the last.
+    #[default]
+    Forward,
+    Backward,
+}
+
+impl EffectTransition {
+    pub fn duration(&self) -> Duration {
+        match self {
+            EffectTransition::Fade { duration_ms } | EffectTransition::Wipe { duration_ms, .. } => {
+                Duration::from_millis(*duration_ms)
+            }
+        }
+    }
+
+    /// The colors `progress` of the way, from 0.0 to 1.0, from `outgoing` to `incoming`.
+    pub fn blend(&self, outgoing: &[Color], incoming: Vec<Color>, progress: f32) -> Vec<Color> {
+        if outgoing.len() != incoming.len() {
+            return incoming;
+        }
+        let progress = progress.clamp(0.0, 1.0);
+
+        match self {
+            EffectTransition::Fade { .. } => outgoing
+                .iter()
+                .zip(&incoming)
+                .map(|(outgoing, incoming)| lerp_color(progress, outgoing, incoming))
+                .collect(),
+            EffectTransition::Wipe { direction, .. } => {
+                let wiped = (progress * incoming.len() as f32).round() as usize;
+                let mut colors = outgoing.to_vec();
+                match direction {
+                    WipeDirection::Forward => colors[..wiped].copy_from_slice(&incoming[..wiped]),
+                    WipeDirection::Backward => {
+                        let start = colors.len() - wiped;
+                        colors[start..].copy_from_slice(&incoming[start..])
+                    }
+                }
+                colors
+            }
+        }
+    }
+}
+
+/// The effect of a zone, going over to the next one with an `EffectTransition` instead of cutting
+/// to it.
+pub struct EffectStateMachine {
+    current: Effect,
+    next: Option<Effect>,
+    transition: EffectTransition,
+    /// How far into the transition to `next` it is.
+    elapsed: Duration,
+}
+
+impl EffectStateMachine {
+    pub fn new(effect: Effect, transition: EffectTransition) -> Self {
+        Self {
+            current: effect,
+            next: None,
+            transition,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    /// Starts the transition to `effect`. A transition still going on is cut short.
+    pub fn switch_to(&mut self, effect: Effect) {
+        if let Some(next) = self.next.take() {
+            self.current = next;
+        }
+        self.next = Some(effect);
+        self.elapsed = Duration::ZERO;
+    }
+
+    /// Advances the transition by the `elapsed` time since the last tick and renders the zone.
+    pub fn tick(
+        &mut self,
+        elapsed: Duration,
+        value: f32,
+        metrics: &MetricValues,
+        led_count: usize,
+        color_space: ColorSpace,
+    ) -> Vec<Color> {
+        let Some(next) = &self.next else {
+            return self.current.render(value, metrics, led_count, color_space);
+        };
+
+        self.elapsed += elapsed;
+        let duration = self.transition.duration();
+        if self.elapsed >= duration {
+            self.current = self.next.take().unwrap();
+            return self.current.render(value, metrics, led_count, color_space);
+        }
+
+        let outgoing = self.current.render(value, metrics, led_count, color_space);
+        let incoming = next.render(value, metrics, led_count, color_space);
+        let progress = self.elapsed.as_secs_f32() / duration.as_secs_f32();
+
+        self.transition.blend(&outgoing, incoming, progress)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ease_in_out_cubic_is_halfway_at_the_midpoint() {
+        let easing = EasingFunction::EaseInOutCubic;
+        let from = Color::new(0, 0, 0);
+        let to = Color::new(255, 255, 255);
+
+        assert_eq!(easing.apply(0.5), 0.5);
+        assert_eq!(
+            lerp_color(easing.apply(0.5), &from, &to),
+            Color::new(128, 128, 128)
+        );
+        // Slow at the ends, fast in the middle.
+        assert!(easing.apply(0.25) < 0.25);
+        assert!(easing.apply(0.75) > 0.75);
+        assert!(easing.apply(0.55) - easing.apply(0.45) > 0.1);
+        assert_eq!(easing.apply(0.0), 0.0);
+        assert_eq!(easing.apply(1.0), 1.0);
+    }
+
+    #[test]
+    fn fade_is_an_even_mix_halfway() {
+        let fade = EffectTransition::Fade { duration_ms: 1000 };
+        let outgoing = [Color::new(200, 0, 100), Color::new(0, 0, 0)];
+        let incoming = vec![Color::new(0, 200, 100), Color::new(100, 50, 250)];
+
+        assert_eq!(
+            fade.blend(&outgoing, incoming, 0.5),
+            [Color::new(100, 100, 100), Color::new(50, 25, 125)]
+        );
+    }
+}
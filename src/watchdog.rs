@@ -0,0 +1,48 @@
+use crate::systemd;
+use std::{
+    process,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+use tracing::error;
+
+/// Exit code when the watchdog fires, so supervisors can tell it apart from a clean exit.
+const EXIT_CODE: i32 = 2;
+
+/// Exits the process when it isn't kicked for `timeout`. Runs on its own thread, outside the
+/// runtime, so it still fires when a call blocks a runtime thread forever, e.g. on a wedged USB
+/// device, which the health monitor's cancellation can't get out of.
+#[derive(Clone)]
+pub struct Watchdog {
+    last_kick: Arc<Mutex<Instant>>,
+}
+
+impl Watchdog {
+    pub fn spawn(timeout: Duration) -> Self {
+        let watchdog = Self {
+            last_kick: Arc::new(Mutex::new(Instant::now())),
+        };
+
+        let last_kick = watchdog.last_kick.clone();
+        thread::spawn(move || loop {
+            thread::sleep(timeout / 4);
+
+            let since_kick = last_kick.lock().unwrap().elapsed();
+            if since_kick >= timeout {
+                error!(
+                    "Render loop made no progress for {} seconds, exiting",
+                    since_kick.as_secs()
+                );
+                systemd::notify_watchdog_failure("Render loop hung");
+                process::exit(EXIT_CODE);
+            }
+        });
+
+        watchdog
+    }
+
+    pub fn kick(&self) {
+        *self.last_kick.lock().unwrap() = Instant::now();
+    }
+}
@@ -0,0 +1,144 @@
+use crate::{config::Config, daemon};
+use std::{error::Error, ffi::OsString, thread, time::Duration};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+use windows_service::{
+    define_windows_service,
+    service::{
+        ServiceAccess, ServiceControl, ServiceControlAccept, ServiceErrorControl, ServiceExitCode,
+        ServiceInfo, ServiceStartType, ServiceState, ServiceStatus, ServiceType,
+    },
+    service_control_handler::{self, ServiceControlHandlerResult, ServiceStatusHandle},
+    service_dispatcher,
+    service_manager::{ServiceManager, ServiceManagerAccess},
+};
+
+const SERVICE_NAME: &str = "main_pc_2_openrgb_client";
+const SERVICE_DISPLAY_NAME: &str = "Main PC 2 OpenRGB Client";
+const SERVICE_DESCRIPTION: &str = "Drives the OpenRGB controllers from the system metrics.";
+
+pub fn install() -> Result<(), Box<dyn Error>> {
+    let manager = ServiceManager::local_computer(
+        None::<&str>,
+        ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE,
+    )?;
+
+    let service_info = ServiceInfo {
+        name: OsString::from(SERVICE_NAME),
+        display_name: OsString::from(SERVICE_DISPLAY_NAME),
+        service_type: ServiceType::OWN_PROCESS,
+        start_type: ServiceStartType::AutoStart,
+        error_control: ServiceErrorControl::Normal,
+        executable_path: std::env::current_exe()?,
+        launch_arguments: vec![OsString::from("service"), OsString::from("run")],
+        dependencies: vec![],
+        account_name: None, // LocalSystem
+        account_password: None,
+    };
+    let service = manager.create_service(&service_info, ServiceAccess::CHANGE_CONFIG)?;
+    service.set_description(SERVICE_DESCRIPTION)?;
+
+    eventlog::register(SERVICE_NAME)?;
+
+    println!("Service {SERVICE_NAME} installed");
+
+    Ok(())
+}
+
+pub fn uninstall() -> Result<(), Box<dyn Error>> {
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)?;
+    let service = manager.open_service(
+        SERVICE_NAME,
+        ServiceAccess::QUERY_STATUS | ServiceAccess::STOP | ServiceAccess::DELETE,
+    )?;
+
+    // Deletion only completes once the service has stopped.
+    service.delete()?;
+    if service.query_status()?.current_state != ServiceState::Stopped {
+        service.stop()?;
+    }
+    while service.query_status()?.current_state != ServiceState::Stopped {
+        thread::sleep(Duration::from_millis(500));
+    }
+
+    eventlog::deregister(SERVICE_NAME)?;
+
+    println!("Service {SERVICE_NAME} uninstalled");
+
+    Ok(())
+}
+
+/// Hands the process over to the service control manager, returns once the service stopped.
+pub fn run() -> Result<(), Box<dyn Error>> {
+    service_dispatcher::start(SERVICE_NAME, ffi_service_main)?;
+
+    Ok(())
+}
+
+define_windows_service!(ffi_service_main, service_main);
+
+fn service_main(_arguments: Vec<OsString>) {
+    // There's no console, so everything goes to the event log. Without a tracing subscriber the
+    // events are forwarded to it as `log` records.
+    if eventlog::init(SERVICE_NAME, log::Level::Info).is_err() {
+        return;
+    }
+
+    if let Err(e) = run_service() {
+        error!("Service failed: {e}");
+    }
+}
+
+fn run_service() -> Result<(), Box<dyn Error>> {
+    let shutdown = CancellationToken::new();
+
+    let status_handle = service_control_handler::register(SERVICE_NAME, {
+        let shutdown = shutdown.clone();
+        move |control| match control {
+            ServiceControl::Stop | ServiceControl::Shutdown => {
+                info!("Shutting down...");
+                shutdown.cancel();
+
+                ServiceControlHandlerResult::NoError
+            }
+            ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+            _ => ServiceControlHandlerResult::NotImplemented,
+        }
+    })?;
+
+    set_status(
+        &status_handle,
+        ServiceState::Running,
+        ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+        0,
+    )?;
+
+    let result = tokio::runtime::Runtime::new()?
+        .block_on(async { daemon::run(Config::load()?, shutdown).await });
+
+    set_status(
+        &status_handle,
+        ServiceState::Stopped,
+        ServiceControlAccept::empty(),
+        if result.is_ok() { 0 } else { 1 },
+    )?;
+
+    result
+}
+
+fn set_status(
+    status_handle: &ServiceStatusHandle,
+    state: ServiceState,
+    controls_accepted: ServiceControlAccept,
+    exit_code: u32,
+) -> windows_service::Result<()> {
+    status_handle.set_service_status(ServiceStatus {
+        service_type: ServiceType::OWN_PROCESS,
+        current_state: state,
+        controls_accepted,
+        exit_code: ServiceExitCode::Win32(exit_code),
+        checkpoint: 0,
+        wait_hint: Duration::default(),
+        process_id: None,
+    })
+}
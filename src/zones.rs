@@ -0,0 +1,171 @@
+use crate::{
+    config::{Config, ControllerConfig},
+    pool::Stream,
+};
+use openrgb::{OpenRGB, OpenRGBError};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+/// The protocol version the resize zone packet came with, OpenRGB has had it from the start.
+const RESIZE_ZONE_PROTOCOL_VERSION: u32 = 0;
+
+/// Sets the LED count of a resizable zone, e.g. an addressable strip whose length OpenRGB doesn't
+/// know.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ZoneResize {
+    /// The zone's index on the controller.
+    pub zone: u32,
+    pub led_count: u32,
+}
+
+/// Applies every controller's `resize_zone` directives, and with `resize_zones` the `led_count` of
+/// its zones, where the zone doesn't have the requested size yet. Sizes outside the zone's limits
+/// are logged and skipped.
+pub async fn resize_zones(client: &OpenRGB<Stream>, config: &Config) -> Result<(), OpenRGBError> {
+    let controller_count = client.get_controller_count().await?;
+    for controller_id in 0..controller_count {
+        let controller = client.get_controller(controller_id).await?;
+        let Some(mapping) = find_mapping(config, &controller.name) else {
+            continue;
+        };
+
+        for resize in &mapping.resize_zone {
+            resize_zone_if_supported(client, controller_id, resize.zone, resize.led_count).await?;
+        }
+
+        if mapping.resize_zones {
+            // A zone with a name is the controller's zone by that name, the others go by position.
+            for (index, zone) in mapping.zones.iter().enumerate() {
+                let Some(led_count) = zone.led_count else {
+                    continue;
+                };
+                let zone_index = match &zone.name {
+                    Some(name) => match controller
+                        .zones
+                        .iter()
+                        .position(|zone| zone.name.eq_ignore_ascii_case(name))
+                    {
+                        Some(zone_index) => zone_index,
+                        None => continue,
+                    },
+                    None => index,
+                };
+
+                resize_zone_if_supported(
+                    client,
+                    controller_id,
+                    zone_index as u32,
+                    led_count as u32,
+                )
+                .await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Resizes a zone to `target_size` LEDs, unless it has that size already or can't take it, e.g. a
+/// zone with a fixed size or a server whose protocol version has no resizing. Returns whether the
+/// zone was resized.
+pub async fn resize_zone_if_supported(
+    client: &OpenRGB<Stream>,
+    controller_id: u32,
+    zone_index: u32,
+    target_size: u32,
+) -> Result<bool, OpenRGBError> {
+    let protocol_version = client.get_protocol_version();
+    if !supports(protocol_version, RESIZE_ZONE_PROTOCOL_VERSION) {
+        warn!("Can't resize zones, protocol version {protocol_version} has no resizing");
+        return Ok(false);
+    }
+
+    let controller = client.get_controller(controller_id).await?;
+    let Some(zone) = controller.zones.get(zone_index as usize) else {
+        warn!(
+            "Can't resize zone {zone_index} of {}, it only has {} zones",
+            controller.name,
+            controller.zones.len()
+        );
+        return Ok(false);
+    };
+    if zone.leds_count == target_size {
+        return Ok(false);
+    }
+    if !(zone.leds_min..=zone.leds_max).contains(&target_size) {
+        warn!(
+            "Can't resize zone {} of {} to {target_size} LEDs, it takes {} to {}",
+            zone.name, controller.name, zone.leds_min, zone.leds_max
+        );
+        return Ok(false);
+    }
+
+    info!(
+        "Resizing zone {} of {} from {} to {target_size} LEDs",
+        zone.name, controller.name, zone.leds_count
+    );
+    client
+        .resize_zone(controller_id, zone_index as i32, target_size as i32)
+        .await?;
+
+    Ok(true)
+}
+
+/// Whether the negotiated `protocol_version` has a packet that came with `since`.
+fn supports(protocol_version: u32, since: u32) -> bool {
+    protocol_version >= since
+}
+
+/// The mapping of `name` in the base config, or else in any profile, since zone sizes don't change
+/// with the profile.
+fn find_mapping<'a>(config: &'a Config, name: &str) -> Option<&'a ControllerConfig> {
+    config
+        .controllers
+        .iter()
+        .chain(
+            config
+                .profiles
+                .values()
+                .flat_map(|profile| &profile.controllers),
+        )
+        .find(|mapping| {
+            mapping.name == name && (!mapping.resize_zone.is_empty() || mapping.resize_zones)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        mock_server::{MockServer, RESIZE_ZONE, ZONE_LEDS_MAX, ZONE_LED_COUNT},
+        pool::ConnectionPool,
+    };
+
+    #[tokio::test]
+    async fn sends_the_resize_packet_only_for_a_new_size_within_the_limits() {
+        let server = MockServer::start(3, 1).await;
+        let pool = ConnectionPool::new([server.address()], "test");
+        let client = pool.acquire(0).await.unwrap();
+
+        assert!(!resize_zone_if_supported(&client, 0, 0, ZONE_LED_COUNT)
+            .await
+            .unwrap());
+        assert!(!resize_zone_if_supported(&client, 0, 0, ZONE_LEDS_MAX + 1)
+            .await
+            .unwrap());
+        assert!(!server
+            .packets()
+            .iter()
+            .any(|packet| packet.packet_id == RESIZE_ZONE));
+
+        assert!(resize_zone_if_supported(&client, 0, 0, 20).await.unwrap());
+        let resizes: Vec<Vec<u8>> = server
+            .packets()
+            .into_iter()
+            .filter(|packet| packet.packet_id == RESIZE_ZONE)
+            .map(|packet| packet.data)
+            .collect();
+        // The zone index, then the new size.
+        assert_eq!(resizes, [[0, 0, 0, 0, 20, 0, 0, 0]]);
+    }
+}